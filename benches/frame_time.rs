@@ -0,0 +1,50 @@
+//! Frame time with 1000 creatures × 8 limbs × 16 segments: oscillators
+//! ticking, limbs posed and transforms propagated, headless.
+//!
+//! ```sh
+//! cargo bench --bench frame_time
+//! ```
+
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::time::Duration;
+
+use creature_synth::benchmark_creatures_plan;
+use creature_synth::creature::{spawn_creatures, CreatureNames};
+use creature_synth::limb::{animate_limb_segments, LimbAssetStore};
+use creature_synth::oscillator::oscillator_tick;
+
+const CREATURES: usize = 1000;
+
+fn benchmark_app() -> App {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default(), TransformPlugin))
+        .init_asset::<Mesh>()
+        .init_asset::<ColorMaterial>()
+        .insert_resource(LimbAssetStore::default())
+        .init_resource::<CreatureNames>()
+        .insert_resource(benchmark_creatures_plan(CREATURES))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+            1.0 / 60.0,
+        )))
+        .add_systems(Startup, spawn_creatures)
+        .add_systems(Update, (oscillator_tick, animate_limb_segments).chain());
+    // Spawn, and settle the first propagation of every transform.
+    app.update();
+    app.update();
+    app
+}
+
+fn frame_time(c: &mut Criterion) {
+    let mut app = benchmark_app();
+    let mut group = c.benchmark_group("frame");
+    group.sample_size(20);
+    group.bench_function("1000 creatures × 8 limbs × 16 segments", |b| {
+        b.iter(|| app.update())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, frame_time);
+criterion_main!(benches);
@@ -0,0 +1,31 @@
+//! Creatures in an app of your own: no panels or keyboard controls, your own
+//! camera, and the creatures drifting about.
+
+use bevy::prelude::*;
+use creature_synth::{example_creatures_plan, Creature, CreatureSynthPlugin};
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugins(CreatureSynthPlugin {
+            plan: example_creatures_plan(),
+            controls: false,
+            camera: false,
+            ..default()
+        })
+        .add_systems(Startup, setup_camera)
+        .add_systems(Update, drift)
+        .run();
+}
+
+fn setup_camera(mut commands: Commands) {
+    commands.spawn((Camera2d, Transform::from_scale(Vec3::splat(1.5))));
+}
+
+/// Turn the whole group slowly around the origin.
+fn drift(time: Res<Time>, mut creatures: Query<&mut Transform, With<Creature>>) {
+    let rotation = Quat::from_rotation_z(0.1 * time.delta_secs());
+    for mut transform in &mut creatures {
+        transform.rotate_around(Vec3::ZERO, rotation);
+    }
+}
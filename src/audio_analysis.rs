@@ -0,0 +1,874 @@
+use bevy::audio::{Decodable, Source};
+use bevy::prelude::*;
+use rustfft::{num_complex::Complex, Fft, FftPlanner};
+use serde::{Deserialize, Serialize};
+use std::{collections::VecDeque, fmt, path::Path, sync::Arc, time::Duration};
+
+use crate::oscillator::{Modulation, Oscillator};
+
+/// Samples per analysis frame.
+pub const FRAME_SIZE: usize = 1024;
+/// Samples between the starts of consecutive frames.
+pub const HOP_SIZE: usize = 512;
+/// Number of log-spaced frequency bands.
+pub const BAND_COUNT: usize = 8;
+const LOWEST_BAND_HZ: f32 = 40.0;
+const HIGHEST_BAND_HZ: f32 = 16_000.0;
+/// Levels are normalized from this (0) up to full scale (1).
+const FLOOR_DB: f32 = -60.0;
+/// How quickly the level falls back after a loud sound.
+const LEVEL_RELEASE_SECS: f32 = 0.3;
+/// How quickly the onset pulse fades.
+const PULSE_DECAY_SECS: f32 = 0.15;
+/// An onset needs spectral flux this many times the recent average...
+const ONSET_THRESHOLD: f32 = 1.5;
+/// ...and at least this much in absolute terms, so noise in silence is ignored.
+const ONSET_MIN_FLUX: f32 = 0.01;
+/// Frames of flux history for the onset threshold.
+const ONSET_HISTORY: usize = 16;
+/// Onsets closer together than this are merged.
+const ONSET_MIN_GAP_SECS: f32 = 0.05;
+
+/// Errors from loading or capturing audio.
+#[derive(Debug)]
+pub enum AudioError {
+    Wav(hound::Error),
+    #[cfg(feature = "audio-input")]
+    Input(String),
+}
+
+impl fmt::Display for AudioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AudioError::Wav(err) => write!(f, "wav file error: {err}"),
+            #[cfg(feature = "audio-input")]
+            AudioError::Input(err) => write!(f, "audio input error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for AudioError {}
+
+impl From<hound::Error> for AudioError {
+    fn from(err: hound::Error) -> Self {
+        AudioError::Wav(err)
+    }
+}
+
+/// A decoded mono audio clip. Also playable as an audio source.
+#[derive(Asset, TypePath, Debug, Clone)]
+pub struct AudioClip {
+    samples: Arc<[f32]>,
+    sample_rate: u32,
+}
+
+impl AudioClip {
+    pub fn new(samples: Vec<f32>, sample_rate: u32) -> Self {
+        Self {
+            samples: samples.into(),
+            sample_rate,
+        }
+    }
+
+    /// Load a WAV file, mixing all channels down to mono.
+    pub fn load_wav(path: impl AsRef<Path>) -> Result<Self, AudioError> {
+        let reader = hound::WavReader::open(path)?;
+        let spec = reader.spec();
+        let interleaved: Vec<f32> = match spec.sample_format {
+            hound::SampleFormat::Float => reader.into_samples::<f32>().collect::<Result<_, _>>()?,
+            hound::SampleFormat::Int => {
+                let scale = 1.0 / (1u64 << (spec.bits_per_sample - 1)) as f32;
+                reader
+                    .into_samples::<i32>()
+                    .map(|sample| sample.map(|sample| sample as f32 * scale))
+                    .collect::<Result<_, _>>()?
+            }
+        };
+        let channels = spec.channels.max(1) as usize;
+        let samples = interleaved
+            .chunks(channels)
+            .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+            .collect();
+        Ok(Self::new(samples, spec.sample_rate))
+    }
+
+    pub fn samples(&self) -> &[f32] {
+        &self.samples
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+}
+
+/// Plays an AudioClip from the start.
+pub struct ClipDecoder {
+    samples: Arc<[f32]>,
+    sample_rate: u32,
+    index: usize,
+}
+
+impl Iterator for ClipDecoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.samples.get(self.index).copied();
+        self.index += 1;
+        sample
+    }
+}
+
+impl Source for ClipDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        Some(self.samples.len().saturating_sub(self.index))
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        Some(Duration::from_secs_f64(
+            self.samples.len() as f64 / self.sample_rate as f64,
+        ))
+    }
+}
+
+impl Decodable for AudioClip {
+    type DecoderItem = f32;
+    type Decoder = ClipDecoder;
+
+    fn decoder(&self) -> ClipDecoder {
+        ClipDecoder {
+            samples: self.samples.clone(),
+            sample_rate: self.sample_rate,
+            index: 0,
+        }
+    }
+}
+
+/// What the analysis hears in one frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AudioFeatures {
+    /// Overall loudness, 0..1: rises immediately, falls back smoothly.
+    pub level: f32,
+    /// Whether a new sound started in this frame.
+    pub onset: bool,
+    /// Jumps to 1 on each onset and fades back to 0.
+    pub pulse: f32,
+    /// Loudness per frequency band, low to high, 0..1.
+    pub bands: [f32; BAND_COUNT],
+}
+
+/// Frame-by-frame audio analysis: level envelope, onsets (spectral flux) and
+/// FFT bands. Feed it samples in any chunk size.
+pub struct Analyzer {
+    sample_rate: u32,
+    fft: Arc<dyn Fft<f32>>,
+    window: Vec<f32>,
+    /// Scales FFT magnitudes so a full-scale sine peaks at 1.
+    window_gain: f32,
+    band_bins: [(usize, usize); BAND_COUNT],
+    pending: Vec<f32>,
+    spectrum: Vec<Complex<f32>>,
+    magnitudes: Vec<f32>,
+    previous: Vec<f32>,
+    flux_history: VecDeque<f32>,
+    previous_rms: f32,
+    since_onset: f32,
+    features: AudioFeatures,
+}
+
+impl Analyzer {
+    pub fn new(sample_rate: u32) -> Self {
+        let window: Vec<f32> = (0..FRAME_SIZE)
+            .map(|i| 0.5 - 0.5 * (std::f32::consts::TAU * i as f32 / FRAME_SIZE as f32).cos())
+            .collect();
+        let window_gain = 2.0 / window.iter().sum::<f32>();
+
+        let bins = FRAME_SIZE / 2 + 1;
+        let nyquist = sample_rate as f32 / 2.0;
+        let hz_to_bin = |hz: f32| (hz * FRAME_SIZE as f32 / sample_rate as f32).round() as usize;
+        let ratio = HIGHEST_BAND_HZ.min(nyquist) / LOWEST_BAND_HZ;
+        let band_bins = std::array::from_fn(|band| {
+            let low = LOWEST_BAND_HZ * ratio.powf(band as f32 / BAND_COUNT as f32);
+            let high = LOWEST_BAND_HZ * ratio.powf((band + 1) as f32 / BAND_COUNT as f32);
+            let low = hz_to_bin(low).min(bins - 1);
+            (low, hz_to_bin(high).clamp(low + 1, bins))
+        });
+
+        Self {
+            sample_rate,
+            fft: FftPlanner::new().plan_fft_forward(FRAME_SIZE),
+            window,
+            window_gain,
+            band_bins,
+            pending: Vec::with_capacity(FRAME_SIZE * 2),
+            spectrum: vec![Complex::default(); FRAME_SIZE],
+            magnitudes: vec![0.0; bins],
+            previous: vec![0.0; bins],
+            flux_history: VecDeque::with_capacity(ONSET_HISTORY),
+            previous_rms: 0.0,
+            since_onset: ONSET_MIN_GAP_SECS,
+            features: AudioFeatures::default(),
+        }
+    }
+
+    /// The features of the most recent frame.
+    pub fn features(&self) -> AudioFeatures {
+        self.features
+    }
+
+    /// Add samples, returning the features of every frame they complete.
+    pub fn push(&mut self, samples: &[f32]) -> Vec<AudioFeatures> {
+        self.pending.extend_from_slice(samples);
+        let pending = std::mem::take(&mut self.pending);
+        let mut frames = Vec::new();
+        let mut start = 0;
+        while start + FRAME_SIZE <= pending.len() {
+            frames.push(self.process(&pending[start..start + FRAME_SIZE]));
+            start += HOP_SIZE;
+        }
+        self.pending = pending;
+        self.pending.drain(..start);
+        frames
+    }
+
+    fn process(&mut self, frame: &[f32]) -> AudioFeatures {
+        let hop_secs = HOP_SIZE as f32 / self.sample_rate as f32;
+
+        for ((bin, sample), window) in self.spectrum.iter_mut().zip(frame).zip(&self.window) {
+            *bin = Complex::new(sample * window, 0.0);
+        }
+        self.fft.process(&mut self.spectrum);
+        for (magnitude, bin) in self.magnitudes.iter_mut().zip(&self.spectrum) {
+            *magnitude = bin.norm() * self.window_gain;
+        }
+
+        let mut bands = [0.0; BAND_COUNT];
+        for (level, (low, high)) in bands.iter_mut().zip(self.band_bins) {
+            let energy: f32 = self.magnitudes[low..high].iter().map(|m| m * m).sum();
+            *level = normalize(energy.sqrt());
+        }
+
+        // RMS, scaled so a full-scale sine reads as 1.
+        let rms = (frame.iter().map(|s| s * s).sum::<f32>() / frame.len() as f32).sqrt();
+        let level_now = normalize(rms * std::f32::consts::SQRT_2);
+        let level = if level_now > self.features.level {
+            level_now
+        } else {
+            let release = 1.0 - (-hop_secs / LEVEL_RELEASE_SECS).exp();
+            self.features.level + (level_now - self.features.level) * release
+        };
+
+        // Onsets: rising spectral energy well above the recent average. Sounds
+        // cutting off also splatter energy across bins, so the frame as a
+        // whole must be getting louder too.
+        let flux: f32 = self
+            .magnitudes
+            .iter()
+            .zip(&self.previous)
+            .map(|(now, before)| (now - before).max(0.0))
+            .sum();
+        self.previous.copy_from_slice(&self.magnitudes);
+        let average = if self.flux_history.is_empty() {
+            0.0
+        } else {
+            self.flux_history.iter().sum::<f32>() / self.flux_history.len() as f32
+        };
+        if self.flux_history.len() == ONSET_HISTORY {
+            self.flux_history.pop_front();
+        }
+        self.flux_history.push_back(flux);
+
+        self.since_onset += hop_secs;
+        let rising = rms > self.previous_rms;
+        self.previous_rms = rms;
+        let onset = rising
+            && flux > ONSET_MIN_FLUX
+            && flux > average * ONSET_THRESHOLD
+            && self.since_onset >= ONSET_MIN_GAP_SECS;
+        let pulse = if onset {
+            self.since_onset = 0.0;
+            1.0
+        } else {
+            self.features.pulse * (-hop_secs / PULSE_DECAY_SECS).exp()
+        };
+
+        self.features = AudioFeatures {
+            level,
+            onset,
+            pulse,
+            bands,
+        };
+        self.features
+    }
+}
+
+/// Map a linear amplitude onto 0..1 between FLOOR_DB and full scale.
+fn normalize(amplitude: f32) -> f32 {
+    let db = 20.0 * amplitude.max(1e-10).log10();
+    ((db - FLOOR_DB) / -FLOOR_DB).clamp(0.0, 1.0)
+}
+
+/// Features of one analysis frame, timed at the frame's center.
+#[derive(Debug, Clone, Copy)]
+pub struct AnalysisFrame {
+    pub time: f64,
+    pub features: AudioFeatures,
+}
+
+/// Write an analysis as CSV: one row per frame.
+pub fn write_analysis_csv(
+    frames: &[AnalysisFrame],
+    mut out: impl std::io::Write,
+) -> std::io::Result<()> {
+    write!(out, "time,level,onset,pulse")?;
+    for band in 0..BAND_COUNT {
+        write!(out, ",band{band}")?;
+    }
+    writeln!(out)?;
+    for AnalysisFrame { time, features } in frames {
+        write!(
+            out,
+            "{time:.4},{:.4},{},{:.4}",
+            features.level, features.onset as u8, features.pulse
+        )?;
+        for band in features.bands {
+            write!(out, ",{band:.4}")?;
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// Analyze a whole clip offline.
+pub fn analyze_clip(clip: &AudioClip) -> Vec<AnalysisFrame> {
+    let mut analyzer = Analyzer::new(clip.sample_rate);
+    analyzer
+        .push(clip.samples())
+        .into_iter()
+        .enumerate()
+        .map(|(i, features)| AnalysisFrame {
+            time: (i * HOP_SIZE + FRAME_SIZE / 2) as f64 / clip.sample_rate as f64,
+            features,
+        })
+        .collect()
+}
+
+/// The latest analysis of whatever audio is playing or coming in.
+#[derive(Resource, Debug, Clone, Copy, Default)]
+pub struct AudioAnalysis {
+    pub features: AudioFeatures,
+}
+
+/// A clip played on loop and analyzed in step with playback.
+#[derive(Resource)]
+pub struct AudioFileInput {
+    clip: AudioClip,
+    analyzer: Analyzer,
+    /// Samples fed to the analyzer so far, across loops.
+    fed: u64,
+    elapsed: f64,
+}
+
+impl AudioFileInput {
+    pub fn new(clip: AudioClip) -> Self {
+        Self {
+            analyzer: Analyzer::new(clip.sample_rate),
+            clip,
+            fed: 0,
+            elapsed: 0.0,
+        }
+    }
+}
+
+/// Start playing the audio file, if one was given.
+pub fn play_audio_file(
+    mut commands: Commands,
+    input: Option<Res<AudioFileInput>>,
+    mut clips: ResMut<Assets<AudioClip>>,
+) {
+    let Some(input) = input else {
+        return;
+    };
+    commands.spawn((
+        AudioPlayer(clips.add(input.clip.clone())),
+        PlaybackSettings::LOOP,
+    ));
+}
+
+/// Analyze the audio file up to the current playback time.
+pub fn analyze_audio_file(
+    time: Res<Time>,
+    input: Option<ResMut<AudioFileInput>>,
+    mut analysis: ResMut<AudioAnalysis>,
+) {
+    let Some(mut input) = input else {
+        return;
+    };
+    let input = &mut *input;
+    let samples = input.clip.samples();
+    if samples.is_empty() {
+        return;
+    }
+    input.elapsed += time.delta_secs_f64();
+    let played = (input.elapsed * input.clip.sample_rate as f64) as u64;
+    while input.fed < played {
+        let index = (input.fed % samples.len() as u64) as usize;
+        let count = (samples.len() - index).min((played - input.fed) as usize);
+        input.analyzer.push(&samples[index..index + count]);
+        input.fed += count as u64;
+    }
+    analysis.features = input.analyzer.features();
+}
+
+/// Live audio input via cpal, enabled with the `audio-input` feature.
+#[cfg(feature = "audio-input")]
+pub mod input {
+    use bevy::prelude::*;
+    use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+    use std::sync::{Arc, Mutex};
+
+    use super::{Analyzer, AudioAnalysis, AudioError};
+
+    /// Mono samples captured by the input stream, waiting to be analyzed.
+    #[derive(Resource)]
+    pub struct LiveAudioInput {
+        captured: Arc<Mutex<Vec<f32>>>,
+        analyzer: Analyzer,
+    }
+
+    /// Open the default input device. The stream is kept as a non-send
+    /// resource; dropping it stops capture.
+    pub fn start_live_audio_input(world: &mut World) {
+        match open_default_input() {
+            Ok((stream, input)) => {
+                world.insert_non_send_resource(stream);
+                world.insert_resource(input);
+            }
+            Err(err) => warn!("{err}"),
+        }
+    }
+
+    fn open_default_input() -> Result<(cpal::Stream, LiveAudioInput), AudioError> {
+        let input_error = |err: &dyn std::fmt::Display| AudioError::Input(err.to_string());
+        let device = cpal::default_host()
+            .default_input_device()
+            .ok_or_else(|| AudioError::Input("no input device".to_string()))?;
+        let config = device
+            .default_input_config()
+            .map_err(|err| input_error(&err))?;
+        let channels = config.channels().max(1) as usize;
+        let sample_rate = config.sample_rate().0;
+
+        let captured = Arc::new(Mutex::new(Vec::new()));
+        let sink = captured.clone();
+        let on_error = |err| error!("audio input stream error: {err}");
+        let push = move |data: &mut dyn Iterator<Item = f32>| {
+            if let Ok(mut sink) = sink.lock() {
+                let data: Vec<f32> = data.collect();
+                sink.extend(
+                    data.chunks(channels)
+                        .map(|frame| frame.iter().sum::<f32>() / channels as f32),
+                );
+            }
+        };
+        let stream = match config.sample_format() {
+            cpal::SampleFormat::F32 => device.build_input_stream(
+                &config.into(),
+                move |data: &[f32], _: &cpal::InputCallbackInfo| push(&mut data.iter().copied()),
+                on_error,
+                None,
+            ),
+            cpal::SampleFormat::I16 => device.build_input_stream(
+                &config.into(),
+                move |data: &[i16], _: &cpal::InputCallbackInfo| {
+                    push(&mut data.iter().map(|s| *s as f32 / i16::MAX as f32))
+                },
+                on_error,
+                None,
+            ),
+            format => {
+                return Err(AudioError::Input(format!(
+                    "unsupported sample format {format}"
+                )))
+            }
+        }
+        .map_err(|err| input_error(&err))?;
+        stream.play().map_err(|err| input_error(&err))?;
+        info!("analyzing live audio input at {sample_rate} Hz");
+
+        Ok((
+            stream,
+            LiveAudioInput {
+                captured,
+                analyzer: Analyzer::new(sample_rate),
+            },
+        ))
+    }
+
+    /// Analyze everything captured since the last frame.
+    pub fn analyze_live_audio(
+        input: Option<ResMut<LiveAudioInput>>,
+        mut analysis: ResMut<AudioAnalysis>,
+    ) {
+        let Some(mut input) = input else {
+            return;
+        };
+        let input = &mut *input;
+        let samples = match input.captured.lock() {
+            Ok(mut captured) => std::mem::take(&mut *captured),
+            Err(_) => return,
+        };
+        input.analyzer.push(&samples);
+        analysis.features = input.analyzer.features();
+    }
+}
+
+/// Which part of the analysis drives an oscillator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AudioFeature {
+    Level,
+    Pulse,
+    Band(usize),
+}
+
+impl AudioFeature {
+    pub fn all() -> impl Iterator<Item = AudioFeature> {
+        [AudioFeature::Level, AudioFeature::Pulse]
+            .into_iter()
+            .chain((0..BAND_COUNT).map(AudioFeature::Band))
+    }
+
+    /// The feature's current value, 0..1.
+    pub fn value(self, features: &AudioFeatures) -> f32 {
+        match self {
+            AudioFeature::Level => features.level,
+            AudioFeature::Pulse => features.pulse,
+            AudioFeature::Band(band) => features.bands.get(band).copied().unwrap_or(0.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AudioTarget {
+    Amplitude,
+    Frequency,
+}
+
+impl AudioTarget {
+    pub const ALL: [AudioTarget; 2] = [AudioTarget::Amplitude, AudioTarget::Frequency];
+}
+
+/// Drive a limb's oscillator from the audio analysis.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AudioReactive {
+    pub feature: AudioFeature,
+    pub target: AudioTarget,
+    /// For amplitude, extra gain at full value (1.0 doubles the amplitude).
+    /// For frequency, octaves up at full value.
+    pub depth: f32,
+}
+
+impl Default for AudioReactive {
+    fn default() -> Self {
+        Self {
+            feature: AudioFeature::Level,
+            target: AudioTarget::Amplitude,
+            depth: 1.0,
+        }
+    }
+}
+
+impl AudioReactive {
+    pub fn modulation(&self, features: &AudioFeatures) -> Modulation {
+        let value = self.feature.value(features);
+        match self.target {
+            AudioTarget::Amplitude => Modulation {
+                amplitude: (1.0 + self.depth * value).max(0.0),
+                ..default()
+            },
+            AudioTarget::Frequency => Modulation {
+                frequency: 2f32.powf(self.depth * value),
+                ..default()
+            },
+        }
+    }
+}
+
+/// Modulate audio-reactive oscillators from the latest analysis.
+pub fn apply_audio_reactivity(
+    analysis: Res<AudioAnalysis>,
+    mut q: Query<(&AudioReactive, &mut Oscillator)>,
+) {
+    for (reactive, mut osc) in &mut q {
+        osc.set_modulation(reactive.modulation(&analysis.features));
+    }
+}
+
+/// Let limbs go back to their own settings once they stop reacting to audio.
+pub fn reset_audio_modulation(
+    mut removed: RemovedComponents<AudioReactive>,
+    mut oscillators: Query<&mut Oscillator>,
+) {
+    for limb in removed.read() {
+        if let Ok(mut osc) = oscillators.get_mut(limb) {
+            osc.set_modulation(Modulation::default());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::creature::example_creatures_plan;
+    use crate::oscillator::{oscillator_tick, Wave};
+    use crate::testing::Harness;
+
+    const SAMPLE_RATE: u32 = 44_100;
+
+    fn sine(hz: f32, amplitude: f32, secs: f32) -> Vec<f32> {
+        (0..(secs * SAMPLE_RATE as f32) as usize)
+            .map(|i| {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                amplitude * (std::f32::consts::TAU * hz * t).sin()
+            })
+            .collect()
+    }
+
+    /// Short 1 kHz bursts at the given times, silence in between.
+    fn bursts(starts: &[f32], secs: f32) -> Vec<f32> {
+        let burst = sine(1000.0, 0.5, 0.05);
+        let mut samples = vec![0.0; (secs * SAMPLE_RATE as f32) as usize];
+        for start in starts {
+            let at = (start * SAMPLE_RATE as f32) as usize;
+            samples[at..at + burst.len()].copy_from_slice(&burst);
+        }
+        samples
+    }
+
+    fn frames_after(frames: &[AnalysisFrame], secs: f64) -> impl Iterator<Item = &AnalysisFrame> {
+        frames.iter().filter(move |frame| frame.time > secs)
+    }
+
+    #[test]
+    fn sine_lands_in_its_band() {
+        let clip = AudioClip::new(sine(440.0, 0.5, 1.0), SAMPLE_RATE);
+        let frames = analyze_clip(&clip);
+        assert!(!frames.is_empty());
+        for frame in frames_after(&frames, 0.1) {
+            let bands = frame.features.bands;
+            let loudest = (0..BAND_COUNT)
+                .max_by(|a, b| bands[*a].total_cmp(&bands[*b]))
+                .unwrap();
+            // 40 Hz * 400^(3/8) ≈ 378 Hz up to 800 Hz.
+            assert_eq!(loudest, 3, "bands {bands:?}");
+            assert!(bands[3] > 0.8, "bands {bands:?}");
+            assert!(bands[0] < 0.2 && bands[7] < 0.2, "bands {bands:?}");
+        }
+    }
+
+    #[test]
+    fn silence_is_quiet() {
+        let clip = AudioClip::new(vec![0.0; SAMPLE_RATE as usize], SAMPLE_RATE);
+        for frame in analyze_clip(&clip) {
+            assert_eq!(frame.features, AudioFeatures::default());
+        }
+    }
+
+    #[test]
+    fn level_follows_loudness() {
+        let mut samples = sine(220.0, 0.01, 1.0);
+        samples.extend(sine(220.0, 0.5, 1.0));
+        samples.extend(vec![0.0; SAMPLE_RATE as usize]);
+        let frames = analyze_clip(&AudioClip::new(samples, SAMPLE_RATE));
+        let level_at = |secs: f64| {
+            frames_after(&frames, secs)
+                .next()
+                .map(|frame| frame.features.level)
+                .unwrap()
+        };
+
+        let quiet = level_at(0.9);
+        let loud = level_at(1.9);
+        // -40 dB and -6 dB on a 60 dB scale.
+        assert!((quiet - 0.33).abs() < 0.05, "quiet {quiet}");
+        assert!((loud - 0.9).abs() < 0.05, "loud {loud}");
+        // The release is smooth rather than instant...
+        assert!(level_at(2.05) > 0.5);
+        // ...but settles back to silence.
+        assert!(level_at(2.95) < 0.05);
+    }
+
+    #[test]
+    fn detects_onsets_of_bursts() {
+        let starts = [0.25, 0.75, 1.25, 1.75];
+        let clip = AudioClip::new(bursts(&starts, 2.25), SAMPLE_RATE);
+        let frames = analyze_clip(&clip);
+        let onsets: Vec<f64> = frames
+            .iter()
+            .filter(|frame| frame.features.onset)
+            .map(|frame| frame.time)
+            .collect();
+        assert_eq!(onsets.len(), starts.len(), "onsets at {onsets:?}");
+        for (onset, start) in onsets.iter().zip(starts) {
+            assert!((onset - start as f64).abs() < 0.03, "onsets at {onsets:?}");
+        }
+
+        // The pulse jumps on each onset and fades before the next.
+        let pulse_before_next = frames_after(&frames, 0.7)
+            .next()
+            .map(|frame| frame.features.pulse)
+            .unwrap();
+        assert!(pulse_before_next < 0.1);
+    }
+
+    #[test]
+    fn push_in_chunks_matches_whole_clip() {
+        let samples = bursts(&[0.1, 0.4], 0.6);
+        let whole = analyze_clip(&AudioClip::new(samples.clone(), SAMPLE_RATE));
+        let mut analyzer = Analyzer::new(SAMPLE_RATE);
+        let chunked: Vec<AudioFeatures> = samples
+            .chunks(300)
+            .flat_map(|chunk| analyzer.push(chunk))
+            .collect();
+        assert_eq!(chunked.len(), whole.len());
+        for (a, b) in chunked.iter().zip(&whole) {
+            assert_eq!(*a, b.features);
+        }
+    }
+
+    #[test]
+    fn loads_wav_files() {
+        let path = std::env::temp_dir().join(format!(
+            "creature-synth-analysis-{}.wav",
+            std::process::id()
+        ));
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 22_050,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for i in 0..22_050 {
+            // Left at half scale, right silent: mono is a quarter scale.
+            let left = if i % 2 == 0 {
+                i16::MAX / 2
+            } else {
+                -(i16::MAX / 2)
+            };
+            writer.write_sample(left).unwrap();
+            writer.write_sample(0i16).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let clip = AudioClip::load_wav(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(clip.sample_rate, 22_050);
+        assert_eq!(clip.samples().len(), 22_050);
+        assert!((clip.samples()[0] - 0.25).abs() < 1e-3);
+        assert!((clip.samples()[1] + 0.25).abs() < 1e-3);
+        assert!(!analyze_clip(&clip).is_empty());
+    }
+
+    #[test]
+    fn missing_wav_is_an_error() {
+        assert!(AudioClip::load_wav("/nonexistent/creature-synth.wav").is_err());
+    }
+
+    #[test]
+    fn reactive_mapping_modulates_oscillators() {
+        let features = AudioFeatures {
+            level: 0.5,
+            bands: [0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+            ..default()
+        };
+        let amplitude = AudioReactive::default().modulation(&features);
+        assert_eq!(amplitude.amplitude, 1.5);
+        assert_eq!(amplitude.frequency, 1.0);
+
+        let frequency = AudioReactive {
+            feature: AudioFeature::Band(1),
+            target: AudioTarget::Frequency,
+            depth: 1.0,
+        }
+        .modulation(&features);
+        assert_eq!(frequency.frequency, 2.0);
+
+        // Modulation scales the oscillator without changing its settings.
+        let mut osc = Oscillator::new(Wave::Square, 0.5, 1.0);
+        osc.set_modulation(Modulation {
+            amplitude: 2.0,
+            frequency: 2.0,
+        });
+        osc.tick(0.125);
+        assert!((osc.phase() - 0.25).abs() < 1e-6);
+        assert_eq!(osc.sample(), 1.0);
+        assert_eq!(osc.effective_frequency(), 2.0);
+        assert_eq!(osc.amplitude(), 0.5);
+        assert_eq!(osc.target_frequency(), 1.0);
+    }
+
+    #[test]
+    fn limbs_stop_reacting_when_the_mapping_is_removed() {
+        let mut plan = example_creatures_plan();
+        plan.creatures.truncate(1);
+        let mut harness = Harness::new(plan);
+        let creature = harness.creatures()[0];
+        let limb = harness.limbs(creature)[0];
+        harness
+            .app
+            .insert_resource(AudioAnalysis {
+                features: AudioFeatures {
+                    level: 1.0,
+                    ..default()
+                },
+            })
+            .add_systems(
+                Update,
+                (apply_audio_reactivity, reset_audio_modulation).before(oscillator_tick),
+            );
+        let reactive = |target| AudioReactive {
+            feature: AudioFeature::Level,
+            target,
+            depth: 1.0,
+        };
+        let osc = |harness: &Harness| harness.world().get::<Oscillator>(limb).unwrap().clone();
+        // Phase advanced over 0.1 s.
+        let advance = |harness: &mut Harness| {
+            let before = osc(harness).phase();
+            harness.advance(0.1);
+            (osc(harness).phase() - before).rem_euclid(1.0)
+        };
+
+        let world = harness.app.world_mut();
+        world
+            .entity_mut(limb)
+            .insert(reactive(AudioTarget::Amplitude));
+        harness.advance(0.1);
+        let own = osc(&harness);
+        assert_eq!(own.level(), 2.0 * own.amplitude());
+
+        let world = harness.app.world_mut();
+        world
+            .entity_mut(limb)
+            .insert(reactive(AudioTarget::Frequency));
+        let hz = own.current_frequency();
+        assert!((advance(&mut harness) - 0.2 * hz).abs() < 1e-4);
+
+        harness
+            .app
+            .world_mut()
+            .entity_mut(limb)
+            .remove::<AudioReactive>();
+        assert!((advance(&mut harness) - 0.1 * hz).abs() < 1e-4);
+        let own = osc(&harness);
+        assert_eq!(own.level(), own.amplitude());
+    }
+}
@@ -0,0 +1,314 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::creature::{CreatureCommands, CreatureNames, CreaturePlan, CreaturePlans};
+use crate::evolution::Genome;
+use crate::selection::Selection;
+
+/// How children are bred by hand.
+#[derive(Resource, Debug)]
+pub struct Breeding {
+    /// Chance of each trait mutating in a child.
+    pub mutation_rate: f32,
+    rng: StdRng,
+}
+
+impl Default for Breeding {
+    fn default() -> Self {
+        Self {
+            mutation_rate: 0.1,
+            rng: StdRng::from_os_rng(),
+        }
+    }
+}
+
+impl Breeding {
+    pub fn seeded(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            ..default()
+        }
+    }
+
+    /// A child of two plans, its limbs mixed from both and mutated, placed
+    /// halfway between them.
+    pub fn breed(&mut self, a: &CreaturePlan, b: &CreaturePlan) -> CreaturePlan {
+        let mut genome = Genome::from_plan(a).crossover(&Genome::from_plan(b), &mut self.rng);
+        genome.mutate(self.mutation_rate, &mut self.rng);
+        let between = a.transform.translation.lerp(b.transform.translation, 0.5);
+        genome.to_plan(Transform::from_translation(between))
+    }
+}
+
+/// One of a child's parents, as it was when the child was born.
+#[derive(Debug, Clone)]
+pub struct Parent {
+    pub entity: Entity,
+    pub name: String,
+    /// The parent's own birth, if it was bred.
+    pub birth: Option<usize>,
+}
+
+/// A child bred by hand.
+#[derive(Debug, Clone)]
+pub struct Birth {
+    pub name: String,
+    pub parents: [Parent; 2],
+    /// One more than the later-born parent's; creatures that weren't bred
+    /// are generation 0.
+    pub generation: u32,
+}
+
+/// Marks a bred creature with its birth in the Lineage.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Offspring {
+    pub birth: usize,
+}
+
+/// Every child bred so far, in order of birth.
+#[derive(Resource, Debug, Default)]
+pub struct Lineage {
+    births: Vec<Birth>,
+    /// Show the lineage window.
+    pub open: bool,
+}
+
+impl Lineage {
+    pub fn births(&self) -> &[Birth] {
+        &self.births
+    }
+
+    pub fn generation(&self, birth: Option<usize>) -> u32 {
+        birth
+            .and_then(|birth| self.births.get(birth))
+            .map_or(0, |birth| birth.generation)
+    }
+
+    /// Record a birth, returning its index.
+    pub fn record(&mut self, name: String, parents: [Parent; 2]) -> usize {
+        let generation = 1 + parents
+            .iter()
+            .map(|parent| self.generation(parent.birth))
+            .max()
+            .unwrap_or(0);
+        self.births.push(Birth {
+            name,
+            parents,
+            generation,
+        });
+        self.births.len() - 1
+    }
+}
+
+/// Enter breeds the selected creature with its partner: the child grows in
+/// between them, and its birth goes into the lineage.
+pub fn breed_selected(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    selection: Res<Selection>,
+    plans: CreaturePlans,
+    offspring: Query<&Offspring>,
+    mut breeding: ResMut<Breeding>,
+    mut lineage: ResMut<Lineage>,
+    mut names: ResMut<CreatureNames>,
+) {
+    if !keys.just_pressed(KeyCode::Enter) {
+        return;
+    }
+    let (Some(a), Some(b)) = (selection.creature, selection.partner) else {
+        return;
+    };
+    let (Some(plan_a), Some(plan_b)) = (plans.get(a), plans.get(b)) else {
+        return;
+    };
+
+    let parent = |entity: Entity| Parent {
+        entity,
+        name: plans
+            .name(entity)
+            .map_or_else(|| format!("{entity}"), |n| n.to_string()),
+        birth: offspring.get(entity).ok().map(|o| o.birth),
+    };
+    let name = names.next_child_name();
+    let birth = lineage.record(name.to_string(), [parent(a), parent(b)]);
+
+    let child = commands.spawn_creature(breeding.breed(&plan_a, &plan_b));
+    // After the spawn's own name.
+    commands
+        .entity(child.creature)
+        .insert((name, Offspring { birth }));
+    lineage.open = true;
+}
+
+/// Window showing the selected creature's family tree and every birth.
+pub fn lineage_panel(
+    mut contexts: EguiContexts,
+    mut lineage: ResMut<Lineage>,
+    selection: Res<Selection>,
+    offspring: Query<&Offspring>,
+) -> Result {
+    if !lineage.open {
+        return Ok(());
+    }
+    let selected = selection
+        .creature
+        .and_then(|creature| offspring.get(creature).ok())
+        .map(|o| o.birth);
+
+    let mut open = lineage.open;
+    let ctx = contexts.ctx_mut()?;
+    egui::Window::new("Lineage")
+        .open(&mut open)
+        .resizable(true)
+        .show(ctx, |ui| {
+            match selected {
+                Some(birth) => {
+                    ui.label("Selected creature's family tree");
+                    family_tree(ui, &lineage, birth);
+                }
+                None => {
+                    ui.label("Select a bred creature to see its family tree.");
+                }
+            }
+            ui.separator();
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                for birth in lineage.births() {
+                    let [a, b] = &birth.parents;
+                    ui.label(format!(
+                        "{} (generation {}) ← {} × {}",
+                        birth.name, birth.generation, a.name, b.name
+                    ));
+                }
+            });
+        });
+    lineage.open = open;
+    Ok(())
+}
+
+/// A birth and, folded away beneath it, its parents' births.
+fn family_tree(ui: &mut egui::Ui, lineage: &Lineage, birth: usize) {
+    let Some(record) = lineage.births().get(birth) else {
+        return;
+    };
+    egui::CollapsingHeader::new(format!(
+        "{} (generation {})",
+        record.name, record.generation
+    ))
+    .id_salt(("birth", birth))
+    .default_open(true)
+    .show(ui, |ui| {
+        for parent in &record.parents {
+            match parent.birth {
+                Some(parent_birth) => family_tree(ui, lineage, parent_birth),
+                None => {
+                    ui.label(&parent.name);
+                }
+            }
+        }
+    });
+}
+
+/// F4 shows or hides the lineage window.
+pub fn toggle_lineage(keys: Res<ButtonInput<KeyCode>>, mut lineage: ResMut<Lineage>) {
+    if keys.just_pressed(KeyCode::F4) {
+        lineage.open = !lineage.open;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::creature::example_creatures_plan;
+    use crate::testing::Harness;
+    use bevy::ecs::system::RunSystemOnce;
+
+    fn parent(name: &str, birth: Option<usize>) -> Parent {
+        Parent {
+            entity: Entity::PLACEHOLDER,
+            name: name.to_string(),
+            birth,
+        }
+    }
+
+    #[test]
+    fn children_are_born_between_their_parents() {
+        let plan = example_creatures_plan();
+        let (a, b) = (&plan.creatures[0], &plan.creatures[3]);
+        let child = Breeding::seeded(7).breed(a, b);
+
+        let between = (a.transform.translation + b.transform.translation) / 2.0;
+        assert!(child.transform.translation.distance(between) < 1e-3);
+        assert!(!child.limbs.is_empty());
+    }
+
+    #[test]
+    fn enter_breeds_the_selected_pair() {
+        let mut plan = example_creatures_plan();
+        plan.creatures.truncate(2);
+        let mut harness = Harness::new(plan);
+        let [a, b] = harness.creatures()[..] else {
+            panic!("expected two creatures");
+        };
+        let mut keys = ButtonInput::<KeyCode>::default();
+        keys.press(KeyCode::Enter);
+        harness
+            .app
+            .insert_resource(keys)
+            .insert_resource(Selection {
+                creature: Some(a),
+                partner: Some(b),
+                ..default()
+            })
+            .insert_resource(Breeding::seeded(7))
+            .init_resource::<Lineage>();
+        harness
+            .app
+            .world_mut()
+            .run_system_once(breed_selected)
+            .unwrap();
+        harness.advance(0.1);
+
+        let creatures = harness.creatures();
+        assert_eq!(creatures.len(), 3);
+        let child = creatures[2];
+        let world = harness.world();
+        assert_eq!(world.get::<Name>(child).unwrap().as_str(), "Child 1");
+        assert_eq!(world.get::<Offspring>(child).unwrap().birth, 0);
+        let birth = &world.resource::<Lineage>().births()[0];
+        assert_eq!(birth.parents[0].entity, a);
+        assert_eq!(birth.parents[1].entity, b);
+
+        // Names aren't reused, even with the lineage started afresh.
+        let world = harness.app.world_mut();
+        world.insert_resource(Lineage::default());
+        world.run_system_once(breed_selected).unwrap();
+        harness.advance(0.1);
+        let child = harness.creatures()[3];
+        let name = harness.world().get::<Name>(child).unwrap();
+        assert_eq!(name.as_str(), "Child 2");
+    }
+
+    #[test]
+    fn generations_count_from_the_latest_parent() {
+        let mut lineage = Lineage::default();
+        let first = lineage.record("Child 1".into(), [parent("A", None), parent("B", None)]);
+        let second = lineage.record(
+            "Child 2".into(),
+            [parent("Child 1", Some(first)), parent("C", None)],
+        );
+        let third = lineage.record(
+            "Child 3".into(),
+            [
+                parent("Child 2", Some(second)),
+                parent("Child 1", Some(first)),
+            ],
+        );
+
+        assert_eq!(lineage.generation(None), 0);
+        assert_eq!(lineage.generation(Some(first)), 1);
+        assert_eq!(lineage.generation(Some(second)), 2);
+        assert_eq!(lineage.generation(Some(third)), 3);
+    }
+}
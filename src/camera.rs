@@ -0,0 +1,246 @@
+use bevy::input::mouse::{AccumulatedMouseMotion, AccumulatedMouseScroll, MouseScrollUnit};
+use bevy::prelude::*;
+
+use crate::creature::Creature;
+use crate::limb::LimbSegment;
+use crate::piano::Piano;
+use crate::selection::Selection;
+
+/// Scroll pixels that count as one wheel notch.
+const PIXELS_PER_LINE: f32 = 100.0;
+
+/// How the view is panned, zoomed and moved about.
+#[derive(Resource, Debug, Clone)]
+pub struct CameraControls {
+    /// Keyboard pan speed, in screen pixels per second.
+    pub pan_speed: f32,
+    /// Scale change per wheel notch.
+    pub zoom_step: f32,
+    pub min_scale: f32,
+    pub max_scale: f32,
+    /// How quickly the view catches up with where it's headed (per second).
+    pub smoothing: f32,
+    /// Room left around creatures when framing them.
+    pub margin: f32,
+    /// Keep the selected creature in the middle of the view.
+    pub follow: bool,
+    /// Where the view is easing toward, if anywhere.
+    goal: Option<Vec2>,
+    /// The scale the view is easing toward.
+    scale: f32,
+}
+
+impl Default for CameraControls {
+    fn default() -> Self {
+        Self {
+            pan_speed: 600.0,
+            zoom_step: 1.1,
+            min_scale: 0.1,
+            max_scale: 20.0,
+            smoothing: 6.0,
+            margin: 100.0,
+            follow: false,
+            goal: None,
+            scale: 1.0,
+        }
+    }
+}
+
+impl CameraControls {
+    /// Stop easing anywhere: the view is being moved by hand.
+    fn take_over(&mut self) {
+        self.follow = false;
+        self.goal = None;
+    }
+}
+
+/// The center and scale that fit the box from `min` to `max`, plus a margin,
+/// into a viewport.
+fn fit(min: Vec2, max: Vec2, viewport: Vec2, margin: f32) -> (Vec2, f32) {
+    let size = max - min + Vec2::splat(2.0 * margin);
+    let scale = (size / viewport.max(Vec2::ONE)).max_element();
+    ((min + max) / 2.0, scale)
+}
+
+type CameraView<'a> = (&'a Camera, &'a mut Transform, &'a mut Projection);
+/// Everything drawn of a creature: its body and limb segments.
+type CreatureParts = Or<(With<Creature>, With<LimbSegment>)>;
+
+/// Mouse camera controls:
+/// - Wheel: zoom in / out
+/// - Middle drag: pan
+pub fn camera_mouse_update(
+    scroll: Res<AccumulatedMouseScroll>,
+    motion: Res<AccumulatedMouseMotion>,
+    buttons: Res<ButtonInput<MouseButton>>,
+    mut controls: ResMut<CameraControls>,
+    camera: Single<CameraView, With<Camera2d>>,
+) {
+    let (_, mut transform, projection) = camera.into_inner();
+    let Projection::Orthographic(projection) = &*projection else {
+        return;
+    };
+
+    let notches = match scroll.unit {
+        MouseScrollUnit::Line => scroll.delta.y,
+        MouseScrollUnit::Pixel => scroll.delta.y / PIXELS_PER_LINE,
+    };
+    if notches != 0.0 {
+        controls.scale = (controls.scale * controls.zoom_step.powf(-notches))
+            .clamp(controls.min_scale, controls.max_scale);
+    }
+
+    if buttons.pressed(MouseButton::Middle) && motion.delta != Vec2::ZERO {
+        controls.take_over();
+        // Screen y runs down; the world's runs up.
+        let drag = Vec2::new(-motion.delta.x, motion.delta.y) * projection.scale;
+        transform.translation += drag.extend(0.0);
+    }
+}
+
+/// Keyboard camera controls:
+/// - W / A / S / D: pan (not while the piano is on)
+/// - F: follow the selected creature, or stop following (not while the
+///   piano is on)
+/// - Home: frame every creature
+pub fn camera_user_update(
+    keys: Res<ButtonInput<KeyCode>>,
+    time: Res<Time<Real>>,
+    piano: Res<Piano>,
+    mut controls: ResMut<CameraControls>,
+    camera: Single<CameraView, With<Camera2d>>,
+    parts: Query<&GlobalTransform, CreatureParts>,
+) {
+    let (camera, mut transform, projection) = camera.into_inner();
+    let Projection::Orthographic(projection) = &*projection else {
+        return;
+    };
+
+    if !piano.enabled {
+        let mut direction = Vec2::ZERO;
+        for (key, step) in [
+            (KeyCode::KeyW, Vec2::Y),
+            (KeyCode::KeyA, Vec2::NEG_X),
+            (KeyCode::KeyS, Vec2::NEG_Y),
+            (KeyCode::KeyD, Vec2::X),
+        ] {
+            if keys.pressed(key) {
+                direction += step;
+            }
+        }
+        if direction != Vec2::ZERO {
+            controls.take_over();
+            let step = direction.normalize() * controls.pan_speed * projection.scale;
+            transform.translation += (step * time.delta_secs()).extend(0.0);
+        }
+        if keys.just_pressed(KeyCode::KeyF) {
+            controls.follow = !controls.follow;
+        }
+    }
+
+    if keys.just_pressed(KeyCode::Home) {
+        let bounds = parts.iter().map(|t| t.translation().truncate()).fold(
+            None,
+            |bounds: Option<(Vec2, Vec2)>, p| {
+                Some(bounds.map_or((p, p), |(min, max)| (min.min(p), max.max(p))))
+            },
+        );
+        if let Some((min, max)) = bounds
+            && let Some(viewport) = camera.logical_viewport_size()
+        {
+            let (center, scale) = fit(min, max, viewport, controls.margin);
+            controls.follow = false;
+            controls.goal = Some(center);
+            controls.scale = scale.clamp(controls.min_scale, controls.max_scale);
+        }
+    }
+}
+
+/// Ease the view toward its goal (the selected creature, while following)
+/// and its zoom.
+pub fn move_camera(
+    time: Res<Time<Real>>,
+    selection: Res<Selection>,
+    mut controls: ResMut<CameraControls>,
+    camera: Single<CameraView, With<Camera2d>>,
+    creatures: Query<&GlobalTransform, With<Creature>>,
+) {
+    let (_, mut transform, mut projection) = camera.into_inner();
+    let Projection::Orthographic(projection) = &mut *projection else {
+        return;
+    };
+    if controls.follow
+        && let Some(creature) = selection.creature.and_then(|c| creatures.get(c).ok())
+    {
+        controls.goal = Some(creature.translation().truncate());
+    }
+
+    // Frame-rate independent exponential easing.
+    let ease = 1.0 - (-controls.smoothing * time.delta_secs()).exp();
+    if let Some(goal) = controls.goal {
+        let position = transform.translation.truncate().lerp(goal, ease);
+        transform.translation = position.extend(transform.translation.z);
+        if !controls.follow && position.distance(goal) < 0.5 {
+            controls.goal = None;
+        }
+    }
+    projection.scale += (controls.scale - projection.scale) * ease;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::creature::example_creatures_plan;
+    use crate::testing::Harness;
+
+    #[test]
+    fn framing_fits_the_box_and_its_margin() {
+        let viewport = Vec2::new(800.0, 600.0);
+        let (center, scale) = fit(
+            Vec2::new(-500.0, 0.0),
+            Vec2::new(700.0, 200.0),
+            viewport,
+            100.0,
+        );
+        assert_eq!(center, Vec2::new(100.0, 100.0));
+        // 1400 units wide in 800 pixels; the height fits easily.
+        assert_eq!(scale, 1.75);
+    }
+
+    #[test]
+    fn following_eases_toward_the_selected_creature() {
+        let mut harness = Harness::new(example_creatures_plan());
+        let creature = harness.creatures()[1];
+        let camera = harness.app.world_mut().spawn(Camera2d).id();
+        harness
+            .app
+            .insert_resource(Selection {
+                creature: Some(creature),
+                ..default()
+            })
+            .insert_resource(CameraControls {
+                follow: true,
+                ..default()
+            })
+            .add_systems(Update, move_camera);
+        let distance = |harness: &Harness| {
+            let world = harness.world();
+            let target = world
+                .get::<GlobalTransform>(creature)
+                .unwrap()
+                .translation();
+            let view = world.get::<Transform>(camera).unwrap().translation;
+            target.truncate().distance(view.truncate())
+        };
+        let start = distance(&harness);
+        assert!(start > 100.0);
+
+        harness.advance(1.0 / 60.0);
+        let after_one = distance(&harness);
+        // Eased, not jumped.
+        assert!(after_one < start && after_one > start * 0.5, "{after_one}");
+
+        harness.advance(2.0);
+        assert!(distance(&harness) < 1.0);
+    }
+}
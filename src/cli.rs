@@ -0,0 +1,247 @@
+//! Command-line arguments:
+//!
+//! ```text
+//! [plan file] [--audio <wav file>] [--audio-input]
+//! [--analyze <wav file>]
+//! [--render <dir> [--seconds <n>] [--fps <n>] [--no-video]]
+//! [--fixed-hz <n>] [--evolve <generations>] [--seed <n>]
+//! [--ecosystem] [--flock]
+//! ```
+//!
+//! The plan file is loaded instead of the example; audio drives
+//! audio-reactive limbs. --analyze prints the analysis as CSV and exits.
+//! --render runs without a window at a fixed timestep, writes audio and
+//! frames to the directory, and exits. --fixed-hz simulates at a fixed rate,
+//! independent of frame timing. --evolve breeds the plan's creatures for
+//! distance swum, without a window and as fast as it'll go, then saves the
+//! last generation and shows it swimming; --seed makes it breed the same
+//! every run. --ecosystem adds food, and creatures that live and breed on it.
+//! --flock has creatures wander and flock together, the same way every run
+//! with --seed.
+
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::render::{RenderSettings, MIN_RENDER_FPS};
+use crate::MIN_FIXED_HZ;
+
+/// What the command line asked for.
+#[derive(Debug, Clone, Default)]
+pub struct Args {
+    pub plan_path: Option<PathBuf>,
+    pub analyze_path: Option<PathBuf>,
+    pub audio_path: Option<PathBuf>,
+    pub live_audio: bool,
+    pub render: Option<RenderSettings>,
+    pub fixed_hz: Option<f64>,
+    /// Generations to evolve for.
+    pub generations: Option<usize>,
+    pub seed: Option<u64>,
+    pub ecosystem: bool,
+    pub flock: bool,
+}
+
+impl Args {
+    /// Parse arguments, not including the program name.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, ArgsError> {
+        let mut parsed = Args::default();
+        let mut seconds = None;
+        let mut fps = None;
+        let mut video = true;
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--audio" => parsed.audio_path = args.next().map(PathBuf::from),
+                "--audio-input" => parsed.live_audio = true,
+                "--analyze" => parsed.analyze_path = args.next().map(PathBuf::from),
+                "--render" => parsed.render = args.next().map(RenderSettings::new),
+                "--seconds" => seconds = value(args.next(), "--seconds")?,
+                "--fps" => fps = value(args.next(), "--fps")?,
+                "--no-video" => video = false,
+                "--fixed-hz" => parsed.fixed_hz = value(args.next(), "--fixed-hz")?,
+                "--evolve" => parsed.generations = value(args.next(), "--evolve")?,
+                "--seed" => parsed.seed = value(args.next(), "--seed")?,
+                "--ecosystem" => parsed.ecosystem = true,
+                "--flock" => parsed.flock = true,
+                _ => parsed.plan_path = Some(PathBuf::from(arg)),
+            }
+        }
+
+        if let Some(render) = &mut parsed.render {
+            render.seconds = seconds.unwrap_or(render.seconds);
+            render.fps = fps.unwrap_or(render.fps);
+            render.video = video;
+            if render.fps < MIN_RENDER_FPS {
+                return Err(ArgsError::TooFewFrames {
+                    min: MIN_RENDER_FPS,
+                });
+            }
+        }
+        if parsed.fixed_hz.is_some_and(|hz| hz < MIN_FIXED_HZ) {
+            return Err(ArgsError::TooLow {
+                name: "--fixed-hz",
+                min: MIN_FIXED_HZ,
+            });
+        }
+        if parsed.live_audio && !cfg!(feature = "audio-input") {
+            return Err(ArgsError::NeedsFeature {
+                name: "--audio-input",
+                feature: "audio-input",
+            });
+        }
+        Ok(parsed)
+    }
+}
+
+/// An option's value, if it has one.
+fn value<T: FromStr>(arg: Option<String>, name: &'static str) -> Result<Option<T>, ArgsError> {
+    arg.map(|arg| arg.parse().map_err(|_| ArgsError::Invalid { name, value: arg }))
+        .transpose()
+}
+
+/// Arguments that can't be run with.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgsError {
+    /// A value that doesn't parse.
+    Invalid {
+        name: &'static str,
+        value: String,
+    },
+    /// A value below the least that works.
+    TooLow {
+        name: &'static str,
+        min: f64,
+    },
+    /// A render frame rate too slow to simulate at.
+    TooFewFrames { min: u32 },
+    /// An option this build was compiled without.
+    NeedsFeature {
+        name: &'static str,
+        feature: &'static str,
+    },
+}
+
+impl fmt::Display for ArgsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArgsError::Invalid { name, value } => write!(f, "invalid value for {name}: {value}"),
+            ArgsError::TooLow { name, min } => write!(f, "{name} must be at least {min}"),
+            ArgsError::TooFewFrames { min } => {
+                write!(f, "--fps must be at least {min} frames per second to render")
+            }
+            ArgsError::NeedsFeature { name, feature } => {
+                write!(f, "{name} needs the {feature} feature")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ArgsError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, ArgsError> {
+        Args::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn parses_every_option() {
+        let args = parse(&[
+            "plan.ron",
+            "--audio",
+            "song.wav",
+            "--render",
+            "out",
+            "--seconds",
+            "2.5",
+            "--fps",
+            "60",
+            "--no-video",
+            "--fixed-hz",
+            "120",
+            "--evolve",
+            "3",
+            "--seed",
+            "7",
+            "--ecosystem",
+            "--flock",
+        ])
+        .unwrap();
+        assert_eq!(args.plan_path, Some(PathBuf::from("plan.ron")));
+        assert_eq!(args.audio_path, Some(PathBuf::from("song.wav")));
+        let render = args.render.unwrap();
+        assert_eq!(render.dir, PathBuf::from("out"));
+        assert_eq!(render.seconds, 2.5);
+        assert_eq!(render.fps, 60);
+        assert!(!render.video);
+        assert_eq!(args.fixed_hz, Some(120.0));
+        assert_eq!(args.generations, Some(3));
+        assert_eq!(args.seed, Some(7));
+        assert!(args.ecosystem && args.flock);
+        assert!(args.analyze_path.is_none() && !args.live_audio);
+    }
+
+    #[test]
+    fn render_options_need_a_render() {
+        let args = parse(&["--seconds", "2", "--fps", "1"]).unwrap();
+        assert!(args.render.is_none());
+        let render = parse(&["--render", "out"]).unwrap().render.unwrap();
+        assert_eq!(render.fps, RenderSettings::new("out").fps);
+        assert!(render.video);
+    }
+
+    #[test]
+    fn bad_values_are_errors() {
+        let err = parse(&["--seed", "lucky"]).unwrap_err();
+        assert_eq!(
+            err,
+            ArgsError::Invalid {
+                name: "--seed",
+                value: "lucky".into(),
+            }
+        );
+        assert_eq!(err.to_string(), "invalid value for --seed: lucky");
+    }
+
+    #[test]
+    fn fixed_rates_below_the_minimum_are_errors() {
+        let slow = (MIN_FIXED_HZ / 2.0).to_string();
+        assert_eq!(
+            parse(&["--fixed-hz", &slow]).unwrap_err(),
+            ArgsError::TooLow {
+                name: "--fixed-hz",
+                min: MIN_FIXED_HZ,
+            }
+        );
+        let hz = MIN_FIXED_HZ.to_string();
+        assert_eq!(parse(&["--fixed-hz", &hz]).unwrap().fixed_hz, Some(MIN_FIXED_HZ));
+    }
+
+    #[test]
+    fn renders_below_the_minimum_frame_rate_are_errors() {
+        let slow = (MIN_RENDER_FPS - 1).to_string();
+        let err = parse(&["--render", "out", "--fps", &slow]).unwrap_err();
+        assert_eq!(
+            err,
+            ArgsError::TooFewFrames {
+                min: MIN_RENDER_FPS,
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            format!("--fps must be at least {MIN_RENDER_FPS} frames per second to render")
+        );
+        let fps = MIN_RENDER_FPS.to_string();
+        let render = parse(&["--render", "out", "--fps", &fps]).unwrap().render;
+        assert_eq!(render.map(|render| render.fps), Some(MIN_RENDER_FPS));
+    }
+
+    #[test]
+    fn audio_input_needs_its_feature() {
+        let args = parse(&["--audio-input"]);
+        assert_eq!(args.is_ok(), cfg!(feature = "audio-input"));
+    }
+}
@@ -0,0 +1,212 @@
+use bevy::prelude::*;
+use std::ops::RangeInclusive;
+
+/// Where exponential stepping starts when the range reaches down to zero,
+/// which no ratio can step away from. Stepping down from here snaps to zero.
+const EXPONENTIAL_FLOOR: f32 = 0.01;
+
+/// How stepping moves a parameter through its range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepScale {
+    /// Each step adds a fixed amount.
+    Linear,
+    /// Each step multiplies by a fixed ratio. Step sizes are in semitones,
+    /// so 12 steps of 1.0 double the value.
+    Exponential,
+}
+
+/// Step size selected by the held modifier keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StepPrecision {
+    /// Shift held.
+    Fine,
+    #[default]
+    Normal,
+    /// Ctrl held.
+    Coarse,
+}
+
+impl StepPrecision {
+    pub fn from_keys(keys: &ButtonInput<KeyCode>) -> Self {
+        if keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+            StepPrecision::Fine
+        } else if keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+            StepPrecision::Coarse
+        } else {
+            StepPrecision::Normal
+        }
+    }
+}
+
+/// Range and stepping for one controllable parameter.
+#[derive(Debug, Clone, Copy)]
+pub struct ParamControl {
+    pub min: f32,
+    pub max: f32,
+    pub scale: StepScale,
+    pub step: f32,
+    pub fine_step: f32,
+    pub coarse_step: f32,
+}
+
+impl ParamControl {
+    pub fn range(&self) -> RangeInclusive<f32> {
+        self.min..=self.max
+    }
+
+    pub fn step_size(&self, precision: StepPrecision) -> f32 {
+        match precision {
+            StepPrecision::Fine => self.fine_step,
+            StepPrecision::Normal => self.step,
+            StepPrecision::Coarse => self.coarse_step,
+        }
+    }
+
+    /// Move a value by a (signed) number of steps, clamped to the range.
+    pub fn step(&self, value: f32, steps: f32, precision: StepPrecision) -> f32 {
+        let size = self.step_size(precision);
+        let stepped = match self.scale {
+            StepScale::Linear => value + steps * size,
+            StepScale::Exponential => {
+                let floor = if self.min > 0.0 {
+                    self.min
+                } else {
+                    EXPONENTIAL_FLOOR
+                };
+                if self.min <= 0.0 && steps < 0.0 && value <= floor {
+                    0.0
+                } else {
+                    value.max(floor) * 2f32.powf(steps * size / 12.0)
+                }
+            }
+        };
+        stepped.clamp(self.min, self.max)
+    }
+}
+
+/// Ranges and step sizes for every parameter the keyboard and panels control.
+#[derive(Resource, Debug, Clone)]
+pub struct ControlScheme {
+    /// Target frequency (Hz), stepped musically in semitones.
+    pub frequency: ParamControl,
+    pub amplitude: ParamControl,
+    /// Frequency transition time (seconds).
+    pub glide: ParamControl,
+    /// Phase offset (cycles). Phase wraps rather than clamping.
+    pub phase: ParamControl,
+    /// Transport tempo (BPM).
+    pub tempo: ParamControl,
+}
+
+impl Default for ControlScheme {
+    fn default() -> Self {
+        Self {
+            frequency: ParamControl {
+                min: 0.0,
+                max: 20.0,
+                scale: StepScale::Exponential,
+                step: 1.0,
+                fine_step: 0.1,
+                coarse_step: 12.0,
+            },
+            amplitude: ParamControl {
+                min: 0.0,
+                max: 1.5,
+                scale: StepScale::Linear,
+                step: 0.02,
+                fine_step: 0.005,
+                coarse_step: 0.1,
+            },
+            glide: ParamControl {
+                min: 0.0,
+                max: 5.0,
+                scale: StepScale::Linear,
+                step: 0.05,
+                fine_step: 0.01,
+                coarse_step: 0.25,
+            },
+            phase: ParamControl {
+                min: 0.0,
+                max: 1.0,
+                scale: StepScale::Linear,
+                step: 1.0 / 16.0,
+                fine_step: 1.0 / 64.0,
+                coarse_step: 1.0 / 4.0,
+            },
+            tempo: ParamControl {
+                min: 20.0,
+                max: 300.0,
+                scale: StepScale::Linear,
+                step: 1.0,
+                fine_step: 0.1,
+                coarse_step: 10.0,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-4
+    }
+
+    #[test]
+    fn twelve_semitones_double_the_value() {
+        let frequency = ControlScheme::default().frequency;
+        let mut hz = 1.0;
+        for _ in 0..12 {
+            hz = frequency.step(hz, 1.0, StepPrecision::Normal);
+        }
+        assert!(close(hz, 2.0));
+        assert!(close(frequency.step(2.0, -12.0, StepPrecision::Normal), 1.0));
+        // An octave in one coarse step, a tenth of a semitone in a fine one.
+        assert!(close(frequency.step(1.0, 1.0, StepPrecision::Coarse), 2.0));
+        assert!(close(
+            frequency.step(1.0, 10.0, StepPrecision::Fine),
+            frequency.step(1.0, 1.0, StepPrecision::Normal)
+        ));
+    }
+
+    #[test]
+    fn linear_steps_by_precision() {
+        let amplitude = ControlScheme::default().amplitude;
+        assert!(close(amplitude.step(0.5, 1.0, StepPrecision::Fine), 0.505));
+        assert!(close(amplitude.step(0.5, 1.0, StepPrecision::Normal), 0.52));
+        assert!(close(amplitude.step(0.5, -2.0, StepPrecision::Coarse), 0.3));
+    }
+
+    #[test]
+    fn steps_clamp_to_the_range() {
+        let scheme = ControlScheme::default();
+        assert_eq!(scheme.amplitude.step(1.45, 1.0, StepPrecision::Coarse), 1.5);
+        assert_eq!(scheme.amplitude.step(0.05, -1.0, StepPrecision::Coarse), 0.0);
+        assert_eq!(scheme.frequency.step(15.0, 1.0, StepPrecision::Coarse), 20.0);
+        let audible = ParamControl {
+            min: 20.0,
+            max: 20_000.0,
+            ..scheme.frequency
+        };
+        assert_eq!(audible.step(30.0, -1.0, StepPrecision::Coarse), 20.0);
+    }
+
+    #[test]
+    fn exponential_steps_leave_zero() {
+        let frequency = ControlScheme::default().frequency;
+        let up = frequency.step(0.0, 1.0, StepPrecision::Coarse);
+        assert!(close(up, 2.0 * EXPONENTIAL_FLOOR));
+        assert!(frequency.step(up, 1.0, StepPrecision::Coarse) > up);
+    }
+
+    #[test]
+    fn exponential_steps_come_back_down_to_zero() {
+        let frequency = ControlScheme::default().frequency;
+        let down = frequency.step(0.015, -1.0, StepPrecision::Coarse);
+        assert!(close(down, 0.0075));
+        assert_eq!(frequency.step(down, -1.0, StepPrecision::Fine), 0.0);
+        assert_eq!(frequency.step(EXPONENTIAL_FLOOR, -1.0, StepPrecision::Normal), 0.0);
+        assert_eq!(frequency.step(0.0, -1.0, StepPrecision::Normal), 0.0);
+    }
+}
@@ -1,7 +1,17 @@
+use bevy::ecs::system::{SystemParam, SystemState};
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{fmt, fs, io, path::Path};
 
-use crate::limb::{Limb, LimbAssetStore, LimbPlan, LimbSegmentTypeId};
-use crate::oscillator::{Oscillator, Wave};
+use crate::audio_analysis::AudioReactive;
+use crate::editor::EditorPreview;
+use crate::effects::Effects;
+use crate::lifecycle::{Dying, Spawning};
+use crate::limb::{Limb, LimbAssetStore, LimbPlan, LimbSegment, LimbSegmentTypeId, LimbSegments};
+use crate::oscillator::{HardSync, Oscillator, Wave};
+use crate::selection::Selection;
+use crate::sequencer::Sequencer;
+use crate::voices::VoiceAllocator;
 
 #[derive(Component)]
 #[require(Transform, Visibility, Children)]
@@ -11,21 +21,122 @@ pub struct Creature;
 #[require(Transform, Visibility)]
 pub struct CreatureBody;
 
-const BODY_RADIUS: f32 = 35.0;
+pub const BODY_RADIUS: f32 = 35.0;
 
 /// A creature plan is a list of limbs.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreaturePlan {
     pub limbs: Vec<LimbPlan>,
     pub transform: Transform,
+    /// Step pattern played on all limbs.
+    #[serde(default)]
+    pub sequencer: Option<Sequencer>,
+    /// Play the limbs as voices of a polyphonic synth.
+    #[serde(default)]
+    pub voices: Option<VoiceAllocator>,
+    /// Filter, delay, reverb and gain applied to the creature's sound.
+    #[serde(default)]
+    pub effects: Option<Effects>,
+}
+
+impl CreaturePlan {
+    /// The attachment angle of a limb: its own angle if set, otherwise its
+    /// place in an even distribution around the body.
+    pub fn limb_angle(&self, limb_index: usize) -> f32 {
+        self.limbs
+            .get(limb_index)
+            .and_then(|limb| limb.angle)
+            .unwrap_or_else(|| even_limb_angle(limb_index, self.limbs.len()))
+    }
+
+    /// Remove a limb, keeping the other limbs' sync and filter modulation
+    /// indices pointing at the same limbs.
+    pub fn remove_limb(&mut self, limb_index: usize) {
+        self.limbs.remove(limb_index);
+        for limb in &mut self.limbs {
+            limb.sync = match limb.sync {
+                Some(master) if master == limb_index => None,
+                Some(master) if master > limb_index => Some(master - 1),
+                sync => sync,
+            };
+        }
+        if let Some(filter) = self.effects.as_mut().and_then(|e| e.filter.as_mut()) {
+            filter.modulation = filter.modulation.and_then(|mut modulation| {
+                if modulation.limb == limb_index {
+                    return None;
+                }
+                if modulation.limb > limb_index {
+                    modulation.limb -= 1;
+                }
+                Some(modulation)
+            });
+        }
+    }
+}
+
+fn even_limb_angle(limb_index: usize, limb_count: usize) -> f32 {
+    std::f32::consts::TAU * limb_index as f32 / limb_count.max(1) as f32
 }
 
 /// A collection of creatures to spawn, with a transform applied to the grouparent.
-#[derive(Resource, Debug, Clone)]
+#[derive(Resource, Debug, Clone, Default, Serialize, Deserialize)]
 pub struct CreaturesPlan {
     pub creatures: Vec<CreaturePlan>,
 }
 
+impl CreaturesPlan {
+    /// Load a plan from a RON file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, PlanError> {
+        let text = fs::read_to_string(path)?;
+        Ok(ron::from_str(&text)?)
+    }
+
+    /// Save the plan to a RON file, in the same format `load` reads.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), PlanError> {
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?;
+        fs::write(path, text)?;
+        Ok(())
+    }
+}
+
+/// Errors from reading or writing plan files.
+#[derive(Debug)]
+pub enum PlanError {
+    Io(io::Error),
+    Parse(ron::error::SpannedError),
+    Serialize(ron::Error),
+}
+
+impl fmt::Display for PlanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlanError::Io(err) => write!(f, "plan file io error: {err}"),
+            PlanError::Parse(err) => write!(f, "plan file parse error: {err}"),
+            PlanError::Serialize(err) => write!(f, "plan serialize error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for PlanError {}
+
+impl From<io::Error> for PlanError {
+    fn from(err: io::Error) -> Self {
+        PlanError::Io(err)
+    }
+}
+
+impl From<ron::error::SpannedError> for PlanError {
+    fn from(err: ron::error::SpannedError) -> Self {
+        PlanError::Parse(err)
+    }
+}
+
+impl From<ron::Error> for PlanError {
+    fn from(err: ron::Error) -> Self {
+        PlanError::Serialize(err)
+    }
+}
+
 /// Build an example plan:
 /// - 6 creatures, spread around a circle
 /// - each with 8 limbs
@@ -51,6 +162,9 @@ pub fn example_creatures_plan() -> CreaturesPlan {
     let limb = LimbPlan {
         oscillator: oscillator.clone(),
         segments: segments.clone(),
+        angle: None,
+        sync: None,
+        audio: None,
     };
 
     let creature_count = 6usize;
@@ -63,6 +177,9 @@ pub fn example_creatures_plan() -> CreaturesPlan {
             CreaturePlan {
                 limbs: vec![limb.clone(); limb_count],
                 transform: Transform::from_translation(pos),
+                sequencer: None,
+                voices: None,
+                effects: None,
             }
         })
         .collect();
@@ -70,77 +187,506 @@ pub fn example_creatures_plan() -> CreaturesPlan {
     CreaturesPlan { creatures }
 }
 
+/// `count` of the example creatures (8 limbs × 16 segments each) in a grid,
+/// for measuring how the simulation copes at scale.
+pub fn benchmark_creatures_plan(count: usize) -> CreaturesPlan {
+    let spacing = 1000.0;
+    let columns = (count as f32).sqrt().ceil().max(1.0) as usize;
+    let creature = example_creatures_plan().creatures.swap_remove(0);
+    let creatures = (0..count)
+        .map(|i| {
+            let (column, row) = ((i % columns) as f32, (i / columns) as f32);
+            CreaturePlan {
+                transform: Transform::from_xyz(column * spacing, row * spacing, 0.0),
+                ..creature.clone()
+            }
+        })
+        .collect();
+    CreaturesPlan { creatures }
+}
+
+/// Numbers creatures as they're spawned, so names stay unique as others
+/// despawn.
+#[derive(Resource, Debug, Default)]
+pub struct CreatureNames {
+    spawned: usize,
+    bred: usize,
+}
+
+impl CreatureNames {
+    /// A name no creature has had before.
+    pub fn next_name(&mut self) -> Name {
+        let name = Name::new(format!("Creature {}", self.spawned));
+        self.spawned += 1;
+        name
+    }
+
+    /// A name no bred creature has had before, from "Child 1" on.
+    pub fn next_child_name(&mut self) -> Name {
+        self.bred += 1;
+        Name::new(format!("Child {}", self.bred))
+    }
+}
+
+/// Where a creature comes in the order creatures were spawned, for listing
+/// them; entity indices are reused after despawns, so they can't be.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SpawnOrder(pub u64);
+
+/// Creatures spawned so far, to number the next one's SpawnOrder.
+#[derive(Resource, Debug, Default)]
+struct SpawnCount(u64);
+
 /// Spawn all creatures described by the CreaturesPlan resource.
-/// Each creature's body type is inferred from its first limb's first segment type.
 pub fn spawn_creatures(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
     mut store: ResMut<LimbAssetStore>,
+    mut names: ResMut<CreatureNames>,
     plans: Res<CreaturesPlan>,
 ) {
-    let body_mesh = meshes.add(Circle::new(BODY_RADIUS));
-    let body_mat = materials.add(Color::srgb(0.3, 0.05, 0.4));
-
-    for (creature_i, creature_plan) in plans.creatures.iter().enumerate() {
-        // Create the creature entity with its own transform (no shared root).
-        let creature = commands
-            .spawn((
-                Creature,
-                Name::new(format!("Creature {creature_i}")),
-                creature_plan.transform.clone(),
-            ))
-            .id();
-
-        // Visual body
-        commands.entity(creature).with_children(|parent| {
-            parent.spawn((
-                CreatureBody,
-                Name::new("Body"),
-                Mesh2d(body_mesh.clone()),
-                MeshMaterial2d(body_mat.clone()),
-                Transform::from_translation(Vec3::new(0.0, 0.0, -0.1)),
-            ));
+    for creature_plan in &plans.creatures {
+        spawn_creature(
+            &mut commands,
+            &mut meshes,
+            &mut materials,
+            &mut store,
+            creature_plan,
+            names.next_name(),
+        );
+    }
+}
+
+/// A spawned creature and its limbs, in plan order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpawnedCreature {
+    pub creature: Entity,
+    pub limbs: Vec<Entity>,
+}
+
+/// Spawn a single creature from its plan, returning it and its limbs.
+/// The creature's body type is inferred from its first limb's first segment type.
+pub fn spawn_creature(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    store: &mut LimbAssetStore,
+    creature_plan: &CreaturePlan,
+    name: Name,
+) -> SpawnedCreature {
+    let spawned = SpawnedCreature {
+        creature: commands.spawn_empty().id(),
+        limbs: creature_plan
+            .limbs
+            .iter()
+            .map(|_| commands.spawn_empty().id())
+            .collect(),
+    };
+    build_creature(
+        commands,
+        meshes,
+        materials,
+        store,
+        creature_plan,
+        name,
+        &spawned,
+    );
+    spawned
+}
+
+/// Fill in already reserved creature and limb entities from a plan.
+fn build_creature(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    store: &mut LimbAssetStore,
+    creature_plan: &CreaturePlan,
+    name: Name,
+    spawned: &SpawnedCreature,
+) {
+    // The creature entity has its own transform (no shared root).
+    let SpawnedCreature { creature, limbs } = spawned;
+    let creature = *creature;
+    commands
+        .entity(creature)
+        .insert((Creature, name, creature_plan.transform));
+    commands.queue(move |world: &mut World| {
+        let mut count = world.get_resource_or_init::<SpawnCount>();
+        let order = SpawnOrder(count.0);
+        count.0 += 1;
+        if let Ok(mut entity) = world.get_entity_mut(creature) {
+            entity.insert(order);
+        }
+    });
+    if let Some(sequencer) = &creature_plan.sequencer {
+        commands.entity(creature).insert(sequencer.clone());
+    }
+    if let Some(voices) = creature_plan.voices {
+        commands.entity(creature).insert(voices);
+    }
+    if let Some(effects) = creature_plan.effects {
+        commands.entity(creature).insert(effects);
+    }
+
+    // Visual body
+    let body_type = creature_plan
+        .limbs
+        .first()
+        .and_then(|limb| limb.segments.first())
+        .copied()
+        .unwrap_or(LimbSegmentTypeId::Rectangle);
+    body_type.ensure_assets(store, meshes, materials);
+    commands.entity(creature).with_children(|parent| {
+        body_type.spawn_body(parent, store).insert(CreatureBody);
+    });
+
+    // Limbs for this creature (distributed evenly around a circle by default).
+    for (limb_index, (limb_plan, &limb)) in creature_plan.limbs.iter().zip(limbs).enumerate() {
+        let angle = creature_plan.limb_angle(limb_index);
+        let limb_oscillator: Oscillator = limb_plan.oscillator.clone();
+
+        commands.entity(limb).insert((
+            Limb,
+            limb_oscillator,
+            Name::new(format!("Limb {limb_index}")),
+            Transform::from_rotation(Quat::from_rotation_z(angle)),
+        ));
+
+        commands.entity(creature).add_children(&[limb]);
+        if let Some(audio) = limb_plan.audio {
+            commands.entity(limb).insert(audio);
+        }
+
+        // Build the chain of segments for this limb.
+        let mut current_parent = limb;
+        for (segment_index, type_id) in limb_plan.segments.iter().copied().enumerate() {
+            // Ensure assets for this segment type exist.
+            type_id.ensure_assets(store, meshes, materials);
+
+            // Spawn the segment and get the outgoing joint to chain the next one.
+            let next_joint = type_id.spawn_segment(
+                commands,
+                current_parent,
+                limb,
+                limb_index,
+                segment_index,
+                store,
+            );
+            current_parent = next_joint;
+        }
+    }
+
+    // Hard sync between limbs, ignoring self-sync and missing limbs.
+    for (limb_index, limb_plan) in creature_plan.limbs.iter().enumerate() {
+        if let Some(master) = limb_plan.sync.filter(|master| *master != limb_index)
+            && let Some(&master) = limbs.get(master)
+        {
+            commands
+                .entity(limbs[limb_index])
+                .insert(HardSync { master });
+        }
+    }
+}
+
+type SpawnParams = (
+    Commands<'static, 'static>,
+    ResMut<'static, Assets<Mesh>>,
+    ResMut<'static, Assets<ColorMaterial>>,
+    ResMut<'static, LimbAssetStore>,
+);
+
+/// Spawning and despawning creatures from any system, at any time.
+pub trait CreatureCommands {
+    /// Spawn a creature from its plan, growing in. The returned entities can
+    /// be used straight away; their components arrive when commands are
+    /// applied.
+    fn spawn_creature(&mut self, plan: CreaturePlan) -> SpawnedCreature;
+
+    /// Despawn a creature and its limbs once it has curled up and faded
+    /// out, deselecting it straight away.
+    fn despawn_creature(&mut self, creature: Entity);
+}
+
+impl CreatureCommands for Commands<'_, '_> {
+    fn spawn_creature(&mut self, plan: CreaturePlan) -> SpawnedCreature {
+        let spawned = SpawnedCreature {
+            creature: self.spawn_empty().id(),
+            limbs: plan.limbs.iter().map(|_| self.spawn_empty().id()).collect(),
+        };
+        let reserved = spawned.clone();
+        self.queue(move |world: &mut World| {
+            let name = world
+                .get_resource_or_init::<CreatureNames>()
+                .next_name();
+            let mut state: SystemState<SpawnParams> = SystemState::new(world);
+            let (mut commands, mut meshes, mut materials, mut store) = state.get_mut(world);
+            build_creature(
+                &mut commands,
+                &mut meshes,
+                &mut materials,
+                &mut store,
+                &plan,
+                name,
+                &reserved,
+            );
+            state.apply(world);
+            world
+                .entity_mut(reserved.creature)
+                .insert((Spawning::default(), Visibility::Hidden));
         });
+        spawned
+    }
 
-        // Limbs for this creature (distributed evenly around a circle).
-        let limb_count = creature_plan.limbs.len().max(1);
-        for (limb_index, limb_plan) in creature_plan.limbs.iter().enumerate() {
-            let angle = std::f32::consts::TAU * limb_index as f32 / limb_count as f32;
-            let limb_oscillator: Oscillator = limb_plan.oscillator.clone();
-
-            let limb = commands
-                .spawn((
-                    Limb,
-                    limb_oscillator,
-                    Name::new(format!("Limb {limb_index}")),
-                    Transform::from_rotation(Quat::from_rotation_z(angle)),
-                ))
-                .id();
-
-            commands.entity(creature).add_children(&[limb]);
-
-            // Build the chain of segments for this limb.
-            let mut current_parent = limb;
-            for (segment_index, type_id) in creature_plan.limbs[limb_index]
-                .segments
-                .iter()
-                .copied()
-                .enumerate()
-            {
-                // Ensure assets for this segment type exist.
-                type_id.ensure_assets(&mut store, &mut meshes, &mut materials);
-
-                // Spawn the segment and get the outgoing joint to chain the next one.
-                let next_joint = type_id.spawn_segment(
-                    &mut commands,
-                    current_parent,
-                    limb_index,
-                    segment_index,
-                    &store,
-                );
-                current_parent = next_joint;
+    fn despawn_creature(&mut self, creature: Entity) {
+        self.queue(move |world: &mut World| {
+            if world.get::<Creature>(creature).is_none() {
+                warn!("despawn_creature: {creature} is not a creature");
+                return;
             }
+            let mut entity = world.entity_mut(creature);
+            if !entity.contains::<Dying>() {
+                entity.remove::<Spawning>().insert(Dying::default());
+            }
+            if let Some(mut selection) = world.get_resource_mut::<Selection>() {
+                selection.forget(creature);
+            }
+        });
+    }
+}
+
+type LimbData = (
+    &'static Oscillator,
+    &'static Transform,
+    Option<&'static HardSync>,
+    Option<&'static AudioReactive>,
+);
+
+/// Read-only access to spawned creatures as plans, e.g. to export the live
+/// world or to rebuild a creature after editing.
+#[derive(SystemParam)]
+pub struct CreaturePlans<'w, 's> {
+    creatures: Query<
+        'w,
+        's,
+        (Entity, &'static Transform, &'static Children),
+        (With<Creature>, Without<EditorPreview>),
+    >,
+    limbs: Query<'w, 's, LimbData, With<Limb>>,
+    limb_segments: Query<'w, 's, &'static LimbSegments>,
+    segments: Query<'w, 's, &'static LimbSegment>,
+    names: Query<'w, 's, &'static Name>,
+    orders: Query<'w, 's, &'static SpawnOrder>,
+    sequencers: Query<'w, 's, &'static Sequencer>,
+    voices: Query<'w, 's, &'static VoiceAllocator>,
+    effects: Query<'w, 's, &'static Effects>,
+}
+
+impl CreaturePlans<'_, '_> {
+    /// Every spawned creature, in spawn order, leaving out the editor's
+    /// preview.
+    pub fn entities(&self) -> Vec<Entity> {
+        let mut entities: Vec<Entity> = self.creatures.iter().map(|(e, _, _)| e).collect();
+        entities.sort_by_key(|e| self.orders.get(*e).ok().copied());
+        entities
+    }
+
+    pub fn name(&self, creature: Entity) -> Option<&Name> {
+        self.names.get(creature).ok()
+    }
+
+    /// The limb entities of a creature, in spawn order.
+    pub fn limbs(&self, creature: Entity) -> Vec<Entity> {
+        let Ok((_, _, children)) = self.creatures.get(creature) else {
+            return Vec::new();
+        };
+        children
+            .iter()
+            .filter(|child| self.limbs.contains(*child))
+            .collect()
+    }
+
+    /// Rebuild the plan of a spawned creature from its current state.
+    pub fn get(&self, creature: Entity) -> Option<CreaturePlan> {
+        let (_, transform, _) = self.creatures.get(creature).ok()?;
+        let limb_entities = self.limbs(creature);
+        let limb_count = limb_entities.len();
+        let limbs = limb_entities
+            .iter()
+            .enumerate()
+            .filter_map(|(limb_index, &limb)| {
+                let (oscillator, limb_transform, sync, audio) = self.limbs.get(limb).ok()?;
+                // Only record angles that differ from the even distribution.
+                let (_, _, angle) = limb_transform.rotation.to_euler(EulerRot::XYZ);
+                let angle = angle.rem_euclid(std::f32::consts::TAU);
+                let even = even_limb_angle(limb_index, limb_count);
+                let offset = (angle - even).rem_euclid(std::f32::consts::TAU);
+                let is_even = offset.min(std::f32::consts::TAU - offset) < 1e-3;
+                // Already in order, from the body outwards.
+                let segments = self
+                    .limb_segments
+                    .get(limb)
+                    .map(|segments| {
+                        segments
+                            .iter()
+                            .filter_map(|e| self.segments.get(e).ok())
+                            .map(|segment| segment.type_id)
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                Some(LimbPlan {
+                    oscillator: oscillator.clone(),
+                    segments,
+                    angle: (!is_even).then_some(angle),
+                    sync: sync
+                        .and_then(|sync| limb_entities.iter().position(|e| *e == sync.master)),
+                    audio: audio.copied(),
+                })
+            })
+            .collect();
+        Some(CreaturePlan {
+            limbs,
+            transform: *transform,
+            sequencer: self.sequencers.get(creature).ok().cloned(),
+            voices: self.voices.get(creature).ok().copied(),
+            effects: self.effects.get(creature).ok().copied(),
+        })
+    }
+}
+
+/// Replace a spawned creature with a fresh one built from the given plan.
+#[derive(Message, Debug, Clone)]
+pub struct RespawnCreature {
+    pub creature: Entity,
+    pub plan: CreaturePlan,
+}
+
+/// Despawn and rebuild creatures for each RespawnCreature message, keeping
+/// their names and places in the spawn order, and carrying over the
+/// selection.
+pub fn respawn_creatures(
+    mut commands: Commands,
+    mut messages: MessageReader<RespawnCreature>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut store: ResMut<LimbAssetStore>,
+    mut selection: ResMut<Selection>,
+    names: Query<(&Name, Option<&SpawnOrder>), With<Creature>>,
+) {
+    for RespawnCreature { creature, plan } in messages.read() {
+        let Ok((name, order)) = names.get(*creature) else {
+            continue;
+        };
+        let name = name.clone();
+        commands.entity(*creature).despawn();
+        let respawned = spawn_creature(
+            &mut commands,
+            &mut meshes,
+            &mut materials,
+            &mut store,
+            plan,
+            name,
+        )
+        .creature;
+        if let Some(&order) = order {
+            commands.entity(respawned).insert(order);
         }
+        if selection.creature == Some(*creature) {
+            selection.creature = Some(respawned);
+            selection.limb = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lifecycle::Lifecycle;
+    use crate::testing::Harness;
+    use bevy::ecs::system::RunSystemOnce;
+
+    #[test]
+    fn spawned_creatures_read_back_as_their_plan() {
+        let mut plan = example_creatures_plan();
+        plan.creatures[0].limbs[1].sync = Some(0);
+        plan.creatures[1].limbs[2].angle = Some(1.0);
+        let mut harness = Harness::new(plan.clone());
+
+        let read = harness
+            .app
+            .world_mut()
+            .run_system_once(|plans: CreaturePlans| CreaturesPlan {
+                creatures: plans
+                    .entities()
+                    .into_iter()
+                    .filter_map(|creature| plans.get(creature))
+                    .collect(),
+            })
+            .unwrap();
+        let ron = |plan: &CreaturesPlan| ron::to_string(plan).unwrap();
+        assert_eq!(ron(&read), ron(&plan));
+    }
+
+    #[test]
+    fn spawns_and_despawns_at_runtime() {
+        let mut harness = Harness::new(CreaturesPlan::default());
+        let mut plan = example_creatures_plan().creatures.remove(0);
+        plan.limbs.truncate(3);
+
+        let spawned = harness
+            .app
+            .world_mut()
+            .run_system_once(move |mut commands: Commands| commands.spawn_creature(plan.clone()))
+            .unwrap();
+        harness.advance(0.1);
+        assert_eq!(harness.creatures(), vec![spawned.creature]);
+        assert_eq!(harness.limbs(spawned.creature), spawned.limbs);
+        for &limb in &spawned.limbs {
+            assert_eq!(harness.segments(limb).len(), 16);
+            assert!(harness.segment_rotations(limb)[0] != 0.0);
+        }
+
+        harness
+            .app
+            .world_mut()
+            .run_system_once(move |mut commands: Commands| {
+                commands.despawn_creature(spawned.creature)
+            })
+            .unwrap();
+        assert_eq!(harness.creatures(), vec![spawned.creature]);
+        harness.advance(Lifecycle::default().death_secs as f64 + 0.1);
+        assert!(harness.creatures().is_empty());
+        assert!(harness.world().get_entity(spawned.limbs[0]).is_err());
+    }
+
+    #[test]
+    fn names_stay_unique_after_despawning() {
+        let mut harness = Harness::new(example_creatures_plan());
+        let first = harness.creatures()[0];
+        let plan = example_creatures_plan().creatures.remove(0);
+        harness
+            .app
+            .world_mut()
+            .run_system_once(move |mut commands: Commands| commands.despawn_creature(first))
+            .unwrap();
+        harness.advance(Lifecycle::default().death_secs as f64 + 0.1);
+        let world = harness.app.world_mut();
+        world
+            .run_system_once(move |mut commands: Commands| {
+                commands.spawn_creature(plan.clone());
+            })
+            .unwrap();
+
+        let mut names: Vec<String> = world
+            .query_filtered::<&Name, With<Creature>>()
+            .iter(world)
+            .map(|name| name.to_string())
+            .collect();
+        let count = names.len();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), count);
+        assert_eq!(count, 6);
+        assert!(names.contains(&"Creature 6".to_string()));
     }
 }
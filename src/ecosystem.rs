@@ -0,0 +1,370 @@
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::creature::{Creature, CreatureCommands, CreaturePlans};
+use crate::editor::EditorPreview;
+use crate::evolution::Genome;
+use crate::lifecycle::{Dying, Spawning};
+use crate::limb::Limb;
+use crate::oscillator::Oscillator;
+use crate::steering::{Seek, Steering, Target};
+
+const FOOD_RADIUS: f32 = 6.0;
+const FOOD_COLOR: Color = Color::srgb(0.55, 0.9, 0.35);
+/// How close a creature's center must come to food to eat it.
+const EAT_RADIUS: f32 = crate::creature::BODY_RADIUS + FOOD_RADIUS;
+
+/// Food, energy, births and deaths: the creatures as a living aquarium.
+#[derive(Resource, Debug)]
+pub struct Ecosystem {
+    /// Food appears within this distance of the origin.
+    pub radius: f32,
+    pub food_per_sec: f32,
+    pub max_food: usize,
+    /// Energy in each piece of food.
+    pub food_energy: f32,
+    /// Energy creatures start with, unless born to a parent.
+    pub start_energy: f32,
+    /// Energy drained per second just by being alive.
+    pub metabolism: f32,
+    /// Energy drained per second per unit of limb activity (amplitude ×
+    /// frequency, summed over limbs).
+    pub drain: f32,
+    /// Speed (units per second) per unit of limb activity.
+    pub speed: f32,
+    /// Fastest turn toward food (radians per second).
+    pub turn_rate: f32,
+    /// Creatures with this much energy split it with a child.
+    pub birth_energy: f32,
+    /// No births while there are this many creatures.
+    pub max_creatures: usize,
+    /// Chance of each trait mutating in a child.
+    pub mutation_rate: f32,
+    /// Food owed by the spawn rate, in pieces.
+    food_due: f32,
+    rng: StdRng,
+}
+
+impl Default for Ecosystem {
+    fn default() -> Self {
+        Self {
+            radius: 700.0,
+            food_per_sec: 2.0,
+            max_food: 60,
+            food_energy: 10.0,
+            start_energy: 30.0,
+            metabolism: 0.5,
+            drain: 1.0,
+            speed: 60.0,
+            turn_rate: 1.5,
+            birth_energy: 60.0,
+            max_creatures: 40,
+            mutation_rate: 0.1,
+            food_due: 0.0,
+            rng: StdRng::from_os_rng(),
+        }
+    }
+}
+
+impl Ecosystem {
+    pub fn seeded(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            ..default()
+        }
+    }
+}
+
+/// A piece of food, worth `energy` to whoever eats it.
+#[derive(Component, Debug, Clone, Copy)]
+#[require(Transform, Visibility)]
+pub struct Food {
+    pub energy: f32,
+}
+
+/// A creature's energy: gained by eating, spent by moving its limbs. It
+/// dies at zero.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct Energy(pub f32);
+
+#[derive(Resource)]
+pub struct FoodAssets {
+    mesh: Handle<Mesh>,
+    material: Handle<ColorMaterial>,
+}
+
+pub fn setup_food_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    commands.insert_resource(FoodAssets {
+        mesh: meshes.add(Circle::new(FOOD_RADIUS)),
+        material: materials.add(FOOD_COLOR),
+    });
+}
+
+/// Scatter food around the aquarium at the ecosystem's rate.
+pub fn spawn_food(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut ecosystem: ResMut<Ecosystem>,
+    assets: Res<FoodAssets>,
+    food: Query<(), With<Food>>,
+) {
+    ecosystem.food_due += ecosystem.food_per_sec * time.delta_secs();
+    let mut count = food.iter().count();
+    while ecosystem.food_due >= 1.0 {
+        ecosystem.food_due -= 1.0;
+        if count >= ecosystem.max_food {
+            continue;
+        }
+        // Uniform over the disk.
+        let radius = ecosystem.radius * ecosystem.rng.random::<f32>().sqrt();
+        let angle = ecosystem.rng.random_range(0.0..std::f32::consts::TAU);
+        let position = radius * Vec2::from_angle(angle);
+        commands.spawn((
+            Food {
+                energy: ecosystem.food_energy,
+            },
+            Name::new("Food"),
+            Mesh2d(assets.mesh.clone()),
+            MeshMaterial2d(assets.material.clone()),
+            Transform::from_translation(position.extend(-1.0)),
+        ));
+        count += 1;
+    }
+}
+
+/// Give creatures their starting energy as they appear. The editor's preview
+/// stays out of the ecosystem.
+pub fn add_energy(
+    mut commands: Commands,
+    ecosystem: Res<Ecosystem>,
+    creatures: Query<Entity, (Added<Creature>, Without<Energy>, Without<EditorPreview>)>,
+) {
+    for creature in &creatures {
+        commands
+            .entity(creature)
+            .insert(Energy(ecosystem.start_energy));
+    }
+}
+
+type Forager<'a> = (
+    Entity,
+    &'a mut Transform,
+    &'a mut Energy,
+    &'a Children,
+    Has<Steering>,
+);
+type Foraging = (
+    With<Creature>,
+    Without<Spawning>,
+    Without<Dying>,
+    Without<EditorPreview>,
+);
+
+/// Turn creatures toward the nearest food and swim them forward, as fast as
+/// their limbs are busy, paying for it in energy. Steering creatures seek the
+/// food instead.
+pub fn forage(
+    mut commands: Commands,
+    time: Res<Time>,
+    ecosystem: Res<Ecosystem>,
+    mut creatures: Query<Forager, Foraging>,
+    limbs: Query<&Oscillator, With<Limb>>,
+    food: Query<&Transform, (With<Food>, Without<Creature>)>,
+) {
+    let dt = time.delta_secs();
+    for (creature, mut transform, mut energy, children, steering) in &mut creatures {
+        let activity: f32 = children
+            .iter()
+            .filter_map(|child| limbs.get(child).ok())
+            .map(|osc| osc.amplitude() * osc.effective_frequency())
+            .sum();
+        energy.0 -= (ecosystem.metabolism + ecosystem.drain * activity) * dt;
+
+        let position = transform.translation.truncate();
+        let nearest = food
+            .iter()
+            .map(|food| food.translation.truncate())
+            .min_by(|a, b| {
+                a.distance_squared(position)
+                    .total_cmp(&b.distance_squared(position))
+            });
+        if steering {
+            match nearest {
+                Some(target) => commands.entity(creature).insert(Seek {
+                    target: Target::Point(target),
+                    weight: 1.0,
+                }),
+                None => commands.entity(creature).remove::<Seek>(),
+            };
+            continue;
+        }
+        if let Some(target) = nearest {
+            let heading = (transform.rotation * Vec3::X).truncate();
+            let turn = heading.angle_to(target - position);
+            let max_turn = ecosystem.turn_rate * dt;
+            transform.rotate_z(turn.clamp(-max_turn, max_turn));
+        }
+        let forward = transform.rotation * Vec3::X;
+        transform.translation += forward * ecosystem.speed * activity * dt;
+    }
+}
+
+/// Creatures eat any food they reach.
+pub fn eat(
+    mut commands: Commands,
+    mut creatures: Query<(&Transform, &mut Energy), Foraging>,
+    food: Query<(Entity, &Transform, &Food), Without<Creature>>,
+) {
+    let mut eaten = Vec::new();
+    for (transform, mut energy) in &mut creatures {
+        let position = transform.translation.truncate();
+        for (entity, food_transform, food) in &food {
+            if !eaten.contains(&entity)
+                && food_transform.translation.truncate().distance(position) <= EAT_RADIUS
+            {
+                energy.0 += food.energy;
+                eaten.push(entity);
+                commands.entity(entity).despawn();
+            }
+        }
+    }
+}
+
+/// Well-fed creatures split their energy with a mutated copy of themselves;
+/// exhausted ones die.
+pub fn live_and_die(
+    mut commands: Commands,
+    mut ecosystem: ResMut<Ecosystem>,
+    plans: CreaturePlans,
+    mut creatures: Query<(Entity, &mut Energy), Foraging>,
+) {
+    let mut population = plans.entities().len();
+    for (creature, mut energy) in &mut creatures {
+        if energy.0 <= 0.0 {
+            commands.despawn_creature(creature);
+            continue;
+        }
+        if energy.0 < ecosystem.birth_energy || population >= ecosystem.max_creatures {
+            continue;
+        }
+        let Some(plan) = plans.get(creature) else {
+            continue;
+        };
+        let mut genome = Genome::from_plan(&plan);
+        let rate = ecosystem.mutation_rate;
+        genome.mutate(rate, &mut ecosystem.rng);
+        // Born just behind the parent.
+        let behind = plan.transform.rotation * Vec3::NEG_X * 3.0 * EAT_RADIUS;
+        let child = genome.to_plan(Transform::from_translation(
+            plan.transform.translation + behind,
+        ));
+
+        energy.0 /= 2.0;
+        let child = commands.spawn_creature(child);
+        commands.entity(child.creature).insert(*energy);
+        population += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::creature::{example_creatures_plan, CreaturesPlan};
+    use crate::lifecycle::Lifecycle;
+    use crate::testing::Harness;
+    use bevy::ecs::system::RunSystemOnce;
+
+    /// One example creature at the origin, facing +x, with no food falling.
+    fn aquarium() -> Harness {
+        let mut plan = example_creatures_plan();
+        plan.creatures.truncate(1);
+        plan.creatures[0].transform = Transform::default();
+        let mut harness = Harness::new(CreaturesPlan::default());
+        harness
+            .app
+            .insert_resource(plan)
+            .insert_resource(Ecosystem {
+                food_per_sec: 0.0,
+                ..Ecosystem::seeded(7)
+            })
+            .add_systems(
+                Update,
+                (spawn_food, add_energy, forage, eat, live_and_die)
+                    .chain()
+                    .after(crate::limb::animate_limb_segments),
+            );
+        let world = harness.app.world_mut();
+        world.run_system_once(setup_food_assets).unwrap();
+        world
+            .run_system_once(crate::creature::spawn_creatures)
+            .unwrap();
+        harness.advance(1.0 / 60.0);
+        harness
+    }
+
+    fn energy(harness: &Harness, creature: Entity) -> f32 {
+        harness.world().get::<Energy>(creature).unwrap().0
+    }
+
+    #[test]
+    fn creatures_seek_out_food() {
+        let mut harness = aquarium();
+        let creature = harness.creatures()[0];
+        let food = harness
+            .app
+            .world_mut()
+            .spawn((
+                Food { energy: 10.0 },
+                Transform::from_xyz(-60.0, 120.0, 0.0),
+            ))
+            .id();
+        let before = energy(&harness, creature);
+
+        harness.advance(5.0);
+        assert!(harness.world().get_entity(food).is_err());
+        // Fed more than it spent getting there.
+        assert!(
+            energy(&harness, creature) > before,
+            "{}",
+            energy(&harness, creature)
+        );
+    }
+
+    #[test]
+    fn well_fed_creatures_split_their_energy() {
+        let mut harness = aquarium();
+        let parent = harness.creatures()[0];
+        harness
+            .app
+            .world_mut()
+            .entity_mut(parent)
+            .insert(Energy(100.0));
+        harness.advance(1.0 / 60.0);
+
+        let creatures = harness.creatures();
+        assert_eq!(creatures.len(), 2);
+        assert!((energy(&harness, parent) - 50.0).abs() < 0.1);
+        assert_eq!(energy(&harness, parent), energy(&harness, creatures[1]));
+    }
+
+    #[test]
+    fn exhausted_creatures_die() {
+        let mut harness = aquarium();
+        let creature = harness.creatures()[0];
+        harness
+            .app
+            .world_mut()
+            .entity_mut(creature)
+            .insert(Energy(1.0));
+        harness.advance(1.0);
+        assert!(harness.world().get::<Dying>(creature).is_some());
+
+        harness.advance(Lifecycle::default().death_secs as f64);
+        assert!(harness.creatures().is_empty());
+    }
+}
@@ -0,0 +1,594 @@
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::audio_analysis::AudioReactive;
+use crate::controls::ControlScheme;
+use crate::creature::{
+    spawn_creature, Creature, CreaturePlan, CreaturePlans, CreaturesPlan, BODY_RADIUS,
+};
+use crate::effects::Effects;
+use crate::inspector::{
+    audio_controls, effects_controls, oscillator_controls, sequencer_controls, sync_controls,
+    voice_controls,
+};
+use crate::limb::{Limb, LimbAssetStore, LimbPlan, LimbSegmentTypeId};
+use crate::oscillator::{Oscillator, Wave};
+use crate::selection::Selection;
+use crate::sequencer::Sequencer;
+use crate::voices::VoiceAllocator;
+
+/// Whether the app is performing creatures or editing one.
+#[derive(States, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum AppMode {
+    #[default]
+    Play,
+    Edit,
+}
+
+/// F2 switches between play and editor modes.
+pub fn toggle_editor(
+    keys: Res<ButtonInput<KeyCode>>,
+    mode: Res<State<AppMode>>,
+    mut next_mode: ResMut<NextState<AppMode>>,
+) {
+    if keys.just_pressed(KeyCode::F2) {
+        next_mode.set(match mode.get() {
+            AppMode::Play => AppMode::Edit,
+            AppMode::Edit => AppMode::Play,
+        });
+    }
+}
+
+/// Marks the creature spawned as the editor's live preview.
+#[derive(Component)]
+pub struct EditorPreview;
+
+/// The creature being built in the editor, and the editor's UI state.
+#[derive(Resource, Debug, Clone)]
+pub struct Editor {
+    pub plan: CreaturePlan,
+    /// Where "Save" writes (and "Load" reads) the plan file.
+    pub path: String,
+    selected_limb: Option<usize>,
+    new_segment_type: LimbSegmentTypeId,
+    preview: Option<Entity>,
+    rebuild: bool,
+    dragging: bool,
+    status: Option<String>,
+}
+
+impl Editor {
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            plan: CreaturePlan {
+                limbs: vec![default_limb(); 4],
+                transform: Transform::default(),
+                sequencer: None,
+                voices: None,
+                effects: None,
+            },
+            path: path.into(),
+            selected_limb: Some(0),
+            new_segment_type: LimbSegmentTypeId::Rectangle,
+            preview: None,
+            rebuild: true,
+            dragging: false,
+            status: None,
+        }
+    }
+
+    /// Write the plan, as the only creature, to the plan file.
+    fn save(&mut self) {
+        let plans = CreaturesPlan {
+            creatures: vec![self.plan.clone()],
+        };
+        self.status = Some(match plans.save(&self.path) {
+            Ok(()) => format!("Saved to {}", self.path),
+            Err(err) => err.to_string(),
+        });
+    }
+
+    /// Edit the plan file's first creature instead, at the origin.
+    fn load(&mut self) {
+        self.status = Some(match CreaturesPlan::load(&self.path) {
+            Ok(plans) => match plans.creatures.into_iter().next() {
+                Some(plan) => {
+                    self.plan = CreaturePlan {
+                        transform: Transform::default(),
+                        ..plan
+                    };
+                    self.selected_limb = (!self.plan.limbs.is_empty()).then_some(0);
+                    self.rebuild = true;
+                    format!("Loaded {}", self.path)
+                }
+                None => format!("{} has no creatures", self.path),
+            },
+            Err(err) => err.to_string(),
+        });
+    }
+}
+
+fn default_limb() -> LimbPlan {
+    LimbPlan {
+        oscillator: Oscillator::new(Wave::Sine, 0.2, 0.4),
+        segments: vec![LimbSegmentTypeId::Rectangle; 8],
+        angle: None,
+        sync: None,
+        audio: None,
+    }
+}
+
+/// Entering the editor: start from the selected creature (if any) and hide
+/// the rest of the world behind the preview.
+pub fn enter_editor(
+    mut editor: ResMut<Editor>,
+    selection: Res<Selection>,
+    plans: CreaturePlans,
+    mut creatures: Query<&mut Visibility, With<Creature>>,
+) {
+    if let Some(plan) = selection.creature.and_then(|c| plans.get(c)) {
+        editor.plan = plan;
+        editor.selected_limb = (!editor.plan.limbs.is_empty()).then_some(0);
+    }
+    editor.plan.transform = Transform::default();
+    editor.rebuild = true;
+    for mut visibility in &mut creatures {
+        *visibility = Visibility::Hidden;
+    }
+}
+
+/// Leaving the editor: remove the preview and show the world again.
+pub fn exit_editor(
+    mut commands: Commands,
+    mut editor: ResMut<Editor>,
+    mut creatures: Query<&mut Visibility, (With<Creature>, Without<EditorPreview>)>,
+) {
+    if let Some(preview) = editor.preview.take() {
+        commands.entity(preview).despawn();
+    }
+    for mut visibility in &mut creatures {
+        *visibility = Visibility::Inherited;
+    }
+}
+
+/// Respawn the preview creature whenever the plan's structure changes.
+pub fn rebuild_editor_preview(
+    mut commands: Commands,
+    mut editor: ResMut<Editor>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut store: ResMut<LimbAssetStore>,
+) {
+    if !editor.rebuild {
+        return;
+    }
+    editor.rebuild = false;
+    if let Some(preview) = editor.preview.take() {
+        commands.entity(preview).despawn();
+    }
+    let preview = spawn_creature(
+        &mut commands,
+        &mut meshes,
+        &mut materials,
+        &mut store,
+        &editor.plan,
+        Name::new("Editor preview"),
+    )
+    .creature;
+    commands.entity(preview).insert(EditorPreview);
+    editor.preview = Some(preview);
+}
+
+/// Drag outside the body to set the attachment angle of the nearest limb.
+pub fn drag_limb_angle(
+    mouse: Res<ButtonInput<MouseButton>>,
+    window: Single<&Window, With<PrimaryWindow>>,
+    camera: Single<(&Camera, &GlobalTransform)>,
+    mut editor: ResMut<Editor>,
+    previews: Query<(&GlobalTransform, &Children), With<EditorPreview>>,
+    mut limbs: Query<&mut Transform, With<Limb>>,
+) {
+    if mouse.just_released(MouseButton::Left) {
+        editor.dragging = false;
+    }
+    let Some((preview_transform, children)) = editor.preview.and_then(|p| previews.get(p).ok())
+    else {
+        return;
+    };
+    let (camera, camera_transform) = *camera;
+    let Some(cursor) = window
+        .cursor_position()
+        .and_then(|p| camera.viewport_to_world_2d(camera_transform, p).ok())
+    else {
+        return;
+    };
+    let offset = cursor - preview_transform.translation().truncate();
+    let cursor_angle = offset.to_angle();
+
+    if mouse.just_pressed(MouseButton::Left) && offset.length() > BODY_RADIUS {
+        // Grab the limb whose attachment angle is closest to the cursor.
+        editor.selected_limb = (0..editor.plan.limbs.len()).min_by(|a, b| {
+            let da = angle_between(editor.plan.limb_angle(*a), cursor_angle);
+            let db = angle_between(editor.plan.limb_angle(*b), cursor_angle);
+            da.total_cmp(&db)
+        });
+        editor.dragging = editor.selected_limb.is_some();
+    }
+
+    let Some(limb_index) = editor.selected_limb.filter(|_| editor.dragging) else {
+        return;
+    };
+    editor.plan.limbs[limb_index].angle = Some(cursor_angle.rem_euclid(std::f32::consts::TAU));
+
+    // Rotate the live limb in place so it keeps animating while dragged.
+    let limb = children
+        .iter()
+        .filter(|child| limbs.contains(*child))
+        .nth(limb_index);
+    if let Some(mut transform) = limb.and_then(|limb| limbs.get_mut(limb).ok()) {
+        transform.rotation = Quat::from_rotation_z(cursor_angle);
+    }
+}
+
+fn angle_between(a: f32, b: f32) -> f32 {
+    let d = (a - b).rem_euclid(std::f32::consts::TAU);
+    d.min(std::f32::consts::TAU - d)
+}
+
+/// The editor panel: limbs, segments, oscillator and file controls.
+pub fn editor_panel(
+    mut commands: Commands,
+    mut contexts: EguiContexts,
+    mut editor: ResMut<Editor>,
+    scheme: Res<ControlScheme>,
+    previews: Query<&Children, With<EditorPreview>>,
+    mut oscillators: Query<&mut Oscillator, With<Limb>>,
+) -> Result {
+    let ctx = contexts.ctx_mut()?;
+    let editor = &mut *editor;
+    let mut edited_oscillator = false;
+    let mut edited_audio = false;
+    let mut edited_sequencer = false;
+    let mut edited_voices = false;
+    let mut edited_effects = false;
+
+    egui::SidePanel::right("editor")
+        .resizable(true)
+        .show(ctx, |ui| {
+            ui.heading("Creature editor");
+            file_controls(ui, editor);
+            ui.separator();
+
+            egui::CollapsingHeader::new("Sequencer").show(ui, |ui| {
+                edited_sequencer = sequencer_controls(ui, &mut editor.plan.sequencer, &scheme);
+            });
+            egui::CollapsingHeader::new("Voices").show(ui, |ui| {
+                edited_voices = voice_controls(ui, &mut editor.plan.voices);
+            });
+            egui::CollapsingHeader::new("Effects").show(ui, |ui| {
+                let limb_count = editor.plan.limbs.len();
+                edited_effects = effects_controls(ui, &mut editor.plan.effects, limb_count);
+            });
+
+            ui.label("Limbs");
+            let limb_count = editor.plan.limbs.len();
+            for limb_index in 0..limb_count {
+                let degrees = editor.plan.limb_angle(limb_index).to_degrees();
+                let label = format!("Limb {limb_index} ({degrees:.0}°)");
+                if ui
+                    .selectable_label(editor.selected_limb == Some(limb_index), label)
+                    .clicked()
+                {
+                    editor.selected_limb = Some(limb_index);
+                }
+            }
+            ui.horizontal(|ui| {
+                if ui.button("Add limb").clicked() {
+                    let limb = editor
+                        .selected_limb
+                        .map_or_else(default_limb, |i| editor.plan.limbs[i].clone());
+                    editor.plan.limbs.push(LimbPlan {
+                        angle: None,
+                        ..limb
+                    });
+                    editor.selected_limb = Some(editor.plan.limbs.len() - 1);
+                    editor.rebuild = true;
+                }
+                if let Some(limb_index) = editor.selected_limb
+                    && ui.button("Remove limb").clicked()
+                {
+                    editor.plan.remove_limb(limb_index);
+                    editor.selected_limb =
+                        limb_index
+                            .checked_sub(1)
+                            .or((!editor.plan.limbs.is_empty()).then_some(0));
+                    editor.rebuild = true;
+                }
+                if ui.button("Even spacing").clicked() {
+                    for limb in &mut editor.plan.limbs {
+                        limb.angle = None;
+                    }
+                    editor.rebuild = true;
+                }
+            });
+
+            let Some(limb_index) = editor.selected_limb else {
+                return;
+            };
+            ui.separator();
+            ui.label(format!("Limb {limb_index}"));
+
+            let mut degrees = editor.plan.limb_angle(limb_index).to_degrees();
+            if ui
+                .add(egui::Slider::new(&mut degrees, 0.0..=360.0).text("angle (°)"))
+                .changed()
+            {
+                editor.plan.limbs[limb_index].angle = Some(degrees.to_radians());
+                editor.rebuild = true;
+            }
+
+            let limb_count = editor.plan.limbs.len();
+            if sync_controls(
+                ui,
+                &mut editor.plan.limbs[limb_index].sync,
+                limb_index,
+                limb_count,
+            ) {
+                editor.rebuild = true;
+            }
+
+            edited_oscillator =
+                oscillator_controls(ui, &mut editor.plan.limbs[limb_index].oscillator, &scheme);
+            edited_audio = audio_controls(ui, &mut editor.plan.limbs[limb_index].audio);
+
+            ui.separator();
+            if segment_controls(
+                ui,
+                &mut editor.plan.limbs[limb_index].segments,
+                &mut editor.new_segment_type,
+            ) {
+                editor.rebuild = true;
+            }
+        });
+
+    // Sequencer, voice, effect, oscillator and audio edits apply to the live preview
+    // without respawning it.
+    if edited_sequencer
+        && !editor.rebuild
+        && let Some(preview) = editor.preview
+    {
+        match &editor.plan.sequencer {
+            Some(sequencer) => commands.entity(preview).insert(sequencer.clone()),
+            None => commands.entity(preview).remove::<Sequencer>(),
+        };
+    }
+    if edited_voices
+        && !editor.rebuild
+        && let Some(preview) = editor.preview
+    {
+        match editor.plan.voices {
+            Some(voices) => commands.entity(preview).insert(voices),
+            None => commands.entity(preview).remove::<VoiceAllocator>(),
+        };
+    }
+    if edited_effects
+        && !editor.rebuild
+        && let Some(preview) = editor.preview
+    {
+        match editor.plan.effects {
+            Some(effects) => commands.entity(preview).insert(effects),
+            None => commands.entity(preview).remove::<Effects>(),
+        };
+    }
+    if (edited_oscillator || edited_audio) && !editor.rebuild {
+        let limb_index = editor.selected_limb.unwrap_or_default();
+        let limb = editor
+            .preview
+            .and_then(|p| previews.get(p).ok())
+            .and_then(|children| {
+                children
+                    .iter()
+                    .filter(|child| oscillators.contains(*child))
+                    .nth(limb_index)
+            });
+        if let Some(limb) = limb
+            && edited_audio
+        {
+            match editor.plan.limbs[limb_index].audio {
+                Some(audio) => commands.entity(limb).insert(audio),
+                None => commands.entity(limb).remove::<AudioReactive>(),
+            };
+        }
+        if let Some(mut live) = limb.and_then(|limb| oscillators.get_mut(limb).ok()) {
+            let edited = &editor.plan.limbs[limb_index].oscillator;
+            live.set_amplitude(edited.amplitude());
+            live.set_frequency(edited.target_frequency());
+            live.set_transition_time(edited.transition_time());
+            live.set_wave(edited.wave());
+            live.set_beat_division(edited.beat_division());
+            live.set_retrigger(edited.retrigger());
+        }
+    }
+
+    Ok(())
+}
+
+fn file_controls(ui: &mut egui::Ui, editor: &mut Editor) {
+    ui.horizontal(|ui| {
+        ui.label("Plan file");
+        ui.text_edit_singleline(&mut editor.path);
+    });
+    ui.horizontal(|ui| {
+        if ui.button("Save").clicked() {
+            editor.save();
+        }
+        if ui.button("Load").clicked() {
+            editor.load();
+        }
+    });
+    if let Some(status) = &editor.status {
+        ui.label(status);
+    }
+}
+
+/// A change to a limb's segment list.
+#[derive(Debug, Clone, Copy)]
+enum SegmentEdit {
+    Insert(usize),
+    Delete(usize),
+    Swap(usize, usize),
+}
+
+impl SegmentEdit {
+    fn apply(self, segments: &mut Vec<LimbSegmentTypeId>, new_segment_type: LimbSegmentTypeId) {
+        match self {
+            SegmentEdit::Insert(index) => segments.insert(index, new_segment_type),
+            SegmentEdit::Delete(index) => {
+                segments.remove(index);
+            }
+            SegmentEdit::Swap(a, b) => segments.swap(a, b),
+        }
+    }
+}
+
+/// Segment list with insert, delete and reorder. Returns whether anything changed.
+fn segment_controls(
+    ui: &mut egui::Ui,
+    segments: &mut Vec<LimbSegmentTypeId>,
+    new_segment_type: &mut LimbSegmentTypeId,
+) -> bool {
+    let mut action = None;
+
+    ui.horizontal(|ui| {
+        ui.label("Segments");
+        egui::ComboBox::from_id_salt("new segment type")
+            .selected_text(format!("{new_segment_type:?}"))
+            .show_ui(ui, |ui| {
+                for option in LimbSegmentTypeId::ALL {
+                    ui.selectable_value(new_segment_type, option, format!("{option:?}"));
+                }
+            });
+        if ui.button("Insert at start").clicked() {
+            action = Some(SegmentEdit::Insert(0));
+        }
+    });
+
+    let len = segments.len();
+    for (segment_index, type_id) in segments.iter().enumerate() {
+        ui.horizontal(|ui| {
+            ui.label(format!("{segment_index}: {type_id:?}"));
+            if ui.small_button("+").on_hover_text("Insert after").clicked() {
+                action = Some(SegmentEdit::Insert(segment_index + 1));
+            }
+            if ui.small_button("✖").on_hover_text("Delete").clicked() {
+                action = Some(SegmentEdit::Delete(segment_index));
+            }
+            if segment_index > 0 && ui.small_button("⏶").on_hover_text("Move up").clicked() {
+                action = Some(SegmentEdit::Swap(segment_index, segment_index - 1));
+            }
+            if segment_index + 1 < len && ui.small_button("⏷").on_hover_text("Move down").clicked()
+            {
+                action = Some(SegmentEdit::Swap(segment_index, segment_index + 1));
+            }
+        });
+    }
+
+    let Some(action) = action else {
+        return false;
+    };
+    action.apply(segments, *new_segment_type);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::creature::{example_creatures_plan, CreatureCommands};
+    use crate::ecosystem::{add_energy, Ecosystem, Energy};
+    use crate::effects::Delay;
+    use crate::limb::LimbSegmentTypeId::{Disk, Rectangle};
+    use crate::locomotion::{add_swimmers, Swimmer};
+    use crate::steering::{add_flocking, Steering};
+    use crate::testing::Harness;
+    use bevy::ecs::system::RunSystemOnce;
+
+    #[test]
+    fn edited_plans_round_trip_through_their_file() {
+        let path = std::env::temp_dir().join(format!(
+            "creature-synth-editor-{}.ron",
+            std::process::id()
+        ));
+        let mut editor = Editor::new(path.display().to_string());
+        editor.plan.remove_limb(3);
+        editor.plan.limbs[1].angle = Some(1.0);
+        editor.plan.limbs[2].segments = vec![Disk, Rectangle, Disk];
+        editor.plan.effects = Some(Effects {
+            delay: Some(Delay::default()),
+            ..default()
+        });
+        editor.save();
+
+        let mut loaded = Editor::new(editor.path.clone());
+        loaded.selected_limb = None;
+        loaded.load();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.status, Some(format!("Loaded {}", editor.path)));
+        assert_eq!(loaded.selected_limb, Some(0));
+        let ron = |plan: &CreaturePlan| ron::to_string(plan).unwrap();
+        assert_eq!(ron(&loaded.plan), ron(&editor.plan));
+    }
+
+    #[test]
+    fn segments_insert_delete_and_reorder() {
+        let mut segments = vec![Rectangle, Rectangle];
+        SegmentEdit::Insert(1).apply(&mut segments, Disk);
+        assert_eq!(segments, [Rectangle, Disk, Rectangle]);
+        SegmentEdit::Insert(0).apply(&mut segments, Disk);
+        assert_eq!(segments, [Disk, Rectangle, Disk, Rectangle]);
+        SegmentEdit::Swap(1, 2).apply(&mut segments, Disk);
+        assert_eq!(segments, [Disk, Disk, Rectangle, Rectangle]);
+        SegmentEdit::Delete(0).apply(&mut segments, Disk);
+        assert_eq!(segments, [Disk, Rectangle, Rectangle]);
+    }
+
+    #[test]
+    fn the_preview_stays_out_of_the_world() {
+        let mut harness = Harness::new(CreaturesPlan::default());
+        harness
+            .app
+            .insert_resource(Editor::new("unused.ron"))
+            .insert_resource(Ecosystem::seeded(7))
+            .add_systems(
+                Update,
+                (
+                    rebuild_editor_preview,
+                    (add_energy, add_flocking, add_swimmers),
+                )
+                    .chain(),
+            );
+        let plan = example_creatures_plan().creatures.remove(0);
+        let creature = harness
+            .app
+            .world_mut()
+            .run_system_once(move |mut commands: Commands| {
+                commands.spawn_creature(plan.clone()).creature
+            })
+            .unwrap();
+        harness.advance(0.1);
+
+        let preview = harness.world().resource::<Editor>().preview.unwrap();
+        let world = harness.world();
+        for (entity, joins) in [(creature, true), (preview, false)] {
+            assert_eq!(world.get::<Energy>(entity).is_some(), joins);
+            assert_eq!(world.get::<Steering>(entity).is_some(), joins);
+            assert_eq!(world.get::<Swimmer>(entity).is_some(), joins);
+        }
+        let listed = harness
+            .app
+            .world_mut()
+            .run_system_once(|plans: CreaturePlans| plans.entities())
+            .unwrap();
+        assert_eq!(listed, [creature]);
+    }
+}
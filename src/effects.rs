@@ -0,0 +1,387 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+/// A creature's sound processing: filter, then delay, then reverb, then gain.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Effects {
+    #[serde(default)]
+    pub filter: Option<Filter>,
+    #[serde(default)]
+    pub delay: Option<Delay>,
+    #[serde(default)]
+    pub reverb: Option<Reverb>,
+    #[serde(default = "unity_gain")]
+    pub gain: f32,
+}
+
+fn unity_gain() -> f32 {
+    1.0
+}
+
+impl Default for Effects {
+    fn default() -> Self {
+        Self {
+            filter: None,
+            delay: None,
+            reverb: None,
+            gain: unity_gain(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FilterMode {
+    #[default]
+    Lowpass,
+    Bandpass,
+    Highpass,
+}
+
+impl FilterMode {
+    pub const ALL: [FilterMode; 3] = [
+        FilterMode::Lowpass,
+        FilterMode::Bandpass,
+        FilterMode::Highpass,
+    ];
+}
+
+/// State-variable filter.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Filter {
+    #[serde(default)]
+    pub mode: FilterMode,
+    /// Cutoff frequency (Hz).
+    pub cutoff: f32,
+    /// 0..1; near 1 rings.
+    #[serde(default)]
+    pub resonance: f32,
+    /// Sweep the cutoff with one of the creature's limbs.
+    #[serde(default)]
+    pub modulation: Option<CutoffModulation>,
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Self {
+            mode: FilterMode::Lowpass,
+            cutoff: 1200.0,
+            resonance: 0.3,
+            modulation: None,
+        }
+    }
+}
+
+/// Cutoff modulation by a limb's oscillator.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CutoffModulation {
+    /// Index of the limb whose oscillator moves the cutoff.
+    pub limb: usize,
+    /// Octaves the cutoff moves at full oscillator output.
+    pub depth: f32,
+}
+
+impl Default for CutoffModulation {
+    fn default() -> Self {
+        Self {
+            limb: 0,
+            depth: 2.0,
+        }
+    }
+}
+
+/// Feedback delay.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Delay {
+    /// Seconds, up to MAX_DELAY_SECS.
+    pub time: f32,
+    /// 0..1; how much of each echo feeds the next.
+    pub feedback: f32,
+    /// 0..1 wet level.
+    pub mix: f32,
+}
+
+pub const MAX_DELAY_SECS: f32 = 2.0;
+
+impl Default for Delay {
+    fn default() -> Self {
+        Self {
+            time: 0.3,
+            feedback: 0.4,
+            mix: 0.3,
+        }
+    }
+}
+
+/// Simple room reverb.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Reverb {
+    /// 0..1; larger rooms ring longer.
+    pub size: f32,
+    /// 0..1; more damping darkens the tail.
+    pub damping: f32,
+    /// 0..1 wet level.
+    pub mix: f32,
+}
+
+impl Default for Reverb {
+    fn default() -> Self {
+        Self {
+            size: 0.6,
+            damping: 0.4,
+            mix: 0.25,
+        }
+    }
+}
+
+/// Runtime state of a creature's effects on the audio thread. The delay and
+/// reverb buffers are made the first time those effects are used.
+#[derive(Debug, Clone)]
+pub struct EffectChain {
+    sample_rate: f32,
+    filter: SvfState,
+    delay: Option<DelayLine>,
+    reverb: Option<ReverbState>,
+}
+
+impl EffectChain {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate: sample_rate as f32,
+            filter: SvfState::default(),
+            delay: None,
+            reverb: None,
+        }
+    }
+
+    /// Process one sample. `cutoff_offset` is the modulating oscillator's
+    /// current output (-1..1), scaled by the filter's modulation depth.
+    pub fn process(&mut self, effects: &Effects, cutoff_offset: f32, input: f32) -> f32 {
+        let mut x = input;
+        if let Some(filter) = &effects.filter {
+            let depth = filter.modulation.map_or(0.0, |m| m.depth);
+            let cutoff = filter.cutoff * 2f32.powf(depth * cutoff_offset);
+            x = self.filter.process(filter, cutoff, self.sample_rate, x);
+        }
+        if let Some(delay) = &effects.delay {
+            let delay_samples = (delay.time.clamp(0.0, MAX_DELAY_SECS) * self.sample_rate) as usize;
+            let line = self.delay.get_or_insert_with(|| {
+                DelayLine::new((MAX_DELAY_SECS * self.sample_rate) as usize + 1)
+            });
+            let echo = line.read(delay_samples);
+            line.write(x + echo * delay.feedback.clamp(0.0, 0.95));
+            x += echo * delay.mix;
+        }
+        if let Some(reverb) = &effects.reverb {
+            let state = self.reverb.get_or_insert_with(|| ReverbState::new(self.sample_rate));
+            x += state.process(reverb, x) * reverb.mix;
+        }
+        x * effects.gain
+    }
+}
+
+/// Trapezoidal state-variable filter (stable under fast cutoff changes).
+#[derive(Debug, Clone, Copy, Default)]
+struct SvfState {
+    ic1: f32,
+    ic2: f32,
+}
+
+impl SvfState {
+    fn process(&mut self, filter: &Filter, cutoff: f32, sample_rate: f32, input: f32) -> f32 {
+        let cutoff = cutoff.clamp(20.0, 0.45 * sample_rate);
+        let g = (PI * cutoff / sample_rate).tan();
+        let k = 2.0 - 1.98 * filter.resonance.clamp(0.0, 1.0);
+        let a1 = 1.0 / (1.0 + g * (g + k));
+        let a2 = g * a1;
+        let a3 = g * a2;
+        let v3 = input - self.ic2;
+        let v1 = a1 * self.ic1 + a2 * v3;
+        let v2 = self.ic2 + a2 * self.ic1 + a3 * v3;
+        self.ic1 = 2.0 * v1 - self.ic1;
+        self.ic2 = 2.0 * v2 - self.ic2;
+        match filter.mode {
+            FilterMode::Lowpass => v2,
+            FilterMode::Bandpass => v1,
+            FilterMode::Highpass => input - k * v1 - v2,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct DelayLine {
+    buffer: Vec<f32>,
+    write: usize,
+}
+
+impl DelayLine {
+    fn new(len: usize) -> Self {
+        Self {
+            buffer: vec![0.0; len.max(1)],
+            write: 0,
+        }
+    }
+
+    /// The sample written `delay` samples ago.
+    fn read(&self, delay: usize) -> f32 {
+        let len = self.buffer.len();
+        let delay = delay.clamp(1, len);
+        self.buffer[(self.write + len - delay) % len]
+    }
+
+    fn write(&mut self, sample: f32) {
+        self.buffer[self.write] = sample;
+        self.write = (self.write + 1) % self.buffer.len();
+    }
+}
+
+/// Comb filter with a damped feedback path.
+#[derive(Debug, Clone)]
+struct Comb {
+    line: DelayLine,
+    delay: usize,
+    damped: f32,
+}
+
+/// Schroeder reverb: parallel combs into series allpasses. Delay lengths
+/// are the Freeverb ones, scaled to the sample rate.
+#[derive(Debug, Clone)]
+struct ReverbState {
+    combs: Vec<Comb>,
+    allpasses: Vec<(DelayLine, usize)>,
+}
+
+const COMB_TUNINGS: [usize; 4] = [1116, 1188, 1277, 1356];
+const ALLPASS_TUNINGS: [usize; 2] = [556, 441];
+
+impl ReverbState {
+    fn new(sample_rate: f32) -> Self {
+        let scale = |samples: usize| (samples as f32 * sample_rate / 44_100.0) as usize;
+        Self {
+            combs: COMB_TUNINGS
+                .iter()
+                .map(|&tuning| Comb {
+                    line: DelayLine::new(scale(tuning)),
+                    delay: scale(tuning),
+                    damped: 0.0,
+                })
+                .collect(),
+            allpasses: ALLPASS_TUNINGS
+                .iter()
+                .map(|&tuning| (DelayLine::new(scale(tuning)), scale(tuning)))
+                .collect(),
+        }
+    }
+
+    fn process(&mut self, reverb: &Reverb, input: f32) -> f32 {
+        let feedback = 0.7 + 0.28 * reverb.size.clamp(0.0, 1.0);
+        let damping = 0.4 * reverb.damping.clamp(0.0, 1.0);
+        let mut out = 0.0;
+        for comb in &mut self.combs {
+            let delayed = comb.line.read(comb.delay);
+            comb.damped = delayed * (1.0 - damping) + comb.damped * damping;
+            comb.line.write(input + comb.damped * feedback);
+            out += delayed;
+        }
+        out /= self.combs.len() as f32;
+        for (line, delay) in &mut self.allpasses {
+            let delayed = line.read(*delay);
+            line.write(out + delayed * 0.5);
+            out = delayed - out;
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::TAU;
+
+    const SAMPLE_RATE: u32 = 44_100;
+
+    fn run(effects: &Effects, input: impl IntoIterator<Item = f32>) -> Vec<f32> {
+        let mut chain = EffectChain::new(SAMPLE_RATE);
+        input
+            .into_iter()
+            .map(|x| chain.process(effects, 0.0, x))
+            .collect()
+    }
+
+    /// Root-mean-square level of a tone through the effects, once settled.
+    fn tone_level(effects: &Effects, hz: f32) -> f32 {
+        let tone = (0..SAMPLE_RATE / 2).map(|i| {
+            let t = i as f32 / SAMPLE_RATE as f32;
+            (TAU * hz * t).sin()
+        });
+        let out = run(effects, tone);
+        let settled = &out[out.len() / 2..];
+        (settled.iter().map(|x| x * x).sum::<f32>() / settled.len() as f32).sqrt()
+    }
+
+    fn impulse(len: usize) -> impl Iterator<Item = f32> {
+        (0..len).map(|i| if i == 0 { 1.0 } else { 0.0 })
+    }
+
+    #[test]
+    fn lowpass_attenuates_above_its_cutoff() {
+        let effects = Effects {
+            filter: Some(Filter {
+                cutoff: 500.0,
+                resonance: 0.0,
+                ..default()
+            }),
+            ..default()
+        };
+        let unfiltered = tone_level(&Effects::default(), 5000.0);
+        // A 12 dB/octave slope, over three octaves and a bit.
+        assert!(tone_level(&effects, 5000.0) < unfiltered * 0.02);
+        assert!(tone_level(&effects, 100.0) > unfiltered * 0.9);
+    }
+
+    #[test]
+    fn delay_echoes_with_feedback() {
+        let delay = Delay {
+            time: 0.25,
+            feedback: 0.5,
+            mix: 1.0,
+        };
+        let effects = Effects {
+            delay: Some(delay),
+            ..default()
+        };
+        let at = (delay.time * SAMPLE_RATE as f32) as usize;
+        let out = run(&effects, impulse(at * 3 + 1));
+        assert_eq!(out[0], 1.0);
+        assert_eq!(out[at], 1.0);
+        assert_eq!(out[2 * at], 0.5);
+        assert_eq!(out[3 * at], 0.25);
+        let echoes = out.iter().filter(|&&x| x != 0.0).count();
+        assert_eq!(echoes, 4);
+    }
+
+    #[test]
+    fn delay_and_reverb_buffers_wait_until_used() {
+        let mut chain = EffectChain::new(SAMPLE_RATE);
+        chain.process(&Effects::default(), 0.0, 1.0);
+        assert!(chain.delay.is_none() && chain.reverb.is_none());
+        let effects = Effects {
+            delay: Some(Delay::default()),
+            ..default()
+        };
+        chain.process(&effects, 0.0, 1.0);
+        assert!(chain.delay.is_some() && chain.reverb.is_none());
+    }
+
+    #[test]
+    fn zero_gain_is_silent() {
+        let effects = Effects {
+            delay: Some(Delay::default()),
+            reverb: Some(Reverb::default()),
+            gain: 0.0,
+            ..default()
+        };
+        let out = run(&effects, impulse(SAMPLE_RATE as usize));
+        assert!(out.iter().all(|&x| x == 0.0));
+    }
+}
@@ -0,0 +1,440 @@
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use rand::rngs::StdRng;
+use rand::seq::IndexedRandom;
+use rand::{Rng, SeedableRng};
+use std::ops::RangeInclusive;
+use std::time::Duration;
+
+use crate::creature::{spawn_creatures, Creature, CreatureNames, CreaturePlan, CreaturesPlan};
+use crate::limb::{animate_limb_segments, LimbAssetStore, LimbPlan, LimbSegmentTypeId};
+use crate::locomotion::{add_swimmers, swim};
+use crate::oscillator::{oscillator_tick, Oscillator, Wave};
+
+const LIMBS: RangeInclusive<usize> = 1..=12;
+const SEGMENTS: RangeInclusive<usize> = 2..=24;
+const AMPLITUDE: RangeInclusive<f32> = 0.05..=1.0;
+const FREQUENCY: RangeInclusive<f32> = 0.1..=3.0;
+/// Radius of the circle a population is laid out around.
+const LAYOUT_RADIUS: f32 = 450.0;
+
+/// The heritable part of a limb: its oscillator and its segments.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LimbGene {
+    pub wave: Wave,
+    pub amplitude: f32,
+    pub frequency: f32,
+    /// Starting phase (cycles, 0..1), which sets the limb's timing against
+    /// the others.
+    pub phase: f32,
+    pub segments: Vec<LimbSegmentTypeId>,
+}
+
+/// The heritable part of a creature: its limbs, spread evenly around the
+/// body. Sequencers, voices and effects aren't inherited.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Genome {
+    pub limbs: Vec<LimbGene>,
+}
+
+impl LimbGene {
+    fn random(rng: &mut impl Rng) -> Self {
+        let count = rng.random_range(SEGMENTS);
+        Self {
+            wave: *Wave::ALL.choose(rng).unwrap(),
+            amplitude: rng.random_range(AMPLITUDE),
+            frequency: rng.random_range(FREQUENCY),
+            phase: rng.random(),
+            segments: (0..count).map(|_| random_segment(rng)).collect(),
+        }
+    }
+
+    fn mutate(&mut self, rate: f32, rng: &mut impl Rng) {
+        if rng.random::<f32>() < rate {
+            self.wave = *Wave::ALL.choose(rng).unwrap();
+        }
+        if rng.random::<f32>() < rate {
+            self.amplitude = nudge(self.amplitude, AMPLITUDE, rng);
+        }
+        if rng.random::<f32>() < rate {
+            self.frequency = nudge(self.frequency, FREQUENCY, rng);
+        }
+        if rng.random::<f32>() < rate {
+            self.phase = (self.phase + rng.random_range(-0.25..=0.25)).rem_euclid(1.0);
+        }
+        for segment in &mut self.segments {
+            if rng.random::<f32>() < rate / 4.0 {
+                *segment = random_segment(rng);
+            }
+        }
+        if rng.random::<f32>() < rate && self.segments.len() < *SEGMENTS.end() {
+            self.segments.push(random_segment(rng));
+        }
+        if rng.random::<f32>() < rate && self.segments.len() > *SEGMENTS.start() {
+            self.segments.pop();
+        }
+    }
+
+    /// Each trait from one parent or the other, and segments from the base
+    /// of one limb and the tip of the other.
+    fn crossover(&self, other: &LimbGene, rng: &mut impl Rng) -> Self {
+        let cut = rng.random_range(0..=self.segments.len().min(other.segments.len()));
+        Self {
+            wave: pick(self.wave, other.wave, rng),
+            amplitude: pick(self.amplitude, other.amplitude, rng),
+            frequency: pick(self.frequency, other.frequency, rng),
+            phase: pick(self.phase, other.phase, rng),
+            segments: self.segments[..cut]
+                .iter()
+                .chain(&other.segments[cut..])
+                .copied()
+                .collect(),
+        }
+    }
+}
+
+impl Genome {
+    pub fn from_plan(plan: &CreaturePlan) -> Self {
+        Self {
+            limbs: plan
+                .limbs
+                .iter()
+                .map(|limb| LimbGene {
+                    wave: limb.oscillator.wave(),
+                    amplitude: limb.oscillator.amplitude(),
+                    frequency: limb.oscillator.target_frequency(),
+                    phase: limb.oscillator.phase(),
+                    segments: limb.segments.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn to_plan(&self, transform: Transform) -> CreaturePlan {
+        CreaturePlan {
+            limbs: self
+                .limbs
+                .iter()
+                .map(|gene| {
+                    let mut oscillator = Oscillator::new(gene.wave, gene.amplitude, gene.frequency);
+                    oscillator.set_phase(gene.phase);
+                    LimbPlan {
+                        oscillator,
+                        segments: gene.segments.clone(),
+                        angle: None,
+                        sync: None,
+                        audio: None,
+                    }
+                })
+                .collect(),
+            transform,
+            sequencer: None,
+            voices: None,
+            effects: None,
+        }
+    }
+
+    pub fn random(rng: &mut impl Rng) -> Self {
+        let count = rng.random_range(LIMBS);
+        Self {
+            limbs: (0..count).map(|_| LimbGene::random(rng)).collect(),
+        }
+    }
+
+    /// Change each trait with probability `rate`, and now and then gain a
+    /// copy of a limb or lose one.
+    pub fn mutate(&mut self, rate: f32, rng: &mut impl Rng) {
+        for limb in &mut self.limbs {
+            limb.mutate(rate, rng);
+        }
+        if rng.random::<f32>() < rate / 2.0
+            && self.limbs.len() < *LIMBS.end()
+            && let Some(limb) = self.limbs.choose(rng).cloned()
+        {
+            self.limbs.push(limb);
+        }
+        if rng.random::<f32>() < rate / 2.0 && self.limbs.len() > *LIMBS.start() {
+            let index = rng.random_range(0..self.limbs.len());
+            self.limbs.remove(index);
+        }
+    }
+
+    /// A child with either parent's number of limbs, each limb mixed from
+    /// the parents' limbs in the same place.
+    pub fn crossover(&self, other: &Genome, rng: &mut impl Rng) -> Genome {
+        let count = pick(self.limbs.len(), other.limbs.len(), rng);
+        let limbs = (0..count)
+            .filter_map(|i| match (self.limbs.get(i), other.limbs.get(i)) {
+                (Some(a), Some(b)) if rng.random_bool(0.5) => Some(a.crossover(b, rng)),
+                (Some(a), Some(b)) => Some(b.crossover(a, rng)),
+                (a, b) => a.or(b).cloned(),
+            })
+            .collect();
+        Genome { limbs }
+    }
+}
+
+fn random_segment(rng: &mut impl Rng) -> LimbSegmentTypeId {
+    *LimbSegmentTypeId::ALL.choose(rng).unwrap()
+}
+
+/// Scale by up to half again either way, within range.
+fn nudge(value: f32, range: RangeInclusive<f32>, rng: &mut impl Rng) -> f32 {
+    (value * rng.random_range(0.67..=1.5)).clamp(*range.start(), *range.end())
+}
+
+fn pick<T>(a: T, b: T, rng: &mut impl Rng) -> T {
+    if rng.random_bool(0.5) {
+        a
+    } else {
+        b
+    }
+}
+
+/// How a population is bred and tried out.
+#[derive(Debug, Clone)]
+pub struct EvolutionSettings {
+    pub population: usize,
+    /// The fittest this many carry over to the next generation unchanged.
+    pub elite: usize,
+    /// Parents are the fittest of this many picked at random.
+    pub tournament: usize,
+    /// Chance of each trait mutating in a child.
+    pub mutation_rate: f32,
+    /// Simulated seconds each generation swims for.
+    pub trial_secs: f32,
+    /// Simulated seconds per update in a trial.
+    pub step_secs: f32,
+    /// Seed for reproducible runs; random when unset.
+    pub seed: Option<u64>,
+}
+
+impl Default for EvolutionSettings {
+    fn default() -> Self {
+        Self {
+            population: 24,
+            elite: 2,
+            tournament: 3,
+            mutation_rate: 0.2,
+            trial_secs: 20.0,
+            step_secs: 1.0 / 30.0,
+            seed: None,
+        }
+    }
+}
+
+/// A population of genomes, bred a generation at a time from their scores.
+pub struct Evolution {
+    settings: EvolutionSettings,
+    rng: StdRng,
+    population: Vec<Genome>,
+    generation: usize,
+}
+
+impl Evolution {
+    /// A first generation of the seeds and mutants of them, or of random
+    /// genomes without seeds.
+    pub fn new(settings: EvolutionSettings, seeds: &[Genome]) -> Self {
+        let mut rng = match settings.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_os_rng(),
+        };
+        let size = settings.population.max(1);
+        let population = (0..size)
+            .map(|i| match seeds.get(i % seeds.len().max(1)) {
+                Some(seed) if i < seeds.len() => seed.clone(),
+                Some(seed) => {
+                    let mut mutant = seed.clone();
+                    mutant.mutate(settings.mutation_rate, &mut rng);
+                    mutant
+                }
+                None => Genome::random(&mut rng),
+            })
+            .collect();
+        Self {
+            settings,
+            rng,
+            population,
+            generation: 0,
+        }
+    }
+
+    pub fn settings(&self) -> &EvolutionSettings {
+        &self.settings
+    }
+
+    pub fn population(&self) -> &[Genome] {
+        &self.population
+    }
+
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    /// Replace the population with the next generation, given a score for
+    /// each genome (higher is fitter): distance swum, votes, or anything
+    /// else. The elite come first, fittest first.
+    pub fn breed(&mut self, scores: &[f32]) {
+        let mut ranked: Vec<usize> = (0..self.population.len()).collect();
+        let score = |i: usize| scores.get(i).copied().unwrap_or(f32::NEG_INFINITY);
+        ranked.sort_by(|&a, &b| score(b).total_cmp(&score(a)));
+
+        let size = self.population.len();
+        let mut next: Vec<Genome> = ranked
+            .iter()
+            .take(self.settings.elite.min(size))
+            .map(|&i| self.population[i].clone())
+            .collect();
+        while next.len() < size {
+            let a = self.tournament(score);
+            let b = self.tournament(score);
+            let mut child = self.population[a].crossover(&self.population[b], &mut self.rng);
+            child.mutate(self.settings.mutation_rate, &mut self.rng);
+            next.push(child);
+        }
+        self.population = next;
+        self.generation += 1;
+    }
+
+    fn tournament(&mut self, score: impl Fn(usize) -> f32) -> usize {
+        (0..self.settings.tournament.max(1))
+            .map(|_| self.rng.random_range(0..self.population.len()))
+            .max_by(|&a, &b| score(a).total_cmp(&score(b)))
+            .unwrap()
+    }
+
+    /// The population as creatures spread around a circle.
+    pub fn plan(&self) -> CreaturesPlan {
+        let count = self.population.len();
+        let creatures = self
+            .population
+            .iter()
+            .enumerate()
+            .map(|(i, genome)| {
+                let angle = std::f32::consts::TAU * i as f32 / count as f32;
+                let position = LAYOUT_RADIUS * Vec2::from_angle(angle);
+                genome.to_plan(Transform::from_translation(position.extend(0.0)))
+            })
+            .collect();
+        CreaturesPlan { creatures }
+    }
+}
+
+/// Swim each genome alone for `trial_secs` of simulated time, as fast as
+/// it'll run, and score it by how far it got from where it started.
+pub fn swim_trial(genomes: &[Genome], settings: &EvolutionSettings) -> Vec<f32> {
+    // Creatures don't meet, so they can all start from the same spot.
+    let plan = CreaturesPlan {
+        creatures: genomes
+            .iter()
+            .map(|genome| genome.to_plan(Transform::default()))
+            .collect(),
+    };
+    let step = Duration::from_secs_f32(settings.step_secs.max(1e-3));
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+        .init_asset::<Mesh>()
+        .init_asset::<ColorMaterial>()
+        .insert_resource(LimbAssetStore::default())
+        .init_resource::<CreatureNames>()
+        .insert_resource(plan)
+        .insert_resource(TimeUpdateStrategy::ManualDuration(step))
+        .add_systems(Startup, spawn_creatures)
+        .add_systems(
+            Update,
+            (add_swimmers, oscillator_tick, animate_limb_segments, swim).chain(),
+        );
+    let steps = (settings.trial_secs / step.as_secs_f32()).ceil() as usize;
+    for _ in 0..=steps {
+        app.update();
+    }
+
+    let world = app.world_mut();
+    let mut creatures: Vec<(Entity, Vec3)> = world
+        .query_filtered::<(Entity, &Transform), With<Creature>>()
+        .iter(world)
+        .map(|(entity, transform)| (entity, transform.translation))
+        .collect();
+    creatures.sort_by_key(|(entity, _)| entity.index());
+    creatures
+        .into_iter()
+        .map(|(_, position)| position.truncate().length())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limb(wave: Wave, phase: f32, segments: usize) -> LimbGene {
+        LimbGene {
+            wave,
+            amplitude: 0.5,
+            frequency: 1.0,
+            phase,
+            segments: vec![LimbSegmentTypeId::Rectangle; segments],
+        }
+    }
+
+    #[test]
+    fn mutants_stay_in_range() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut genome = Genome::random(&mut rng);
+        for _ in 0..500 {
+            genome.mutate(0.5, &mut rng);
+            assert!(LIMBS.contains(&genome.limbs.len()));
+            for limb in &genome.limbs {
+                assert!(SEGMENTS.contains(&limb.segments.len()));
+                assert!(AMPLITUDE.contains(&limb.amplitude));
+                assert!(FREQUENCY.contains(&limb.frequency));
+                assert!((0.0..1.0).contains(&limb.phase));
+            }
+        }
+    }
+
+    #[test]
+    fn children_take_after_their_parents() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let a = Genome {
+            limbs: vec![limb(Wave::Sine, 0.0, 4); 2],
+        };
+        let b = Genome {
+            limbs: vec![limb(Wave::Square, 0.5, 6); 3],
+        };
+        for _ in 0..50 {
+            let child = a.crossover(&b, &mut rng);
+            assert!([2, 3].contains(&child.limbs.len()));
+            for limb in &child.limbs {
+                assert!([Wave::Sine, Wave::Square].contains(&limb.wave));
+                assert!([0.0, 0.5].contains(&limb.phase));
+                assert!((4..=6).contains(&limb.segments.len()));
+            }
+        }
+    }
+
+    #[test]
+    fn the_fittest_carry_over() {
+        let settings = EvolutionSettings {
+            population: 6,
+            elite: 2,
+            seed: Some(7),
+            ..default()
+        };
+        let mut evolution = Evolution::new(settings, &[]);
+        let before = evolution.population().to_vec();
+        evolution.breed(&[1.0, 5.0, 0.0, 3.0, 2.0, 4.0]);
+
+        assert_eq!(evolution.generation(), 1);
+        assert_eq!(evolution.population().len(), 6);
+        assert_eq!(evolution.population()[0], before[1]);
+        assert_eq!(evolution.population()[1], before[5]);
+    }
+
+    #[test]
+    fn genomes_round_trip_through_plans() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let genome = Genome::random(&mut rng);
+        let plan = genome.to_plan(Transform::default());
+        assert_eq!(Genome::from_plan(&plan), genome);
+    }
+}
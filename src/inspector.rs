@@ -0,0 +1,670 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use std::time::Duration;
+
+use crate::audio_analysis::{AudioFeature, AudioReactive, AudioTarget};
+use crate::controls::{ControlScheme, ParamControl, StepScale};
+use crate::creature::{CreaturePlan, CreaturePlans, CreaturesPlan, RespawnCreature};
+use crate::effects::{Effects, FilterMode, MAX_DELAY_SECS};
+use crate::limb::{Limb, LimbSegmentTypeId};
+use crate::oscillator::{Oscillator, Wave};
+use crate::selection::Selection;
+use crate::sequencer::{Sequencer, Step};
+use crate::transport::{BeatDivision, NoteModifier, NoteValue};
+use crate::voices::{VoiceAllocator, VoiceMode};
+
+/// State of the egui parameter inspector panel.
+#[derive(Resource, Debug, Clone)]
+pub struct Inspector {
+    pub open: bool,
+    /// Where "Export" writes the live creatures as a plan file.
+    pub export_path: String,
+    status: Option<String>,
+}
+
+impl Inspector {
+    pub fn new(export_path: impl Into<String>) -> Self {
+        Self {
+            open: true,
+            export_path: export_path.into(),
+            status: None,
+        }
+    }
+}
+
+/// F1 shows or hides the inspector.
+pub fn toggle_inspector(keys: Res<ButtonInput<KeyCode>>, mut inspector: ResMut<Inspector>) {
+    if keys.just_pressed(KeyCode::F1) {
+        inspector.open = !inspector.open;
+    }
+}
+
+/// A creature as shown in the inspector: a snapshot of its plan that the UI
+/// edits, plus the live limb entities the edits are applied back to.
+struct CreatureView {
+    entity: Entity,
+    name: String,
+    plan: CreaturePlan,
+    limbs: Vec<Entity>,
+    edited_oscillators: Vec<usize>,
+    edited_audio: Vec<usize>,
+    edited_sequencer: bool,
+    edited_voices: bool,
+    edited_effects: bool,
+    edited_segments: bool,
+    respawn: bool,
+}
+
+/// Side panel listing every creature, limb and oscillator. Oscillator and
+/// audio edits apply live; segment edits and "Respawn" rebuild the creature.
+pub fn inspector_panel(
+    mut commands: Commands,
+    mut contexts: EguiContexts,
+    mut inspector: ResMut<Inspector>,
+    scheme: Res<ControlScheme>,
+    mut selection: ResMut<Selection>,
+    mut respawn: MessageWriter<RespawnCreature>,
+    mut world: ParamSet<(CreaturePlans, Query<&mut Oscillator, With<Limb>>)>,
+) -> Result {
+    if !inspector.open {
+        return Ok(());
+    }
+
+    let mut views: Vec<CreatureView> = {
+        let plans = world.p0();
+        plans
+            .entities()
+            .into_iter()
+            .filter_map(|entity| {
+                Some(CreatureView {
+                    entity,
+                    name: plans
+                        .name(entity)
+                        .map_or_else(|| format!("{entity}"), |n| n.to_string()),
+                    plan: plans.get(entity)?,
+                    limbs: plans.limbs(entity),
+                    edited_oscillators: Vec::new(),
+                    edited_audio: Vec::new(),
+                    edited_sequencer: false,
+                    edited_voices: false,
+                    edited_effects: false,
+                    edited_segments: false,
+                    respawn: false,
+                })
+            })
+            .collect()
+    };
+
+    let ctx = contexts.ctx_mut()?;
+    egui::SidePanel::right("inspector")
+        .resizable(true)
+        .show(ctx, |ui| {
+            ui.heading("Creatures");
+            ui.horizontal(|ui| {
+                ui.label("Plan file");
+                ui.text_edit_singleline(&mut inspector.export_path);
+            });
+            if ui.button("Export").clicked() {
+                let plan = CreaturesPlan {
+                    creatures: views.iter().map(|view| view.plan.clone()).collect(),
+                };
+                inspector.status = Some(match plan.save(&inspector.export_path) {
+                    Ok(()) => format!("Exported to {}", inspector.export_path),
+                    Err(err) => err.to_string(),
+                });
+            }
+            if let Some(status) = &inspector.status {
+                ui.label(status);
+            }
+            ui.separator();
+
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                for view in &mut views {
+                    creature_section(ui, view, &mut selection, &scheme);
+                }
+            });
+        });
+
+    let mut oscillators = world.p1();
+    for view in views {
+        if view.respawn || view.edited_segments {
+            respawn.write(RespawnCreature {
+                creature: view.entity,
+                plan: view.plan,
+            });
+            continue;
+        }
+        for limb_index in view.edited_oscillators {
+            let edited = &view.plan.limbs[limb_index].oscillator;
+            if let Ok(mut live) = oscillators.get_mut(view.limbs[limb_index]) {
+                live.set_amplitude(edited.amplitude());
+                live.set_frequency(edited.target_frequency());
+                live.set_transition_time(edited.transition_time());
+                live.set_wave(edited.wave());
+                live.set_beat_division(edited.beat_division());
+                live.set_retrigger(edited.retrigger());
+            }
+        }
+        if view.edited_sequencer {
+            match &view.plan.sequencer {
+                Some(sequencer) => commands.entity(view.entity).insert(sequencer.clone()),
+                None => commands.entity(view.entity).remove::<Sequencer>(),
+            };
+        }
+        if view.edited_voices {
+            match view.plan.voices {
+                Some(voices) => commands.entity(view.entity).insert(voices),
+                None => commands.entity(view.entity).remove::<VoiceAllocator>(),
+            };
+        }
+        if view.edited_effects {
+            match view.plan.effects {
+                Some(effects) => commands.entity(view.entity).insert(effects),
+                None => commands.entity(view.entity).remove::<Effects>(),
+            };
+        }
+        for limb_index in view.edited_audio {
+            let limb = view.limbs[limb_index];
+            match view.plan.limbs[limb_index].audio {
+                Some(audio) => commands.entity(limb).insert(audio),
+                None => commands.entity(limb).remove::<AudioReactive>(),
+            };
+        }
+    }
+
+    Ok(())
+}
+
+fn creature_section(
+    ui: &mut egui::Ui,
+    view: &mut CreatureView,
+    selection: &mut Selection,
+    scheme: &ControlScheme,
+) {
+    let creature_selected = selection.creature == Some(view.entity);
+    let header = if creature_selected {
+        format!("▶ {}", view.name)
+    } else {
+        view.name.clone()
+    };
+    egui::CollapsingHeader::new(header)
+        .id_salt(view.entity)
+        .show(ui, |ui| {
+            ui.horizontal(|ui| {
+                if ui
+                    .selectable_label(creature_selected && selection.limb.is_none(), "Select")
+                    .clicked()
+                {
+                    selection.creature = Some(view.entity);
+                    selection.limb = None;
+                }
+                if ui.button("Respawn").clicked() {
+                    view.respawn = true;
+                }
+            });
+            egui::CollapsingHeader::new("Sequencer")
+                .id_salt((view.entity, "sequencer"))
+                .show(ui, |ui| {
+                    ui.push_id((view.entity, "sequencer"), |ui| {
+                        if sequencer_controls(ui, &mut view.plan.sequencer, scheme) {
+                            view.edited_sequencer = true;
+                        }
+                    });
+                });
+            egui::CollapsingHeader::new("Voices")
+                .id_salt((view.entity, "voices"))
+                .show(ui, |ui| {
+                    if voice_controls(ui, &mut view.plan.voices) {
+                        view.edited_voices = true;
+                    }
+                });
+            egui::CollapsingHeader::new("Effects")
+                .id_salt((view.entity, "effects"))
+                .show(ui, |ui| {
+                    ui.push_id((view.entity, "effects"), |ui| {
+                        let limb_count = view.plan.limbs.len();
+                        if effects_controls(ui, &mut view.plan.effects, limb_count) {
+                            view.edited_effects = true;
+                        }
+                    });
+                });
+
+            let limb_count = view.plan.limbs.len();
+            for (limb_index, limb_plan) in view.plan.limbs.iter_mut().enumerate() {
+                let limb = view.limbs[limb_index];
+                let limb_selected = selection.limb == Some(limb);
+                egui::CollapsingHeader::new(format!("Limb {limb_index}"))
+                    .id_salt(limb)
+                    .show(ui, |ui| {
+                        ui.push_id(limb, |ui| {
+                            if ui.selectable_label(limb_selected, "Select").clicked() {
+                                selection.creature = Some(view.entity);
+                                selection.limb = Some(limb);
+                            }
+                            if oscillator_controls(ui, &mut limb_plan.oscillator, scheme) {
+                                view.edited_oscillators.push(limb_index);
+                            }
+                            if sync_controls(ui, &mut limb_plan.sync, limb_index, limb_count) {
+                                view.respawn = true;
+                            }
+                            if audio_controls(ui, &mut limb_plan.audio) {
+                                view.edited_audio.push(limb_index);
+                            }
+                            ui.label("Segments");
+                            if segment_list(ui, &mut limb_plan.segments) {
+                                view.edited_segments = true;
+                            }
+                        });
+                    });
+            }
+        });
+}
+
+/// Sliders for one oscillator, with ranges from the control scheme. Returns
+/// whether anything changed.
+pub fn oscillator_controls(
+    ui: &mut egui::Ui,
+    osc: &mut Oscillator,
+    scheme: &ControlScheme,
+) -> bool {
+    let mut changed = false;
+
+    let mut amplitude = osc.amplitude();
+    if ui
+        .add(param_slider(&mut amplitude, &scheme.amplitude).text("amplitude"))
+        .changed()
+    {
+        osc.set_amplitude(amplitude);
+        changed = true;
+    }
+
+    let mut locked = osc.beat_division().is_some();
+    if ui.checkbox(&mut locked, "beat sync").changed() {
+        osc.set_beat_division(locked.then(BeatDivision::default));
+        changed = true;
+    }
+    match osc.beat_division() {
+        Some(division) => {
+            if let Some(division) = beat_division_controls(ui, division) {
+                osc.set_beat_division(Some(division));
+                changed = true;
+            }
+        }
+        None => {
+            let mut frequency = osc.target_frequency();
+            if ui
+                .add(param_slider(&mut frequency, &scheme.frequency).text("frequency (Hz)"))
+                .changed()
+            {
+                osc.set_frequency(frequency);
+                changed = true;
+            }
+        }
+    }
+    ui.label(format!(
+        "current: {:.3} Hz, phase {:.2}",
+        osc.current_frequency(),
+        osc.phase()
+    ));
+
+    let mut tau = osc.transition_time().as_secs_f32();
+    if ui
+        .add(param_slider(&mut tau, &scheme.glide).text("transition (s)"))
+        .changed()
+    {
+        osc.set_transition_time(Duration::from_secs_f32(tau));
+        changed = true;
+    }
+
+    let mut retrigger = osc.retrigger();
+    if ui.checkbox(&mut retrigger, "retrigger on gate").changed() {
+        osc.set_retrigger(retrigger);
+        changed = true;
+    }
+
+    let mut wave = osc.wave();
+    egui::ComboBox::from_label("wave")
+        .selected_text(format!("{wave:?}"))
+        .show_ui(ui, |ui| {
+            for option in Wave::ALL {
+                ui.selectable_value(&mut wave, option, format!("{option:?}"));
+            }
+        });
+    if wave != osc.wave() {
+        osc.set_wave(wave);
+        changed = true;
+    }
+
+    changed
+}
+
+/// Pick another limb of the same creature to hard-sync to. Returns whether
+/// the choice changed.
+pub fn sync_controls(
+    ui: &mut egui::Ui,
+    sync: &mut Option<usize>,
+    limb_index: usize,
+    limb_count: usize,
+) -> bool {
+    let label = |sync: Option<usize>| sync.map_or("none".to_string(), |i| format!("Limb {i}"));
+    let before = *sync;
+    egui::ComboBox::from_label("hard sync to")
+        .selected_text(label(*sync))
+        .show_ui(ui, |ui| {
+            ui.selectable_value(sync, None, label(None));
+            for master in (0..limb_count).filter(|i| *i != limb_index) {
+                ui.selectable_value(sync, Some(master), label(Some(master)));
+            }
+        });
+    *sync != before
+}
+
+/// Optional audio-reactive mapping: which analysis feature drives the
+/// amplitude or frequency, and how strongly. Returns whether it changed.
+pub fn audio_controls(ui: &mut egui::Ui, audio: &mut Option<AudioReactive>) -> bool {
+    let mut changed = false;
+    let mut reactive = audio.is_some();
+    if ui.checkbox(&mut reactive, "audio reactive").changed() {
+        *audio = reactive.then(AudioReactive::default);
+        changed = true;
+    }
+    let Some(audio) = audio else {
+        return changed;
+    };
+    let before = *audio;
+    ui.horizontal(|ui| {
+        egui::ComboBox::from_id_salt("audio feature")
+            .selected_text(feature_label(audio.feature))
+            .show_ui(ui, |ui| {
+                for option in AudioFeature::all() {
+                    ui.selectable_value(&mut audio.feature, option, feature_label(option));
+                }
+            });
+        ui.label("→");
+        egui::ComboBox::from_id_salt("audio target")
+            .selected_text(format!("{:?}", audio.target))
+            .show_ui(ui, |ui| {
+                for option in AudioTarget::ALL {
+                    ui.selectable_value(&mut audio.target, option, format!("{option:?}"));
+                }
+            });
+    });
+    ui.add(egui::Slider::new(&mut audio.depth, -2.0..=2.0).text("depth"));
+    changed || *audio != before
+}
+
+fn feature_label(feature: AudioFeature) -> String {
+    match feature {
+        AudioFeature::Band(band) => format!("Band {band}"),
+        feature => format!("{feature:?}"),
+    }
+}
+
+/// Optional step sequencer: rate, length, gate length and a row per step.
+/// Returns whether anything changed.
+pub fn sequencer_controls(
+    ui: &mut egui::Ui,
+    sequencer: &mut Option<Sequencer>,
+    scheme: &ControlScheme,
+) -> bool {
+    let mut changed = false;
+    let mut enabled = sequencer.is_some();
+    if ui.checkbox(&mut enabled, "step sequencer").changed() {
+        *sequencer = enabled.then(Sequencer::default);
+        changed = true;
+    }
+    let Some(sequencer) = sequencer else {
+        return changed;
+    };
+    let before = sequencer.clone();
+
+    ui.horizontal(|ui| {
+        ui.label("step");
+        if let Some(rate) = beat_division_controls(ui, sequencer.rate) {
+            sequencer.rate = rate;
+        }
+    });
+    let mut length = sequencer.steps.len();
+    ui.horizontal(|ui| {
+        ui.label("steps");
+        ui.add(egui::DragValue::new(&mut length).range(1..=64));
+    });
+    sequencer.steps.resize(length, Step::default());
+    ui.add(egui::Slider::new(&mut sequencer.gate_length, 0.05..=1.0).text("gate length"));
+
+    egui::Grid::new("steps").striped(true).show(ui, |ui| {
+        for header in ["", "gate", "frequency (Hz)", "amplitude", "wave"] {
+            ui.label(header);
+        }
+        ui.end_row();
+        for (index, step) in sequencer.steps.iter_mut().enumerate() {
+            ui.label(index.to_string());
+            ui.checkbox(&mut step.gate, "");
+            optional_value(ui, &mut step.frequency, 1.0, &scheme.frequency, index);
+            optional_value(ui, &mut step.amplitude, 0.4, &scheme.amplitude, index);
+            let wave_label =
+                |wave: Option<Wave>| wave.map_or("—".to_string(), |w| format!("{w:?}"));
+            egui::ComboBox::from_id_salt(("step wave", index))
+                .selected_text(wave_label(step.wave))
+                .width(70.0)
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut step.wave, None, wave_label(None));
+                    for option in Wave::ALL {
+                        ui.selectable_value(&mut step.wave, Some(option), wave_label(Some(option)));
+                    }
+                });
+            ui.end_row();
+        }
+    });
+
+    changed || *sequencer != before
+}
+
+/// Optional polyphonic voice allocation: allocation mode and envelope.
+/// Returns whether anything changed.
+pub fn voice_controls(ui: &mut egui::Ui, voices: &mut Option<VoiceAllocator>) -> bool {
+    let mut changed = false;
+    let mut enabled = voices.is_some();
+    if ui.checkbox(&mut enabled, "polyphonic voices").changed() {
+        *voices = enabled.then(VoiceAllocator::default);
+        changed = true;
+    }
+    let Some(voices) = voices else {
+        return changed;
+    };
+    let before = *voices;
+
+    egui::ComboBox::from_id_salt("voice mode")
+        .selected_text(format!("{:?}", voices.mode))
+        .show_ui(ui, |ui| {
+            for option in VoiceMode::ALL {
+                ui.selectable_value(&mut voices.mode, option, format!("{option:?}"));
+            }
+        });
+    let envelope = &mut voices.envelope;
+    ui.add(
+        egui::Slider::new(&mut envelope.attack, 0.0..=2.0)
+            .text("attack (s)")
+            .logarithmic(true),
+    );
+    ui.add(
+        egui::Slider::new(&mut envelope.decay, 0.0..=2.0)
+            .text("decay (s)")
+            .logarithmic(true),
+    );
+    ui.add(egui::Slider::new(&mut envelope.sustain, 0.0..=1.0).text("sustain"));
+    ui.add(
+        egui::Slider::new(&mut envelope.release, 0.0..=4.0)
+            .text("release (s)")
+            .logarithmic(true),
+    );
+
+    changed || *voices != before
+}
+
+/// Optional effects chain: gain, then filter, delay and reverb, each with a
+/// checkbox. Returns whether anything changed.
+pub fn effects_controls(
+    ui: &mut egui::Ui,
+    effects: &mut Option<Effects>,
+    limb_count: usize,
+) -> bool {
+    let mut changed = false;
+    let mut enabled = effects.is_some();
+    if ui.checkbox(&mut enabled, "effects").changed() {
+        *effects = enabled.then(Effects::default);
+        changed = true;
+    }
+    let Some(effects) = effects else {
+        return changed;
+    };
+    let before = *effects;
+
+    ui.add(egui::Slider::new(&mut effects.gain, 0.0..=2.0).text("gain"));
+
+    toggle_section(ui, &mut effects.filter, "filter");
+    if let Some(filter) = &mut effects.filter {
+        egui::ComboBox::from_id_salt("filter mode")
+            .selected_text(format!("{:?}", filter.mode))
+            .show_ui(ui, |ui| {
+                for option in FilterMode::ALL {
+                    ui.selectable_value(&mut filter.mode, option, format!("{option:?}"));
+                }
+            });
+        ui.add(
+            egui::Slider::new(&mut filter.cutoff, 20.0..=20_000.0)
+                .logarithmic(true)
+                .text("cutoff (Hz)"),
+        );
+        ui.add(egui::Slider::new(&mut filter.resonance, 0.0..=1.0).text("resonance"));
+        toggle_section(ui, &mut filter.modulation, "cutoff follows a limb");
+        if let Some(modulation) = &mut filter.modulation {
+            ui.horizontal(|ui| {
+                ui.label("limb");
+                ui.add(
+                    egui::DragValue::new(&mut modulation.limb)
+                        .range(0..=limb_count.saturating_sub(1)),
+                );
+            });
+            ui.add(egui::Slider::new(&mut modulation.depth, 0.0..=6.0).text("depth (octaves)"));
+        }
+    }
+
+    toggle_section(ui, &mut effects.delay, "delay");
+    if let Some(delay) = &mut effects.delay {
+        ui.add(egui::Slider::new(&mut delay.time, 0.01..=MAX_DELAY_SECS).text("time (s)"));
+        ui.add(egui::Slider::new(&mut delay.feedback, 0.0..=0.95).text("feedback"));
+        ui.add(egui::Slider::new(&mut delay.mix, 0.0..=1.0).text("mix"));
+    }
+
+    toggle_section(ui, &mut effects.reverb, "reverb");
+    if let Some(reverb) = &mut effects.reverb {
+        ui.add(egui::Slider::new(&mut reverb.size, 0.0..=1.0).text("size"));
+        ui.add(egui::Slider::new(&mut reverb.damping, 0.0..=1.0).text("damping"));
+        ui.add(egui::Slider::new(&mut reverb.mix, 0.0..=1.0).text("mix"));
+    }
+
+    changed || *effects != before
+}
+
+/// A checkbox that sets a section to its default or clears it.
+fn toggle_section<T: Default>(ui: &mut egui::Ui, section: &mut Option<T>, label: &str) {
+    let mut enabled = section.is_some();
+    if ui.checkbox(&mut enabled, label).changed() {
+        *section = enabled.then(T::default);
+    }
+}
+
+/// A checkbox to set (to `default`) or clear a value, and a drag value to
+/// edit it.
+fn optional_value(
+    ui: &mut egui::Ui,
+    value: &mut Option<f32>,
+    default: f32,
+    control: &ParamControl,
+    id: usize,
+) {
+    ui.push_id(id, |ui| {
+        ui.horizontal(|ui| {
+            let mut set = value.is_some();
+            if ui.checkbox(&mut set, "").changed() {
+                *value = set.then_some(default.clamp(control.min, control.max));
+            }
+            if let Some(value) = value {
+                let speed = (control.max - control.min) / 200.0;
+                ui.add(
+                    egui::DragValue::new(value)
+                        .range(control.range())
+                        .speed(speed),
+                );
+            }
+        });
+    });
+}
+
+/// Note value and modifier pickers. Returns the new division if changed.
+fn beat_division_controls(ui: &mut egui::Ui, division: BeatDivision) -> Option<BeatDivision> {
+    let mut edited = division;
+    ui.horizontal(|ui| {
+        egui::ComboBox::from_id_salt("note value")
+            .selected_text(format!("{:?}", edited.value))
+            .show_ui(ui, |ui| {
+                for option in NoteValue::ALL {
+                    ui.selectable_value(&mut edited.value, option, format!("{option:?}"));
+                }
+            });
+        egui::ComboBox::from_id_salt("note modifier")
+            .selected_text(format!("{:?}", edited.modifier))
+            .show_ui(ui, |ui| {
+                for option in NoteModifier::ALL {
+                    ui.selectable_value(&mut edited.modifier, option, format!("{option:?}"));
+                }
+            });
+    });
+    (edited != division).then_some(edited)
+}
+
+fn param_slider<'a>(value: &'a mut f32, control: &ParamControl) -> egui::Slider<'a> {
+    egui::Slider::new(value, control.range())
+        .logarithmic(control.scale == StepScale::Exponential)
+        .clamping(egui::SliderClamping::Edits)
+}
+
+/// Editable list of segment types. Returns whether anything changed.
+fn segment_list(ui: &mut egui::Ui, segments: &mut Vec<LimbSegmentTypeId>) -> bool {
+    let mut changed = false;
+    let mut remove = None;
+
+    for (segment_index, type_id) in segments.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            ui.label(format!("{segment_index}"));
+            let before = *type_id;
+            egui::ComboBox::from_id_salt(segment_index)
+                .selected_text(format!("{type_id:?}"))
+                .show_ui(ui, |ui| {
+                    for option in LimbSegmentTypeId::ALL {
+                        ui.selectable_value(type_id, option, format!("{option:?}"));
+                    }
+                });
+            changed |= *type_id != before;
+            if ui.small_button("✖").clicked() {
+                remove = Some(segment_index);
+            }
+        });
+    }
+
+    if let Some(segment_index) = remove {
+        segments.remove(segment_index);
+        changed = true;
+    }
+    if ui.button("Add segment").clicked() {
+        let type_id = segments
+            .last()
+            .copied()
+            .unwrap_or(LimbSegmentTypeId::Rectangle);
+        segments.push(type_id);
+        changed = true;
+    }
+
+    changed
+}
@@ -0,0 +1,416 @@
+//! Creatures whose limbs are oscillators, and the synth they play.
+//!
+//! Add [`CreatureSynthPlugin`] to an app to spawn creatures from a
+//! [`CreaturesPlan`] and hear them; its fields choose which inputs and
+//! panels come along.
+
+pub mod audio_analysis;
+pub mod breeding;
+pub mod camera;
+pub mod cli;
+pub mod controls;
+pub mod creature;
+pub mod ecosystem;
+pub mod editor;
+pub mod effects;
+pub mod evolution;
+pub mod inspector;
+pub mod lifecycle;
+pub mod limb;
+pub mod locomotion;
+pub mod midi_clock;
+pub mod oscillator;
+pub mod piano;
+pub mod render;
+pub mod selection;
+pub mod sequencer;
+pub mod sound;
+pub mod steering;
+#[cfg(test)]
+mod testing;
+pub mod transport;
+pub mod voices;
+
+use bevy::{
+    audio::AddAudioSource,
+    ecs::{intern::Interned, schedule::ScheduleLabel},
+    input::common_conditions::input_just_pressed,
+    prelude::*,
+};
+use bevy_egui::{
+    input::{egui_wants_any_keyboard_input, egui_wants_any_pointer_input},
+    EguiPlugin, EguiPrimaryContextPass,
+};
+use std::path::PathBuf;
+
+pub use crate::audio_analysis::{AudioClip, AudioReactive};
+pub use crate::creature::{
+    benchmark_creatures_plan, example_creatures_plan, spawn_creature, Creature, CreatureCommands,
+    CreatureNames, CreaturePlan, CreaturesPlan, PlanError, SpawnOrder, SpawnedCreature,
+};
+pub use crate::effects::Effects;
+pub use crate::evolution::{Evolution, EvolutionSettings, Genome};
+pub use crate::limb::{Limb, LimbPlan, LimbSegmentTypeId};
+pub use crate::oscillator::{Oscillator, Wave};
+pub use crate::sequencer::Sequencer;
+pub use crate::voices::VoiceAllocator;
+
+use crate::{
+    audio_analysis::{
+        analyze_audio_file, apply_audio_reactivity, play_audio_file, reset_audio_modulation,
+        AudioAnalysis, AudioFileInput,
+    },
+    breeding::{breed_selected, lineage_panel, toggle_lineage, Breeding, Lineage},
+    camera::{camera_mouse_update, camera_user_update, move_camera, CameraControls},
+    controls::ControlScheme,
+    creature::{respawn_creatures, spawn_creatures, RespawnCreature},
+    ecosystem::{add_energy, eat, forage, live_and_die, setup_food_assets, spawn_food, Ecosystem},
+    editor::{
+        drag_limb_angle, editor_panel, enter_editor, exit_editor, rebuild_editor_preview,
+        toggle_editor, AppMode, Editor,
+    },
+    inspector::{inspector_panel, toggle_inspector, Inspector},
+    lifecycle::{
+        fade_dying_creatures, grow_spawning_creatures, start_dying, wither_dying_creatures,
+        Lifecycle,
+    },
+    limb::{animate_limb_segments, record_fixed_samples, LimbAssetStore},
+    locomotion::{add_swimmers, swim},
+    midi_clock::{sync_transport_to_midi_clock, MidiClock, MidiClockInput},
+    oscillator::{
+        gate_with_keys, hard_sync_oscillators, oscillator_tick, oscillator_user_update,
+        retrigger_on_gate, Gate,
+    },
+    piano::{piano_disabled, play_piano, toggle_piano, Piano},
+    selection::{
+        draw_selection_outline, pick_partner_with_click, select_with_click, select_with_keys,
+        shift_held, Selection,
+    },
+    sequencer::{advance_sequencers, close_removed_sequencer_gates},
+    sound::{play_synth, sound_panel, update_mixer, MasterBus, Synth},
+    steering::{add_flocking, pace_limbs, reset_limb_pace, steer, Wandering},
+    transport::{
+        lock_oscillators_to_transport, transport_panel, transport_tick, transport_user_update,
+        Transport,
+    },
+    voices::{play_monophonic, play_voices, reset_voice_gains, Note, NoteMapping},
+};
+
+pub const DEFAULT_PLAN_PATH: &str = "creatures.ron";
+pub const DEFAULT_EDITOR_PATH: &str = "creature.ron";
+/// Slowest fixed simulation rate: oscillator_tick caps steps at 50 ms.
+pub const MIN_FIXED_HZ: f64 = 20.0;
+
+/// Creatures, transport, oscillators and sound, plus whichever inputs and
+/// panels are enabled.
+///
+/// The simulation always runs; `sound`, `controls`, `camera`, `midi` and
+/// `audio_input` can each be turned off to fit into another app.
+#[derive(Debug, Clone)]
+pub struct CreatureSynthPlugin {
+    /// Creatures spawned at startup.
+    pub plan: CreaturesPlan,
+    /// Simulate at this rate (Hz) in FixedUpdate rather than once per frame,
+    /// so a run plays out the same however frames are timed. Limbs are drawn
+    /// between fixed steps.
+    pub fixed_hz: Option<f64>,
+    /// Creatures swim, pushed along by their limbs.
+    pub swim: bool,
+    /// Food to forage for, and creatures living, breeding and dying by
+    /// their energy.
+    pub ecosystem: bool,
+    /// Creatures wander and flock together, steering as they go.
+    pub flock: bool,
+    /// Play the creatures (and `audio_file`) through the default output.
+    pub sound: bool,
+    /// Keyboard and mouse controls, the inspector, transport and sound
+    /// panels, and the editor. Adds EguiPlugin if it isn't already.
+    pub controls: bool,
+    /// Spawn a 2D camera. Sound is panned across the view of the app's
+    /// Camera2d, whoever spawns it.
+    pub camera: bool,
+    /// Follow live MIDI clock and notes. Needs the `midi` feature.
+    pub midi: bool,
+    /// Drive audio-reactive limbs from the default input device. Needs the
+    /// `audio-input` feature.
+    pub audio_input: bool,
+    /// A clip driving audio-reactive limbs, looped.
+    pub audio_file: Option<AudioClip>,
+    /// Where the inspector exports the live creatures.
+    pub export_path: PathBuf,
+    /// Where the editor saves its creature.
+    pub editor_path: PathBuf,
+}
+
+impl Default for CreatureSynthPlugin {
+    fn default() -> Self {
+        Self {
+            plan: CreaturesPlan::default(),
+            fixed_hz: None,
+            swim: false,
+            ecosystem: false,
+            flock: false,
+            sound: true,
+            controls: true,
+            camera: true,
+            midi: cfg!(feature = "midi"),
+            audio_input: false,
+            audio_file: None,
+            export_path: PathBuf::from(DEFAULT_PLAN_PATH),
+            editor_path: PathBuf::from(DEFAULT_EDITOR_PATH),
+        }
+    }
+}
+
+impl Plugin for CreatureSynthPlugin {
+    fn build(&self, app: &mut App) {
+        self.add_simulation(app);
+        if self.swim {
+            app.add_systems(
+                Update,
+                (add_swimmers, swim)
+                    .chain()
+                    .after(grow_spawning_creatures)
+                    .after(wither_dying_creatures)
+                    .before(update_mixer),
+            );
+        }
+        if self.ecosystem {
+            app.init_resource::<Ecosystem>()
+                .add_systems(Startup, setup_food_assets)
+                .add_systems(
+                    Update,
+                    (spawn_food, add_energy, forage, eat, live_and_die)
+                        .chain()
+                        .after(animate_limb_segments)
+                        .before(update_mixer),
+                );
+        }
+        if self.flock {
+            app.init_resource::<Wandering>()
+                .add_systems(Update, (add_flocking, reset_limb_pace))
+                .add_systems(
+                    self.simulation_schedule(),
+                    (steer, pace_limbs).chain().before(oscillator_tick),
+                );
+        }
+        if self.sound {
+            app.add_systems(Startup, (play_audio_file, play_synth));
+        }
+        if self.camera {
+            app.add_systems(Startup, setup_camera);
+        }
+        if self.controls {
+            self.add_controls(app);
+        }
+        if let Some(clip) = &self.audio_file {
+            app.insert_resource(AudioFileInput::new(clip.clone()));
+        }
+
+        // Live audio input
+        #[cfg(feature = "audio-input")]
+        if self.audio_input {
+            app.add_systems(Startup, audio_analysis::input::start_live_audio_input)
+                .add_systems(
+                    Update,
+                    audio_analysis::input::analyze_live_audio.before(apply_audio_reactivity),
+                );
+        }
+        #[cfg(not(feature = "audio-input"))]
+        if self.audio_input {
+            warn!("audio input needs the audio-input feature");
+        }
+
+        // Live MIDI clock and note input
+        #[cfg(feature = "midi")]
+        if self.midi {
+            app.add_systems(Startup, midi_clock::input::connect_midi_input)
+                .add_systems(
+                    Update,
+                    midi_clock::input::receive_midi_input.before(sync_transport_to_midi_clock),
+                );
+        }
+        #[cfg(not(feature = "midi"))]
+        if self.midi {
+            warn!("MIDI input needs the midi feature");
+        }
+    }
+}
+
+impl CreatureSynthPlugin {
+    /// FixedUpdate at a fixed rate, otherwise Update.
+    fn simulation_schedule(&self) -> Interned<dyn ScheduleLabel> {
+        match self.fixed_hz {
+            Some(_) => FixedUpdate.intern(),
+            None => Update.intern(),
+        }
+    }
+
+    /// Creatures, transport, oscillators and the mixer.
+    fn add_simulation(&self, app: &mut App) {
+        let simulation = self.simulation_schedule();
+        if let Some(hz) = self.fixed_hz {
+            if hz < MIN_FIXED_HZ {
+                warn!("fixed_hz {hz} is below the minimum; simulating at {MIN_FIXED_HZ} Hz");
+            }
+            app.insert_resource(Time::<Fixed>::from_hz(hz.max(MIN_FIXED_HZ)))
+                .add_systems(
+                    FixedUpdate,
+                    record_fixed_samples.after(hard_sync_oscillators),
+                );
+        }
+
+        // Resources: type asset cache and the multi-creature plan.
+        app.insert_resource(LimbAssetStore::default())
+            .init_resource::<CreatureNames>()
+            .insert_resource(self.plan.clone())
+            .init_resource::<Selection>()
+            .init_resource::<ControlScheme>()
+            .init_resource::<Transport>()
+            .init_resource::<MidiClock>()
+            .add_message::<MidiClockInput>()
+            .add_message::<Gate>()
+            .init_resource::<NoteMapping>()
+            .add_message::<Note>()
+            .init_resource::<AudioAnalysis>()
+            .add_audio_source::<AudioClip>()
+            .init_resource::<MasterBus>()
+            .add_audio_source::<Synth>()
+            .add_message::<RespawnCreature>()
+            .init_resource::<Lifecycle>()
+            // Startup
+            .add_systems(Startup, spawn_creatures)
+            // Audio analysis
+            .add_systems(
+                simulation,
+                (analyze_audio_file, apply_audio_reactivity)
+                    .chain()
+                    .before(oscillator_tick),
+            )
+            // Transport and oscillator updates
+            .add_systems(
+                simulation,
+                (
+                    transport_tick,
+                    sync_transport_to_midi_clock,
+                    advance_sequencers,
+                    play_monophonic,
+                    oscillator_tick,
+                    lock_oscillators_to_transport,
+                    retrigger_on_gate,
+                    play_voices,
+                    hard_sync_oscillators,
+                )
+                    .chain()
+                    .before(animate_limb_segments),
+            )
+            .add_systems(
+                Update,
+                (
+                    respawn_creatures,
+                    reset_voice_gains,
+                    reset_audio_modulation,
+                    close_removed_sequencer_gates,
+                ),
+            )
+            // Animation and sound
+            .add_systems(Update, animate_limb_segments)
+            .add_systems(
+                Update,
+                (
+                    grow_spawning_creatures,
+                    (start_dying, wither_dying_creatures, fade_dying_creatures).chain(),
+                )
+                    .after(animate_limb_segments)
+                    .before(update_mixer),
+            )
+            .add_systems(Update, update_mixer.after(animate_limb_segments));
+    }
+
+    /// Keyboard and mouse input, panels and the editor.
+    fn add_controls(&self, app: &mut App) {
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugins(EguiPlugin::default());
+        }
+        app.init_resource::<Piano>()
+            .init_resource::<Breeding>()
+            .init_resource::<Lineage>()
+            .init_resource::<CameraControls>()
+            .insert_resource(Inspector::new(self.export_path.display().to_string()))
+            .insert_resource(Editor::new(self.editor_path.display().to_string()))
+            .init_state::<AppMode>()
+            .add_systems(
+                Update,
+                (
+                    select_with_keys,
+                    select_with_click.run_if(
+                        input_just_pressed(MouseButton::Left)
+                            .and(not(shift_held))
+                            .and(not(egui_wants_any_pointer_input)),
+                    ),
+                    pick_partner_with_click.run_if(
+                        input_just_pressed(MouseButton::Left)
+                            .and(shift_held)
+                            .and(not(egui_wants_any_pointer_input)),
+                    ),
+                    (oscillator_user_update, gate_with_keys).run_if(piano_disabled),
+                    play_piano,
+                    transport_user_update,
+                    breed_selected,
+                )
+                    .chain()
+                    .run_if(in_state(AppMode::Play).and(not(egui_wants_any_keyboard_input))),
+            )
+            .add_systems(
+                Update,
+                draw_selection_outline.run_if(in_state(AppMode::Play)),
+            )
+            // Camera
+            .add_systems(
+                Update,
+                (
+                    camera_mouse_update.run_if(not(egui_wants_any_pointer_input)),
+                    camera_user_update
+                        .run_if(in_state(AppMode::Play).and(not(egui_wants_any_keyboard_input))),
+                    move_camera,
+                )
+                    .chain()
+                    .after(TransformSystems::Propagate),
+            )
+            // Inspector
+            .add_systems(
+                Update,
+                (
+                    toggle_inspector,
+                    toggle_editor,
+                    toggle_piano,
+                    toggle_lineage,
+                )
+                    .run_if(not(egui_wants_any_keyboard_input)),
+            )
+            .add_systems(
+                EguiPrimaryContextPass,
+                (inspector_panel, transport_panel, sound_panel, lineage_panel)
+                    .run_if(in_state(AppMode::Play)),
+            )
+            // Editor
+            .add_systems(OnEnter(AppMode::Edit), enter_editor)
+            .add_systems(OnExit(AppMode::Edit), exit_editor)
+            .add_systems(
+                Update,
+                (
+                    drag_limb_angle.run_if(not(egui_wants_any_pointer_input)),
+                    rebuild_editor_preview,
+                )
+                    .run_if(in_state(AppMode::Edit)),
+            )
+            .add_systems(
+                EguiPrimaryContextPass,
+                editor_panel.run_if(in_state(AppMode::Edit)),
+            );
+    }
+}
+
+fn setup_camera(mut commands: Commands) {
+    commands.spawn(Camera2d);
+}
@@ -0,0 +1,339 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::sprite_render::AlphaMode2d;
+
+use crate::creature::{Creature, CreatureBody};
+use crate::editor::AppMode;
+use crate::limb::{Limb, LimbSegment, LimbSegments};
+
+/// Share of the spawn animation spent popping the body in, before the limbs
+/// start growing.
+const BODY_SHARE: f32 = 0.25;
+
+/// How long creatures take to appear and to die.
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct Lifecycle {
+    /// Seconds from spawning to fully grown.
+    pub grow_secs: f32,
+    /// Seconds from despawning to gone.
+    pub death_secs: f32,
+    /// How far each segment curls (radians) by the end of dying.
+    pub curl: f32,
+}
+
+impl Default for Lifecycle {
+    fn default() -> Self {
+        Self {
+            grow_secs: 1.5,
+            death_secs: 1.2,
+            curl: 0.5,
+        }
+    }
+}
+
+/// A creature growing in: the body pops in, then each limb grows out
+/// segment by segment, in the order they were spawned.
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct Spawning {
+    elapsed: f32,
+}
+
+/// A creature curling up and fading out, despawned when done.
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct Dying {
+    elapsed: f32,
+}
+
+impl Spawning {
+    /// 0 when just spawned, 1 when fully grown.
+    pub fn progress(&self, lifecycle: &Lifecycle) -> f32 {
+        progress(self.elapsed, lifecycle.grow_secs)
+    }
+}
+
+impl Dying {
+    /// 0 when just despawned, 1 when gone.
+    pub fn progress(&self, lifecycle: &Lifecycle) -> f32 {
+        progress(self.elapsed, lifecycle.death_secs)
+    }
+}
+
+fn progress(elapsed: f32, secs: f32) -> f32 {
+    if secs <= 0.0 {
+        1.0
+    } else {
+        (elapsed / secs).clamp(0.0, 1.0)
+    }
+}
+
+fn smoothstep(x: f32) -> f32 {
+    let x = x.clamp(0.0, 1.0);
+    x * x * (3.0 - 2.0 * x)
+}
+
+/// Overshoots a little before settling at 1, for a pop.
+fn ease_out_back(x: f32) -> f32 {
+    let c = 1.7;
+    let x = x.clamp(0.0, 1.0) - 1.0;
+    1.0 + (c + 1.0) * x * x * x + c * x * x
+}
+
+/// A creature's audible and visible presence (0..1) while coming or going.
+pub fn presence(lifecycle: &Lifecycle, spawning: Option<&Spawning>, dying: Option<&Dying>) -> f32 {
+    let growing = spawning.map_or(1.0, |s| s.progress(lifecycle));
+    let fading = dying.map_or(1.0, |d| 1.0 - smoothstep(d.progress(lifecycle)));
+    growing * fading
+}
+
+/// A creature's limb segments, for posing them while coming and going.
+#[derive(SystemParam)]
+pub struct CreatureSegments<'w, 's> {
+    limbs: Query<'w, 's, &'static LimbSegments, With<Limb>>,
+}
+
+impl CreatureSegments<'_, '_> {
+    /// Each segment of each of a creature's limbs, with its index and the
+    /// limb's segment count.
+    fn of(&self, creature: &Children) -> Vec<(Entity, usize, usize)> {
+        let mut out = Vec::new();
+        for segments in creature
+            .iter()
+            .filter_map(|child| self.limbs.get(child).ok())
+        {
+            let count = segments.len();
+            out.extend(
+                segments
+                    .iter()
+                    .enumerate()
+                    .map(|(index, segment)| (segment, index, count)),
+            );
+        }
+        out
+    }
+}
+
+type SpawningCreature<'a> = (Entity, &'a mut Spawning, &'a mut Visibility, &'a Children);
+type Posed = Or<(With<CreatureBody>, With<LimbSegment>)>;
+
+/// Grow spawning creatures: scale the body in, then each limb segment in
+/// turn. Segment scales multiply down the limb, so later segments stay
+/// hidden until their turn. Creatures born in the editor stay hidden behind
+/// its preview until it closes.
+pub fn grow_spawning_creatures(
+    mut commands: Commands,
+    time: Res<Time>,
+    lifecycle: Res<Lifecycle>,
+    mode: Option<Res<State<AppMode>>>,
+    mut creatures: Query<SpawningCreature, With<Creature>>,
+    segments: CreatureSegments,
+    mut transforms: Query<&mut Transform, Posed>,
+) {
+    let editing = mode.is_some_and(|mode| *mode.get() == AppMode::Edit);
+    for (creature, mut spawning, mut visibility, creature_children) in &mut creatures {
+        // Hidden until the first pose is in place; after that, visibility is
+        // the editor's to change.
+        if spawning.elapsed == 0.0 && !editing {
+            *visibility = Visibility::Inherited;
+        }
+        spawning.elapsed += time.delta_secs();
+        let p = spawning.progress(&lifecycle);
+
+        // The body is the only such direct child; segments hang off limbs.
+        let body = ease_out_back(p / BODY_SHARE).max(0.0);
+        for child in creature_children.iter() {
+            if let Ok(mut transform) = transforms.get_mut(child) {
+                transform.scale = Vec3::splat(body);
+            }
+        }
+
+        let grown = ((p - BODY_SHARE) / (1.0 - BODY_SHARE)).max(0.0);
+        for (segment, index, count) in segments.of(creature_children) {
+            if let Ok(mut transform) = transforms.get_mut(segment) {
+                let scale = smoothstep(grown * count as f32 - index as f32);
+                transform.scale = Vec3::splat(scale);
+            }
+        }
+
+        if p >= 1.0 {
+            commands.entity(creature).remove::<Spawning>();
+        }
+    }
+}
+
+/// Give a dying creature its own copies of its materials, so it can fade
+/// without fading every creature sharing them.
+pub fn start_dying(
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    creatures: Query<Entity, Added<Dying>>,
+    children: Query<&Children>,
+    mut meshes: Query<&mut MeshMaterial2d<ColorMaterial>>,
+) {
+    for creature in &creatures {
+        for entity in children.iter_descendants(creature) {
+            if let Ok(mut material) = meshes.get_mut(entity)
+                && let Some(own) = materials.get(&material.0).cloned()
+            {
+                material.0 = materials.add(ColorMaterial {
+                    alpha_mode: AlphaMode2d::Blend,
+                    ..own
+                });
+            }
+        }
+    }
+}
+
+/// Curl dying creatures' limbs, despawning them when done. Runs after the
+/// limbs are posed by their oscillators.
+pub fn wither_dying_creatures(
+    mut commands: Commands,
+    time: Res<Time>,
+    lifecycle: Res<Lifecycle>,
+    mut creatures: Query<(Entity, &mut Dying, &Children), With<Creature>>,
+    segments: CreatureSegments,
+    mut transforms: Query<&mut Transform, With<LimbSegment>>,
+) {
+    for (creature, mut dying, creature_children) in &mut creatures {
+        dying.elapsed += time.delta_secs();
+        let p = dying.progress(&lifecycle);
+        if p >= 1.0 {
+            commands.entity(creature).despawn();
+            continue;
+        }
+
+        let curl = lifecycle.curl * smoothstep(p);
+        for (segment, index, count) in segments.of(creature_children) {
+            if let Ok(mut transform) = transforms.get_mut(segment) {
+                // Tips curl furthest.
+                let along = (index + 1) as f32 / count as f32;
+                transform.rotation *= Quat::from_rotation_z(curl * along);
+            }
+        }
+    }
+}
+
+/// Fade dying creatures out along with their sound.
+pub fn fade_dying_creatures(
+    lifecycle: Res<Lifecycle>,
+    creatures: Query<(Entity, &Dying)>,
+    children: Query<&Children>,
+    meshes: Query<&MeshMaterial2d<ColorMaterial>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    for (creature, dying) in &creatures {
+        let alpha = presence(&lifecycle, None, Some(dying));
+        for entity in children.iter_descendants(creature) {
+            if let Ok(material) = meshes.get(entity)
+                && let Some(material) = materials.get_mut(&material.0)
+            {
+                material.color.set_alpha(alpha);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::creature::{
+        example_creatures_plan, CreatureCommands, CreaturesPlan, SpawnedCreature,
+    };
+    use crate::testing::Harness;
+    use bevy::ecs::system::RunSystemOnce;
+
+    fn spawn(harness: &mut Harness) -> SpawnedCreature {
+        let mut plan = example_creatures_plan().creatures.remove(0);
+        plan.limbs.truncate(2);
+        harness
+            .app
+            .world_mut()
+            .run_system_once(move |mut commands: Commands| commands.spawn_creature(plan.clone()))
+            .unwrap()
+    }
+
+    fn scales(harness: &Harness, limb: Entity) -> Vec<f32> {
+        harness
+            .segments(limb)
+            .into_iter()
+            .map(|segment| harness.world().get::<Transform>(segment).unwrap().scale.x)
+            .collect()
+    }
+
+    #[test]
+    fn limbs_grow_out_from_the_body() {
+        let mut harness = Harness::new(CreaturesPlan::default());
+        let spawned = spawn(&mut harness);
+        let lifecycle = Lifecycle::default();
+
+        // Halfway through growing the limbs.
+        harness.advance((lifecycle.grow_secs * (1.0 + BODY_SHARE) / 2.0) as f64);
+        let halfway = scales(&harness, spawned.limbs[0]);
+        assert_eq!(halfway[0], 1.0);
+        assert_eq!(*halfway.last().unwrap(), 0.0);
+        assert!(halfway.windows(2).all(|pair| pair[0] >= pair[1]));
+
+        harness.advance(lifecycle.grow_secs as f64);
+        assert!(harness.world().get::<Spawning>(spawned.creature).is_none());
+        assert!(scales(&harness, spawned.limbs[1]).iter().all(|&s| s == 1.0));
+    }
+
+    #[test]
+    fn growing_leaves_the_editor_hiding_creatures() {
+        let mut harness = Harness::new(CreaturesPlan::default());
+        let visibility = |harness: &Harness, creature: Entity| {
+            *harness.world().get::<Visibility>(creature).unwrap()
+        };
+        let spawned = spawn(&mut harness);
+        harness.advance(0.1);
+        assert_eq!(visibility(&harness, spawned.creature), Visibility::Inherited);
+
+        // Entering the editor mid-growth hides it, and it stays hidden.
+        harness
+            .app
+            .world_mut()
+            .entity_mut(spawned.creature)
+            .insert(Visibility::Hidden);
+        harness.app.insert_resource(State::new(AppMode::Edit));
+        harness.advance(0.1);
+        assert_eq!(visibility(&harness, spawned.creature), Visibility::Hidden);
+
+        // As does one born while editing.
+        let born = spawn(&mut harness);
+        harness.advance(0.1);
+        assert_eq!(visibility(&harness, born.creature), Visibility::Hidden);
+        assert!(harness.world().get::<Spawning>(born.creature).is_some());
+    }
+
+    #[test]
+    fn dying_creatures_fade_out() {
+        let mut harness = Harness::new(CreaturesPlan::default());
+        let spawned = spawn(&mut harness);
+        let survivor = spawn(&mut harness);
+        harness.advance(Lifecycle::default().grow_secs as f64 + 0.1);
+        harness
+            .app
+            .world_mut()
+            .run_system_once(move |mut commands: Commands| {
+                commands.despawn_creature(spawned.creature)
+            })
+            .unwrap();
+
+        let alpha = |harness: &Harness, limb: Entity| {
+            let world = harness.world();
+            let segment = harness.segments(limb)[0];
+            let material = world
+                .get::<Children>(segment)
+                .unwrap()
+                .iter()
+                .find_map(|child| world.get::<MeshMaterial2d<ColorMaterial>>(child));
+            let materials = world.resource::<Assets<ColorMaterial>>();
+            materials.get(&material.unwrap().0).unwrap().color.alpha()
+        };
+        harness.advance(0.3);
+        let early = alpha(&harness, spawned.limbs[0]);
+        harness.advance(0.6);
+        let late = alpha(&harness, spawned.limbs[0]);
+        assert!(early < 1.0 && late < early, "{early} then {late}");
+        // The survivor shares the type's materials, but not the fading.
+        assert_eq!(alpha(&harness, survivor.limbs[0]), 1.0);
+    }
+}
@@ -1,8 +1,10 @@
 use bevy::ecs::relationship::RelatedSpawnerCommands;
 use bevy::math::primitives::{Circle, Rectangle};
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+use crate::audio_analysis::AudioReactive;
 use crate::oscillator::Oscillator;
 
 #[derive(Component)]
@@ -16,6 +18,25 @@ pub struct LimbSegment {
     pub type_id: LimbSegmentTypeId,
 }
 
+/// The limb a segment belongs to, however far down the limb's chain it is.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+#[relationship(relationship_target = LimbSegments)]
+pub struct SegmentOf(pub Entity);
+
+/// A limb's segments, from the body outwards, so they can be reached without
+/// walking the chain of joints.
+#[derive(Component, Debug, Default)]
+#[relationship_target(relationship = SegmentOf)]
+pub struct LimbSegments(Vec<Entity>);
+
+impl std::ops::Deref for LimbSegments {
+    type Target = [Entity];
+
+    fn deref(&self) -> &[Entity] {
+        &self.0
+    }
+}
+
 #[derive(Component)]
 #[require(Transform, Visibility)]
 pub struct LimbSegmentBody;
@@ -24,12 +45,16 @@ pub struct LimbSegmentBody;
 #[require(Transform, Visibility, Children)]
 pub struct LimbSegmentJoint;
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum LimbSegmentTypeId {
     Rectangle,
     Disk,
 }
 
+impl LimbSegmentTypeId {
+    pub const ALL: [LimbSegmentTypeId; 2] = [LimbSegmentTypeId::Rectangle, LimbSegmentTypeId::Disk];
+}
+
 /// Mesh and material handles used by a given type id.
 #[derive(Clone)]
 pub struct TypeHandles {
@@ -78,6 +103,7 @@ pub trait LimbSegmentType {
     fn spawn_segment(
         commands: &mut Commands,
         parent: Entity,
+        limb: Entity,
         limb_index: usize,
         segment_index: usize,
         store: &LimbAssetStore,
@@ -150,6 +176,7 @@ impl LimbSegmentType for RectType {
     fn spawn_segment(
         commands: &mut Commands,
         parent: Entity,
+        limb: Entity,
         limb_index: usize,
         segment_index: usize,
         store: &LimbAssetStore,
@@ -164,6 +191,7 @@ impl LimbSegmentType for RectType {
                     segment_index,
                     type_id: LimbSegmentTypeId::Rectangle,
                 },
+                SegmentOf(limb),
                 Name::new(format!("Limb {limb_index} Segment {segment_index}")),
                 Transform::default(),
             ));
@@ -269,6 +297,7 @@ impl LimbSegmentType for DiskType {
     fn spawn_segment(
         commands: &mut Commands,
         parent: Entity,
+        limb: Entity,
         limb_index: usize,
         segment_index: usize,
         store: &LimbAssetStore,
@@ -287,6 +316,7 @@ impl LimbSegmentType for DiskType {
                     segment_index,
                     type_id: LimbSegmentTypeId::Disk,
                 },
+                SegmentOf(limb),
                 Name::new(format!("Limb {limb_index} Segment {segment_index}")),
                 Transform::default(),
             ));
@@ -350,16 +380,17 @@ impl LimbSegmentTypeId {
         &self,
         commands: &mut Commands,
         parent: Entity,
+        limb: Entity,
         limb_index: usize,
         segment_index: usize,
         store: &LimbAssetStore,
     ) -> Entity {
         match self {
             LimbSegmentTypeId::Rectangle => {
-                RectType::spawn_segment(commands, parent, limb_index, segment_index, store)
+                RectType::spawn_segment(commands, parent, limb, limb_index, segment_index, store)
             }
             LimbSegmentTypeId::Disk => {
-                DiskType::spawn_segment(commands, parent, limb_index, segment_index, store)
+                DiskType::spawn_segment(commands, parent, limb, limb_index, segment_index, store)
             }
         }
     }
@@ -372,28 +403,203 @@ impl LimbSegmentTypeId {
     }
 }
 
-/// Animate all limb segments with their limb oscillator and type-specific flex.
-pub fn animate_limb_segments(
-    children: Query<&Children>,
-    limbs: Query<(&Oscillator, Entity), With<Limb>>,
-    mut limb_segments: Query<(&mut Transform, &LimbSegment), With<LimbSegment>>,
+/// A limb's oscillator output at the last two fixed steps, so drawing can
+/// fall between them. Only present when simulating at a fixed rate.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct FixedSample {
+    previous: f32,
+    current: f32,
+}
+
+impl FixedSample {
+    /// The output `fraction` (0..1) of the way from the previous step to the
+    /// current one.
+    pub fn interpolate(&self, fraction: f32) -> f32 {
+        self.previous + (self.current - self.previous) * fraction
+    }
+}
+
+/// Keep each limb's FixedSample up to date, after the fixed step's
+/// oscillator updates.
+pub fn record_fixed_samples(
+    mut commands: Commands,
+    mut limbs: Query<(Entity, &Oscillator, Option<&mut FixedSample>), With<Limb>>,
 ) {
-    for (osc, limb_entity) in &limbs {
-        let angle = osc.sample();
-        for child in children.iter_descendants(limb_entity) {
-            if let Ok((mut transform, limb_segment)) = limb_segments.get_mut(child) {
-                let flex = limb_segment
-                    .type_id
-                    .flex_for_segment(limb_segment.segment_index);
-                transform.rotation = Quat::from_rotation_z(angle * flex);
+    for (entity, osc, sample) in &mut limbs {
+        let current = osc.sample();
+        match sample {
+            Some(mut sample) => {
+                sample.previous = sample.current;
+                sample.current = current;
+            }
+            None => {
+                commands.entity(entity).insert(FixedSample {
+                    previous: current,
+                    current,
+                });
             }
         }
     }
 }
 
+/// Animate all limb segments with their limb oscillator and type-specific flex.
+/// At a fixed simulation rate, limbs are drawn between the last two steps.
+///
+/// Segments are posed in parallel, each looking up its own limb, so no
+/// limb's chain is walked.
+pub fn animate_limb_segments(
+    fixed: Res<Time<Fixed>>,
+    limbs: Query<(&Oscillator, Option<&FixedSample>), With<Limb>>,
+    mut limb_segments: Query<(&mut Transform, &LimbSegment, &SegmentOf)>,
+) {
+    let fraction = fixed.overstep_fraction();
+    limb_segments
+        .par_iter_mut()
+        .for_each(|(mut transform, limb_segment, segment_of)| {
+            let Ok((osc, sample)) = limbs.get(segment_of.0) else {
+                return;
+            };
+            let angle = sample.map_or_else(|| osc.sample(), |s| s.interpolate(fraction));
+            let flex = limb_segment
+                .type_id
+                .flex_for_segment(limb_segment.segment_index);
+            transform.rotation = Quat::from_rotation_z(angle * flex);
+        });
+}
+
 /// A limb plan specifies its oscillator and the per-segment types.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LimbPlan {
     pub oscillator: Oscillator,
     pub segments: Vec<LimbSegmentTypeId>,
+    /// Attachment angle around the body (radians). When unset, limbs are
+    /// distributed evenly around a circle.
+    #[serde(default)]
+    pub angle: Option<f32>,
+    /// Index of another limb of the same creature whose oscillator this one
+    /// hard-syncs to.
+    #[serde(default)]
+    pub sync: Option<usize>,
+    /// Audio analysis driving this limb's oscillator.
+    #[serde(default)]
+    pub audio: Option<AudioReactive>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::creature::{example_creatures_plan, CreaturePlan, CreaturesPlan};
+    use crate::oscillator::Wave;
+    use crate::testing::Harness;
+    use std::f32::consts::TAU;
+
+    fn one_limb_plan(segments: Vec<LimbSegmentTypeId>) -> CreaturesPlan {
+        CreaturesPlan {
+            creatures: vec![CreaturePlan {
+                limbs: vec![LimbPlan {
+                    oscillator: Oscillator::new(Wave::Sine, 0.2, 0.4),
+                    segments,
+                    angle: None,
+                    sync: None,
+                    audio: None,
+                }],
+                transform: Transform::default(),
+                sequencer: None,
+                voices: None,
+                effects: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn spawns_the_planned_hierarchy() {
+        let mut harness = Harness::new(example_creatures_plan());
+        let creatures = harness.creatures();
+        assert_eq!(creatures.len(), 6);
+        for creature in creatures {
+            let limbs = harness.limbs(creature);
+            assert_eq!(limbs.len(), 8);
+            for limb in limbs {
+                let segments = harness.segments(limb);
+                assert_eq!(segments.len(), 16);
+                let cached = harness.world().get::<LimbSegments>(limb).unwrap();
+                assert_eq!(**cached, segments[..]);
+                assert_eq!(harness.joints(limb).len(), 16);
+                for (i, segment) in segments.into_iter().enumerate() {
+                    let segment = harness.world().get::<LimbSegment>(segment).unwrap();
+                    assert_eq!(segment.segment_index, i);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn segments_bend_with_their_oscillator() {
+        let types = vec![
+            LimbSegmentTypeId::Rectangle,
+            LimbSegmentTypeId::Rectangle,
+            LimbSegmentTypeId::Disk,
+            LimbSegmentTypeId::Disk,
+        ];
+        let mut harness = Harness::new(one_limb_plan(types.clone()));
+        let creature = harness.creatures()[0];
+        let limb = harness.limbs(creature)[0];
+
+        let times = [0.0, 0.3, 0.625, 1.0, 2.2];
+        let samples = harness.sample_rotations(limb, &times);
+        for (time, rotations) in times.iter().zip(samples) {
+            let angle = 0.2 * (TAU * 0.4 * *time as f32).sin();
+            for (i, (rotation, type_id)) in rotations.iter().zip(&types).enumerate() {
+                let expected = angle * type_id.flex_for_segment(i);
+                assert!(
+                    (rotation - expected).abs() < 1e-4,
+                    "segment {i} at {time}s: {rotation} != {expected}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn fixed_samples_interpolate_between_steps() {
+        let sample = FixedSample {
+            previous: 0.2,
+            current: 0.6,
+        };
+        assert_eq!(sample.interpolate(0.0), 0.2);
+        assert!((sample.interpolate(0.25) - 0.3).abs() < 1e-6);
+        assert_eq!(sample.interpolate(1.0), 0.6);
+    }
+
+    /// Every limb's phase after running the example at 60 Hz fixed steps,
+    /// with frames of the given lengths.
+    fn fixed_rate_phases(frames: impl IntoIterator<Item = f64>) -> Vec<f32> {
+        let mut harness = Harness::fixed(example_creatures_plan(), 60.0);
+        for secs in frames {
+            harness.frame(secs);
+        }
+        let limbs: Vec<Entity> = harness
+            .creatures()
+            .into_iter()
+            .flat_map(|creature| harness.limbs(creature))
+            .collect();
+        limbs
+            .into_iter()
+            .map(|limb| harness.world().get::<Oscillator>(limb).unwrap().phase())
+            .collect()
+    }
+
+    #[test]
+    fn fixed_rate_runs_ignore_frame_timing() {
+        // Just over two seconds (120 fixed steps), in steady frames and in
+        // ragged ones.
+        let steady = fixed_rate_phases(std::iter::repeat_n(0.01, 201));
+        let ragged = fixed_rate_phases(
+            [0.003, 0.041, 0.007, 0.022, 0.1, 0.011, 0.016]
+                .into_iter()
+                .cycle()
+                .take(71),
+        );
+        assert!(steady.iter().any(|&phase| phase > 0.0));
+        assert_eq!(steady, ragged);
+    }
 }
@@ -0,0 +1,194 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+
+use crate::creature::Creature;
+use crate::editor::EditorPreview;
+use crate::lifecycle::{Dying, Spawning};
+use crate::limb::{LimbSegment, LimbSegmentBody, LimbSegmentTypeId};
+
+/// Drag on the body, against that of a single segment moving along itself.
+const BODY_DRAG: f32 = 8.0;
+
+/// A creature swimming through a thick fluid, pushed along by its limbs.
+///
+/// Each segment drags against the fluid, rectangles more across their
+/// length than along it, and the creature moves and turns so the drag
+/// balances out. A limb waving back and forth on its own goes nowhere;
+/// limbs need to move out of step with each other to get anywhere.
+#[derive(Component, Debug, Clone, Default)]
+pub struct Swimmer {
+    /// Each segment's position in the creature's frame at the last update.
+    previous: Vec<Vec2>,
+}
+
+/// A segment's place and heading in its creature's frame.
+struct Stroke {
+    position: Vec2,
+    tangent: Vec2,
+    type_id: LimbSegmentTypeId,
+}
+
+impl LimbSegmentTypeId {
+    /// Drag along and across the segment.
+    fn drag(&self) -> (f32, f32) {
+        match self {
+            LimbSegmentTypeId::Rectangle => (1.0, 2.0),
+            LimbSegmentTypeId::Disk => (1.5, 1.5),
+        }
+    }
+}
+
+/// Poses a creature's segments in its own frame, from their local
+/// transforms, so a pose is up to date as soon as the limbs are animated.
+#[derive(SystemParam)]
+pub struct Strokes<'w, 's> {
+    children: Query<'w, 's, &'static Children>,
+    transforms: Query<'w, 's, &'static Transform, Without<Creature>>,
+    segments: Query<'w, 's, &'static LimbSegment>,
+    bodies: Query<'w, 's, (), With<LimbSegmentBody>>,
+}
+
+impl Strokes<'_, '_> {
+    fn of(&self, creature: Entity) -> Vec<Stroke> {
+        let mut strokes = Vec::new();
+        self.collect(creature, Transform::IDENTITY, &mut strokes);
+        strokes
+    }
+
+    fn collect(&self, parent: Entity, pose: Transform, strokes: &mut Vec<Stroke>) {
+        let Ok(children) = self.children.get(parent) else {
+            return;
+        };
+        for child in children.iter() {
+            let Ok(transform) = self.transforms.get(child) else {
+                continue;
+            };
+            let pose = pose.mul_transform(*transform);
+            if let Ok(segment) = self.segments.get(child) {
+                // Drag acts at the segment's visible body.
+                let center = self
+                    .children
+                    .get(child)
+                    .ok()
+                    .and_then(|children| children.iter().find(|e| self.bodies.contains(*e)))
+                    .and_then(|body| self.transforms.get(body).ok())
+                    .map_or(pose.translation, |body| {
+                        pose.transform_point(body.translation)
+                    });
+                strokes.push(Stroke {
+                    position: center.truncate(),
+                    tangent: (pose.rotation * Vec3::X).truncate().normalize_or_zero(),
+                    type_id: segment.type_id,
+                });
+            }
+            self.collect(child, pose, strokes);
+        }
+    }
+}
+
+/// How far the creature moves (in its own frame) and turns so the drag on
+/// its segments balances, given how far each moved relative to the body.
+fn balance(strokes: &[Stroke], motion: &[Vec2]) -> (Vec2, f32) {
+    // Solve for (step, turn) with no net force or torque: each segment moves
+    // by step + turn × position + motion, and drags with D·v.
+    let mut matrix = Mat3::from_diagonal(Vec3::new(BODY_DRAG, BODY_DRAG, 0.0));
+    let mut rhs = Vec3::ZERO;
+    for (stroke, &motion) in strokes.iter().zip(motion) {
+        let (along, across) = stroke.type_id.drag();
+        let t = stroke.tangent;
+        let n = t.perp();
+        let drag = Mat2::from_cols(
+            along * t.x * t + across * n.x * n,
+            along * t.y * t + across * n.y * n,
+        );
+        let arm = stroke.position.perp();
+        let drag_arm = drag * arm;
+        let force = drag * motion;
+
+        matrix.x_axis += drag.x_axis.extend(arm.dot(drag.x_axis));
+        matrix.y_axis += drag.y_axis.extend(arm.dot(drag.y_axis));
+        matrix.z_axis += drag_arm.extend(arm.dot(drag_arm));
+        rhs -= force.extend(arm.dot(force));
+    }
+    if matrix.determinant().abs() < f32::EPSILON {
+        return (Vec2::ZERO, 0.0);
+    }
+    let solution = matrix.inverse() * rhs;
+    (solution.truncate(), solution.z)
+}
+
+/// Move swimming creatures by how their limbs moved since the last update.
+/// Creatures still growing in or dying drift with the fluid.
+pub fn swim(
+    time: Res<Time>,
+    mut creatures: Query<(Entity, &mut Transform, &mut Swimmer), SwimmingCreature>,
+    strokes: Strokes,
+) {
+    let dt = time.delta_secs();
+    for (creature, mut transform, mut swimmer) in &mut creatures {
+        let current = strokes.of(creature);
+        let positions: Vec<Vec2> = current.iter().map(|stroke| stroke.position).collect();
+        if dt > 0.0 && swimmer.previous.len() == positions.len() {
+            let motion: Vec<Vec2> = positions
+                .iter()
+                .zip(&swimmer.previous)
+                .map(|(now, before)| *now - *before)
+                .collect();
+            // Drag is linear, so steps scale with how far limbs moved,
+            // whatever the time step.
+            let (step, turn) = balance(&current, &motion);
+            let step = transform.rotation * step.extend(0.0);
+            transform.translation += step;
+            transform.rotate_z(turn);
+        }
+        swimmer.previous = positions;
+    }
+}
+
+type SwimmingCreature = (With<Creature>, Without<Spawning>, Without<Dying>);
+
+/// Give every creature a Swimmer as it's spawned, bar the editor's preview.
+pub fn add_swimmers(
+    mut commands: Commands,
+    creatures: Query<Entity, (Added<Creature>, Without<EditorPreview>)>,
+) {
+    for creature in &creatures {
+        commands.entity(creature).insert(Swimmer::default());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::evolution::{swim_trial, EvolutionSettings, Genome, LimbGene};
+    use crate::limb::LimbSegmentTypeId;
+    use crate::oscillator::Wave;
+
+    fn swimmer(phases: &[f32]) -> Genome {
+        Genome {
+            limbs: phases
+                .iter()
+                .map(|&phase| LimbGene {
+                    wave: Wave::Sine,
+                    amplitude: 0.5,
+                    frequency: 1.0,
+                    phase,
+                    segments: vec![LimbSegmentTypeId::Rectangle; 8],
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn limbs_out_of_step_swim_further() {
+        let settings = EvolutionSettings {
+            trial_secs: 5.0,
+            ..Default::default()
+        };
+        let distances = swim_trial(
+            &[swimmer(&[0.0, 0.0, 0.0]), swimmer(&[0.0, 0.3, 0.6])],
+            &settings,
+        );
+        assert!(distances[0] < 1.0, "{distances:?}");
+        assert!(distances[1] > 5.0, "{distances:?}");
+    }
+}
@@ -1,34 +1,120 @@
-mod creature;
-mod limb;
-mod oscillator;
-
 use bevy::{dev_tools::fps_overlay::FpsOverlayPlugin, prelude::*};
+use std::path::PathBuf;
 
-use crate::{
-    creature::{example_creatures_plan, spawn_creatures},
-    limb::{animate_limb_segments, LimbAssetStore},
-    oscillator::{oscillator_tick, oscillator_user_update},
+use creature_synth::{
+    audio_analysis::{analyze_clip, write_analysis_csv},
+    cli::Args,
+    evolution::swim_trial,
+    example_creatures_plan,
+    render::{add_render, headless_plugins, Recorder},
+    steering::Wandering,
+    AudioClip, CreatureSynthPlugin, CreaturesPlan, Evolution, EvolutionSettings, Genome,
+    DEFAULT_PLAN_PATH,
 };
 
+/// Where --evolve saves the last generation.
+const EVOLVED_PLAN_PATH: &str = "evolved.ron";
+
 fn main() {
-    App::new()
-        .add_plugins(DefaultPlugins)
-        .add_plugins(FpsOverlayPlugin {
-            ..Default::default()
+    let args = Args::parse(std::env::args().skip(1)).unwrap_or_else(|err| {
+        eprintln!("{err}");
+        std::process::exit(1);
+    });
+    if let Some(path) = &args.analyze_path {
+        let result = AudioClip::load_wav(path)
+            .map_err(|err| err.to_string())
+            .and_then(|clip| {
+                write_analysis_csv(&analyze_clip(&clip), std::io::stdout().lock())
+                    .map_err(|err| err.to_string())
+            });
+        if let Err(err) = result {
+            eprintln!("failed to analyze {}: {err}", path.display());
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let mut plan = match &args.plan_path {
+        Some(path) => CreaturesPlan::load(path).unwrap_or_else(|err| {
+            eprintln!("failed to load {}: {err}", path.display());
+            std::process::exit(1);
+        }),
+        None => example_creatures_plan(),
+    };
+    if let Some(generations) = args.generations {
+        plan = evolve(&plan, generations, args.seed);
+    }
+    let audio_file = args.audio_path.map(|path| {
+        AudioClip::load_wav(&path).unwrap_or_else(|err| {
+            eprintln!("failed to load {}: {err}", path.display());
+            std::process::exit(1);
         })
-        // Resources: type asset cache and an example multi-creature plan.
-        .insert_resource(LimbAssetStore::default())
-        .insert_resource(example_creatures_plan())
-        // Startup
-        .add_systems(Startup, (setup_camera, spawn_creatures))
-        // Oscillator updates
-        .add_systems(Update, oscillator_tick.before(animate_limb_segments))
-        .add_systems(Update, oscillator_user_update)
-        // Animation
-        .add_systems(Update, animate_limb_segments)
-        .run();
+    });
+    let plugin = CreatureSynthPlugin {
+        plan,
+        fixed_hz: args.fixed_hz,
+        swim: args.generations.is_some(),
+        ecosystem: args.ecosystem,
+        flock: args.flock,
+        audio_input: args.live_audio,
+        audio_file,
+        export_path: args
+            .plan_path
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_PLAN_PATH)),
+        ..default()
+    };
+
+    let mut app = App::new();
+    if args.flock && let Some(seed) = args.seed {
+        app.insert_resource(Wandering::seeded(seed));
+    }
+    match args.render {
+        Some(settings) => {
+            let recorder = Recorder::new(settings.clone(), plugin.audio_file.clone())
+                .unwrap_or_else(|err| {
+                    eprintln!("failed to start render: {err}");
+                    std::process::exit(1);
+                });
+            // The recorder has its own camera, and writes sound to disk.
+            app.add_plugins(headless_plugins(&settings))
+                .add_plugins(CreatureSynthPlugin {
+                    sound: false,
+                    controls: false,
+                    camera: false,
+                    midi: false,
+                    ..plugin
+                });
+            add_render(&mut app, recorder);
+        }
+        None => {
+            app.add_plugins(DefaultPlugins)
+                .add_plugins(FpsOverlayPlugin {
+                    ..Default::default()
+                })
+                .add_plugins(plugin);
+        }
+    }
+    app.run();
 }
 
-fn setup_camera(mut commands: Commands) {
-    commands.spawn(Camera2d);
+/// Breed the plan's creatures for distance swum, printing each
+/// generation's scores, and save the last generation.
+fn evolve(plan: &CreaturesPlan, generations: usize, seed: Option<u64>) -> CreaturesPlan {
+    let seeds: Vec<Genome> = plan.creatures.iter().map(Genome::from_plan).collect();
+    let mut evolution = Evolution::new(EvolutionSettings { seed, ..default() }, &seeds);
+    for _ in 0..generations {
+        let scores = swim_trial(evolution.population(), evolution.settings());
+        let best = scores.iter().copied().fold(0.0, f32::max);
+        let mean = scores.iter().sum::<f32>() / scores.len().max(1) as f32;
+        println!(
+            "generation {}: best {best:.1}, mean {mean:.1}",
+            evolution.generation()
+        );
+        evolution.breed(&scores);
+    }
+    let evolved = evolution.plan();
+    if let Err(err) = evolved.save(EVOLVED_PLAN_PATH) {
+        eprintln!("failed to save {EVOLVED_PLAN_PATH}: {err}");
+    }
+    evolved
 }
@@ -0,0 +1,482 @@
+use bevy::prelude::*;
+
+use crate::transport::Transport;
+
+/// MIDI clock resolution: ticks per quarter note.
+pub const PPQN: u32 = 24;
+/// Song position pointer units (MIDI beats, i.e. sixteenths) in ticks.
+const TICKS_PER_SONG_POSITION: u64 = 6;
+/// With no tick for this long the clock counts as disconnected and the
+/// transport runs on its own again.
+const CLOCK_TIMEOUT_SECS: f64 = 0.5;
+/// Smoothing for the tick interval estimate; lower is steadier but slower
+/// to follow tempo changes.
+const INTERVAL_SMOOTHING: f64 = 0.1;
+
+/// The MIDI realtime messages the clock understands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockMessage {
+    /// 0xF8, sent 24 times per quarter note.
+    Tick,
+    /// 0xFA: play from the top.
+    Start,
+    /// 0xFB: play from the current song position.
+    Continue,
+    /// 0xFC
+    Stop,
+    /// 0xF2: jump to a position, in sixteenths.
+    SongPosition(u16),
+}
+
+impl ClockMessage {
+    /// Parse a raw MIDI message, ignoring anything that isn't clock related.
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        match *bytes {
+            [0xF8, ..] => Some(ClockMessage::Tick),
+            [0xFA, ..] => Some(ClockMessage::Start),
+            [0xFB, ..] => Some(ClockMessage::Continue),
+            [0xFC, ..] => Some(ClockMessage::Stop),
+            [0xF2, lsb, msb, ..] => Some(ClockMessage::SongPosition(
+                (lsb & 0x7F) as u16 | ((msb & 0x7F) as u16) << 7,
+            )),
+            _ => None,
+        }
+    }
+}
+
+/// A clock message and when it arrived, in seconds on the same timebase as
+/// `Time<Real>::elapsed`.
+#[derive(Message, Debug, Clone, Copy)]
+pub struct MidiClockInput {
+    pub message: ClockMessage,
+    pub timestamp: f64,
+}
+
+/// Follows an external MIDI clock: song position, running state and tempo.
+///
+/// Position is counted in ticks and interpolated between them using the
+/// smoothed tick interval, so beat-locked oscillators move continuously
+/// rather than in 24 ppqn steps.
+#[derive(Resource, Debug, Clone)]
+pub struct MidiClock {
+    /// Whether the transport follows the clock while it is present.
+    pub follow: bool,
+    running: bool,
+    /// Set by Start / Continue: the next tick lands on the current position
+    /// rather than advancing past it.
+    awaiting_first_tick: bool,
+    ticks: u64,
+    last_tick: Option<f64>,
+    /// Smoothed seconds per tick.
+    interval: Option<f64>,
+}
+
+impl Default for MidiClock {
+    fn default() -> Self {
+        Self {
+            follow: true,
+            running: false,
+            awaiting_first_tick: false,
+            ticks: 0,
+            last_tick: None,
+            interval: None,
+        }
+    }
+}
+
+impl MidiClock {
+    pub fn receive(&mut self, message: ClockMessage, timestamp: f64) {
+        match message {
+            ClockMessage::Tick => self.tick(timestamp),
+            ClockMessage::Start => {
+                self.ticks = 0;
+                self.running = true;
+                self.awaiting_first_tick = true;
+            }
+            ClockMessage::Continue => {
+                self.running = true;
+                self.awaiting_first_tick = true;
+            }
+            ClockMessage::Stop => self.running = false,
+            ClockMessage::SongPosition(sixteenths) => {
+                self.ticks = sixteenths as u64 * TICKS_PER_SONG_POSITION;
+            }
+        }
+    }
+
+    fn tick(&mut self, timestamp: f64) {
+        if let Some(last) = self.last_tick {
+            let interval = timestamp - last;
+            if interval > 0.0 && interval < CLOCK_TIMEOUT_SECS {
+                self.interval = Some(match self.interval {
+                    Some(smoothed) => smoothed + (interval - smoothed) * INTERVAL_SMOOTHING,
+                    None => interval,
+                });
+            }
+        }
+        self.last_tick = Some(timestamp);
+
+        // Clocks keep ticking while stopped, for tempo only.
+        if self.running {
+            if self.awaiting_first_tick {
+                self.awaiting_first_tick = false;
+            } else {
+                self.ticks += 1;
+            }
+        }
+    }
+
+    /// Whether a tick has arrived recently.
+    pub fn is_active(&self, now: f64) -> bool {
+        self.last_tick
+            .is_some_and(|last| now - last < CLOCK_TIMEOUT_SECS)
+    }
+
+    /// Estimated tempo (BPM), once two ticks have arrived.
+    pub fn bpm(&self) -> Option<f32> {
+        self.interval
+            .map(|interval| (60.0 / (interval * PPQN as f64)) as f32)
+    }
+
+    /// Position in quarter-note beats at `now`, interpolated from the last
+    /// tick but never past the next one.
+    pub fn position(&self, now: f64) -> f64 {
+        let mut ticks = self.ticks as f64;
+        if self.running
+            && !self.awaiting_first_tick
+            && let (Some(last), Some(interval)) = (self.last_tick, self.interval)
+        {
+            ticks += ((now - last) / interval).clamp(0.0, 1.0);
+        }
+        ticks / PPQN as f64
+    }
+
+    /// Drive the transport from the clock, or hand it back once the clock
+    /// goes quiet.
+    pub fn drive(&self, transport: &mut Transport, now: f64) {
+        let Some(bpm) = self.bpm().filter(|_| self.follow && self.is_active(now)) else {
+            transport.release();
+            return;
+        };
+        transport.follow(self.position(now), bpm, self.running);
+    }
+}
+
+/// Apply incoming clock messages and sync the transport to them.
+pub fn sync_transport_to_midi_clock(
+    time: Res<Time<Real>>,
+    mut messages: MessageReader<MidiClockInput>,
+    mut clock: ResMut<MidiClock>,
+    mut transport: ResMut<Transport>,
+) {
+    for input in messages.read() {
+        clock.receive(input.message, input.timestamp);
+    }
+    clock.drive(&mut transport, time.elapsed_secs_f64());
+}
+
+/// Live MIDI input via midir, enabled with the `midi` feature.
+///
+/// Connects to the first input port whose name contains
+/// `CREATURE_SYNTH_MIDI_PORT`, or the first port if that isn't set, and
+/// forwards clock messages and notes.
+#[cfg(feature = "midi")]
+pub mod input {
+    use bevy::platform::time::Instant;
+    use bevy::prelude::*;
+    use midir::{Ignore, MidiInput, MidiInputConnection};
+    use std::sync::{mpsc, Mutex};
+
+    use super::{ClockMessage, MidiClockInput};
+    use crate::voices::Note;
+
+    const PORT_ENV_VAR: &str = "CREATURE_SYNTH_MIDI_PORT";
+
+    /// A message from the midir thread.
+    enum MidiEvent {
+        /// Stamped on arrival.
+        Clock(ClockMessage, Instant),
+        Note(Note),
+    }
+
+    #[derive(Resource)]
+    pub struct MidiInputReceiver(Mutex<mpsc::Receiver<MidiEvent>>);
+
+    pub fn connect_midi_input(world: &mut World) {
+        let mut midi_in = match MidiInput::new("creature-synth") {
+            Ok(midi_in) => midi_in,
+            Err(err) => {
+                warn!("MIDI input unavailable: {err}");
+                return;
+            }
+        };
+        midi_in.ignore(Ignore::None);
+
+        let wanted = std::env::var(PORT_ENV_VAR).ok();
+        let port = midi_in.ports().into_iter().find(|port| {
+            let name = midi_in.port_name(port).unwrap_or_default();
+            wanted.as_ref().is_none_or(|wanted| name.contains(wanted))
+        });
+        let Some(port) = port else {
+            warn!("no MIDI input port found");
+            return;
+        };
+        let name = midi_in.port_name(&port).unwrap_or_default();
+
+        let (sender, receiver) = mpsc::channel();
+        let connection: MidiInputConnection<()> = match midi_in.connect(
+            &port,
+            "creature-synth-input",
+            move |_, bytes, _| {
+                if let Some(message) = ClockMessage::parse(bytes) {
+                    let _ = sender.send(MidiEvent::Clock(message, Instant::now()));
+                } else if let Some(note) = Note::parse_midi(bytes) {
+                    let _ = sender.send(MidiEvent::Note(note));
+                }
+            },
+            (),
+        ) {
+            Ok(connection) => connection,
+            Err(err) => {
+                warn!("failed to connect to MIDI input {name}: {err}");
+                return;
+            }
+        };
+        info!("following MIDI input from {name}");
+
+        // Dropping the connection closes it.
+        world.insert_non_send_resource(connection);
+        world.insert_resource(MidiInputReceiver(Mutex::new(receiver)));
+    }
+
+    pub fn receive_midi_input(
+        time: Res<Time<Real>>,
+        receiver: Option<Res<MidiInputReceiver>>,
+        mut clock: MessageWriter<MidiClockInput>,
+        mut notes: MessageWriter<Note>,
+    ) {
+        let Some(receiver) = receiver else {
+            return;
+        };
+        let Ok(receiver) = receiver.0.lock() else {
+            return;
+        };
+        let startup = time.startup();
+        for event in receiver.try_iter() {
+            match event {
+                MidiEvent::Clock(message, at) => {
+                    clock.write(MidiClockInput {
+                        message,
+                        timestamp: at.saturating_duration_since(startup).as_secs_f64(),
+                    });
+                }
+                MidiEvent::Note(note) => {
+                    notes.write(note);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::oscillator::{Oscillator, Wave};
+    use crate::transport::{BeatDivision, NoteValue};
+
+    const PHASE_TOLERANCE: f32 = 1e-3;
+
+    /// Seconds per tick at a tempo.
+    fn interval(bpm: f64) -> f64 {
+        60.0 / bpm / PPQN as f64
+    }
+
+    fn locked_oscillator(division: BeatDivision) -> Oscillator {
+        let mut osc = Oscillator::new(Wave::Sine, 1.0, 1.0);
+        osc.set_beat_division(Some(division));
+        osc
+    }
+
+    /// Feed Start followed by `ticks` ticks at a steady tempo, returning the
+    /// time of the last tick.
+    fn start_and_tick(clock: &mut MidiClock, bpm: f64, ticks: u64) -> f64 {
+        clock.receive(ClockMessage::Start, 0.0);
+        let mut t = 0.0;
+        for i in 0..ticks {
+            t = i as f64 * interval(bpm);
+            clock.receive(ClockMessage::Tick, t);
+        }
+        t
+    }
+
+    fn phase_distance(a: f32, b: f32) -> f32 {
+        let d = (a - b).rem_euclid(1.0);
+        d.min(1.0 - d)
+    }
+
+    #[test]
+    fn parses_realtime_messages() {
+        assert_eq!(ClockMessage::parse(&[0xF8]), Some(ClockMessage::Tick));
+        assert_eq!(ClockMessage::parse(&[0xFA]), Some(ClockMessage::Start));
+        assert_eq!(ClockMessage::parse(&[0xFB]), Some(ClockMessage::Continue));
+        assert_eq!(ClockMessage::parse(&[0xFC]), Some(ClockMessage::Stop));
+        assert_eq!(
+            ClockMessage::parse(&[0xF2, 0x10, 0x01]),
+            Some(ClockMessage::SongPosition(144))
+        );
+        assert_eq!(ClockMessage::parse(&[0x90, 60, 100]), None);
+        assert_eq!(ClockMessage::parse(&[]), None);
+    }
+
+    #[test]
+    fn estimates_tempo() {
+        let mut clock = MidiClock::default();
+        start_and_tick(&mut clock, 128.0, 48);
+        let bpm = clock.bpm().unwrap();
+        assert!((bpm - 128.0).abs() < 0.01, "bpm {bpm}");
+    }
+
+    #[test]
+    fn estimates_tempo_through_jitter() {
+        let mut clock = MidiClock::default();
+        clock.receive(ClockMessage::Start, 0.0);
+        for i in 0..(PPQN as u64 * 16) {
+            // Deterministic ±1 ms jitter.
+            let jitter = [0.001, -0.0005, 0.0, -0.001, 0.0005][i as usize % 5];
+            clock.receive(ClockMessage::Tick, i as f64 * interval(120.0) + jitter);
+        }
+        let bpm = clock.bpm().unwrap();
+        assert!((bpm - 120.0).abs() < 1.0, "bpm {bpm}");
+    }
+
+    #[test]
+    fn first_tick_after_start_is_the_downbeat() {
+        let mut clock = MidiClock::default();
+        let last = start_and_tick(&mut clock, 120.0, 1);
+        assert_eq!(clock.position(last), 0.0);
+
+        let last = start_and_tick(&mut clock, 120.0, PPQN as u64 * 4 + 1);
+        assert!((clock.position(last) - 4.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn stop_holds_and_continue_resumes() {
+        let mut clock = MidiClock::default();
+        let mut t = start_and_tick(&mut clock, 120.0, PPQN as u64 + 1);
+        clock.receive(ClockMessage::Stop, t);
+
+        // Ticks while stopped update tempo but not position.
+        for _ in 0..PPQN {
+            t += interval(120.0);
+            clock.receive(ClockMessage::Tick, t);
+        }
+        assert!((clock.position(t) - 1.0).abs() < 1e-9);
+
+        clock.receive(ClockMessage::Continue, t);
+        for _ in 0..=PPQN {
+            t += interval(120.0);
+            clock.receive(ClockMessage::Tick, t);
+        }
+        assert!((clock.position(t) - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn song_position_moves_the_clock() {
+        let mut clock = MidiClock::default();
+        // Sixteenth 10 is two and a half beats in.
+        clock.receive(ClockMessage::SongPosition(10), 0.0);
+        clock.receive(ClockMessage::Continue, 0.0);
+        clock.receive(ClockMessage::Tick, 0.0);
+        assert!((clock.position(0.0) - 2.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn interpolates_between_ticks_without_overshooting() {
+        let mut clock = MidiClock::default();
+        let last = start_and_tick(&mut clock, 120.0, PPQN as u64 + 1);
+        let half = last + interval(120.0) / 2.0;
+        let expected = 1.0 + 0.5 / PPQN as f64;
+        assert!((clock.position(half) - expected).abs() < 1e-6);
+
+        // A late tick holds at the next tick position.
+        let late = last + interval(120.0) * 3.0;
+        assert!((clock.position(late) - (1.0 + 1.0 / PPQN as f64)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn oscillators_follow_clock_phase() {
+        let bpm = 96.0;
+        let mut clock = MidiClock::default();
+        let mut transport = Transport::default();
+        let divisions = [
+            BeatDivision::new(NoteValue::Quarter),
+            BeatDivision::new(NoteValue::Eighth),
+            BeatDivision {
+                value: NoteValue::Half,
+                modifier: crate::transport::NoteModifier::Dotted,
+            },
+        ];
+        let mut oscillators = divisions.map(locked_oscillator);
+
+        clock.receive(ClockMessage::Start, 0.0);
+        for i in 0..(PPQN as u64 * 8) {
+            let t = i as f64 * interval(bpm);
+            clock.receive(ClockMessage::Tick, t);
+            // Sample halfway to the next tick, as a frame would.
+            let now = t + interval(bpm) / 2.0;
+            clock.drive(&mut transport, now);
+            for (osc, division) in oscillators.iter_mut().zip(divisions) {
+                osc.lock_to_beat(transport.position(), transport.bpm());
+                if i == 0 {
+                    continue;
+                }
+                let beats = (i as f64 + 0.5) / PPQN as f64;
+                let expected = (beats / division.beats() as f64).fract() as f32;
+                assert!(
+                    phase_distance(osc.phase(), expected) < PHASE_TOLERANCE,
+                    "tick {i} {division:?}: phase {} expected {expected}",
+                    osc.phase()
+                );
+            }
+        }
+        assert!((transport.bpm() - bpm as f32).abs() < 0.01);
+        assert!(transport.is_synced());
+    }
+
+    #[test]
+    fn start_resets_phase() {
+        let mut clock = MidiClock::default();
+        let mut transport = Transport::default();
+        let mut osc = locked_oscillator(BeatDivision::new(NoteValue::Quarter));
+        osc.shift_phase(0.25);
+
+        let t = start_and_tick(&mut clock, 120.0, 37);
+        clock.drive(&mut transport, t);
+        osc.lock_to_beat(transport.position(), transport.bpm());
+        assert!(phase_distance(osc.phase(), 0.75) < PHASE_TOLERANCE);
+
+        // Restart: back to the downbeat, keeping the oscillator's offset.
+        clock.receive(ClockMessage::Start, t);
+        let t = t + interval(120.0);
+        clock.receive(ClockMessage::Tick, t);
+        clock.drive(&mut transport, t);
+        osc.lock_to_beat(transport.position(), transport.bpm());
+        assert!(phase_distance(osc.phase(), 0.25) < PHASE_TOLERANCE);
+    }
+
+    #[test]
+    fn transport_is_released_when_clock_stops_ticking() {
+        let mut clock = MidiClock::default();
+        let mut transport = Transport::default();
+        let t = start_and_tick(&mut clock, 100.0, 25);
+        clock.drive(&mut transport, t);
+        assert!(transport.is_synced());
+
+        clock.drive(&mut transport, t + CLOCK_TIMEOUT_SECS * 2.0);
+        assert!(!transport.is_synced());
+
+        clock.follow = false;
+        clock.drive(&mut transport, t);
+        assert!(!transport.is_synced());
+    }
+}
@@ -1,6 +1,13 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::{f32::consts::TAU, time::Duration};
 
+use crate::controls::{ControlScheme, StepPrecision};
+use crate::creature::Creature;
+use crate::limb::Limb;
+use crate::selection::Selection;
+use crate::transport::BeatDivision;
+
 /// Advance all oscillators by Time, with a capped delta.
 pub fn oscillator_tick(time: Res<Time>, mut q: Query<&mut Oscillator>) {
     let dt = time.delta_secs().min(0.05);
@@ -9,31 +16,180 @@ pub fn oscillator_tick(time: Res<Time>, mut q: Query<&mut Oscillator>) {
     }
 }
 
-const FREQ_STEP: f32 = 0.05;
-const MIN_FREQ: f32 = 0.0;
-const MAX_FREQ: f32 = 2.0;
+/// Hard sync: restart this limb's oscillator whenever the master limb's
+/// oscillator completes a cycle.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HardSync {
+    pub master: Entity,
+}
 
-/// Simple user input: Up/Down arrow changes the target frequency of all
-/// oscillators. Smoothing is handled by the oscillator itself.
-pub fn oscillator_user_update(keys: Res<ButtonInput<KeyCode>>, mut q: Query<&mut Oscillator>) {
-    let mut delta = 0.0;
-    if keys.just_pressed(KeyCode::ArrowUp) {
-        delta += FREQ_STEP;
+/// Restart synced oscillators whose master wrapped this frame.
+pub fn hard_sync_oscillators(
+    syncs: Query<(Entity, &HardSync)>,
+    mut oscillators: Query<&mut Oscillator>,
+) {
+    for (slave, sync) in &syncs {
+        let Ok([mut slave, master]) = oscillators.get_many_mut([slave, sync.master]) else {
+            continue;
+        };
+        if master.wrapped() {
+            slave.sync_to(&master);
+        }
     }
-    if keys.just_pressed(KeyCode::ArrowDown) {
-        delta -= FREQ_STEP;
+}
+
+/// A gate opening or closing on a creature (all its limbs) or a single limb.
+#[derive(Message, Debug, Clone, Copy)]
+pub struct Gate {
+    pub target: Entity,
+    pub open: bool,
+}
+
+/// Restart oscillators set to retrigger when a gate opens on them.
+pub fn retrigger_on_gate(
+    mut gates: MessageReader<Gate>,
+    mut q: Query<(Entity, &ChildOf, &mut Oscillator), With<Limb>>,
+) {
+    for gate in gates.read() {
+        if !gate.open {
+            continue;
+        }
+        for (limb, child_of, mut osc) in &mut q {
+            if (limb == gate.target || child_of.parent() == gate.target) && osc.retrigger() {
+                osc.reset_phase();
+            }
+        }
     }
-    if delta == 0.0 {
+}
+
+/// Hold G to open a gate on the selection: the selected limb, the selected
+/// creature, or every creature.
+pub fn gate_with_keys(
+    keys: Res<ButtonInput<KeyCode>>,
+    selection: Res<Selection>,
+    creatures: Query<Entity, With<Creature>>,
+    mut gates: MessageWriter<Gate>,
+) {
+    let open = if keys.just_pressed(KeyCode::KeyG) {
+        true
+    } else if keys.just_released(KeyCode::KeyG) {
+        false
+    } else {
+        return;
+    };
+    let targets: Vec<Entity> = match (selection.limb, selection.creature) {
+        (Some(limb), _) => vec![limb],
+        (None, Some(creature)) => vec![creature],
+        (None, None) => creatures.iter().collect(),
+    };
+    gates.write_batch(targets.into_iter().map(|target| Gate { target, open }));
+}
+
+/// Keyboard controls for the selected oscillators:
+/// - Up/Down: target frequency
+/// - Left/Right: amplitude
+/// - PageUp/PageDown: glide (frequency transition time)
+/// - Comma/Period: phase
+/// - Slash: cycle wave shape
+/// - R: reset phase
+/// - B: lock to / release from the transport beat (Up/Down then step the
+///   note value)
+///
+/// Hold Shift for fine steps or Ctrl for coarse steps; ranges and step sizes
+/// come from the ControlScheme. Only limbs in the current selection are
+/// affected; with nothing selected, every limb is. Smoothing is handled by
+/// the oscillator itself.
+pub fn oscillator_user_update(
+    keys: Res<ButtonInput<KeyCode>>,
+    scheme: Res<ControlScheme>,
+    selection: Res<Selection>,
+    mut q: Query<(Entity, &ChildOf, &mut Oscillator), With<Limb>>,
+) {
+    let axis = |up: KeyCode, down: KeyCode| -> f32 {
+        let mut delta = 0.0;
+        if keys.just_pressed(up) {
+            delta += 1.0;
+        }
+        if keys.just_pressed(down) {
+            delta -= 1.0;
+        }
+        delta
+    };
+    let freq = axis(KeyCode::ArrowUp, KeyCode::ArrowDown);
+    let amp = axis(KeyCode::ArrowRight, KeyCode::ArrowLeft);
+    let glide = axis(KeyCode::PageUp, KeyCode::PageDown);
+    let phase = axis(KeyCode::Period, KeyCode::Comma);
+    let cycle_wave = keys.just_pressed(KeyCode::Slash);
+    let toggle_beat = keys.just_pressed(KeyCode::KeyB);
+    let reset_phase = keys.just_pressed(KeyCode::KeyR);
+
+    if freq == 0.0
+        && amp == 0.0
+        && glide == 0.0
+        && phase == 0.0
+        && !cycle_wave
+        && !toggle_beat
+        && !reset_phase
+    {
         return;
     }
-    for mut osc in &mut q {
-        let target = (osc.target_frequency() + delta).clamp(MIN_FREQ, MAX_FREQ);
-        osc.set_frequency(target);
+    let precision = StepPrecision::from_keys(&keys);
+
+    for (limb, child_of, mut osc) in &mut q {
+        if !selection.targets(limb, child_of.parent()) {
+            continue;
+        }
+        if toggle_beat {
+            let division = match osc.beat_division() {
+                Some(_) => None,
+                None => Some(BeatDivision::default()),
+            };
+            osc.set_beat_division(division);
+        }
+        if freq != 0.0 {
+            match osc.beat_division() {
+                // Beat-locked: step the note value instead of the frequency.
+                Some(mut division) => {
+                    let value = if freq > 0.0 {
+                        division.value.shorter()
+                    } else {
+                        division.value.longer()
+                    };
+                    division.value = value.unwrap_or(division.value);
+                    osc.set_beat_division(Some(division));
+                }
+                None => {
+                    let target = scheme
+                        .frequency
+                        .step(osc.target_frequency(), freq, precision);
+                    osc.set_frequency(target);
+                }
+            }
+        }
+        if amp != 0.0 {
+            let amplitude = scheme.amplitude.step(osc.amplitude(), amp, precision);
+            osc.set_amplitude(amplitude);
+        }
+        if glide != 0.0 {
+            let tau = scheme
+                .glide
+                .step(osc.transition_time().as_secs_f32(), glide, precision);
+            osc.set_transition_time(Duration::from_secs_f32(tau));
+        }
+        if reset_phase {
+            osc.reset_phase();
+        }
+        if phase != 0.0 {
+            osc.shift_phase(phase * scheme.phase.step_size(precision));
+        }
+        if cycle_wave {
+            let wave = osc.wave().next();
+            osc.set_wave(wave);
+        }
     }
 }
 
-#[derive(Debug, Clone, Copy, Default)]
-#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Wave {
     #[default]
     Flat,
@@ -42,11 +198,44 @@ pub enum Wave {
     Triangle,
 }
 
+impl Wave {
+    pub const ALL: [Wave; 4] = [Wave::Flat, Wave::Sine, Wave::Square, Wave::Triangle];
+
+    /// The next wave shape, wrapping around.
+    pub fn next(self) -> Self {
+        match self {
+            Wave::Flat => Wave::Sine,
+            Wave::Sine => Wave::Square,
+            Wave::Square => Wave::Triangle,
+            Wave::Triangle => Wave::Flat,
+        }
+    }
+
+    /// The wave at a phase (cycles, 0..1), in -1..1.
+    pub fn value(self, phase: f32) -> f32 {
+        match self {
+            Wave::Flat => 0.0,
+            Wave::Sine => (TAU * phase).sin(),
+            Wave::Square => {
+                if (TAU * phase).sin() >= 0.0 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Wave::Triangle => {
+                let p = (phase + 0.25).fract();
+                1.0 - 4.0 * (p - 0.5).abs()
+            }
+        }
+    }
+}
+
 /// Frequency smoother with a time constant (tau).
 /// - current: instantaneous frequency (Hz)
 /// - target: desired frequency (Hz)
 /// - tau: time constant; ~63% toward target in tau seconds
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Frequency {
     current: f32,
     target: f32,
@@ -80,10 +269,20 @@ impl Frequency {
         self.tau = tau.as_secs_f32().max(0.0);
     }
 
+    pub fn tau(&self) -> Duration {
+        Duration::from_secs_f32(self.tau)
+    }
+
     pub fn target(&self) -> f32 {
         self.target
     }
 
+    /// Set both current and target frequency, skipping the glide.
+    pub fn jump(&mut self, hz: f32) {
+        self.target = hz.max(0.0);
+        self.current = self.target;
+    }
+
     pub fn current(&self) -> f32 {
         self.current
     }
@@ -104,12 +303,56 @@ impl Frequency {
     }
 }
 
-#[derive(Component, Debug, Clone)]
+/// Scaling applied on top of an oscillator's own settings by modulation
+/// sources such as audio analysis. Not saved in plans.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Modulation {
+    pub amplitude: f32,
+    pub frequency: f32,
+}
+
+impl Default for Modulation {
+    fn default() -> Self {
+        Self {
+            amplitude: 1.0,
+            frequency: 1.0,
+        }
+    }
+}
+
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct Oscillator {
     wave: Wave,
     amplitude: f32,
     frequency: Frequency,
     phase: f32,
+    /// When set, frequency and phase follow the transport instead of `tick`.
+    #[serde(default)]
+    beat: Option<BeatDivision>,
+    /// Phase offset (cycles) applied on top of the transport when beat-locked.
+    #[serde(default)]
+    beat_phase: f32,
+    /// Restart from phase zero when a gate opens.
+    #[serde(default)]
+    retrigger: bool,
+    #[serde(skip)]
+    wrapped: bool,
+    /// Transport position (cycles of the beat division) at the last lock.
+    #[serde(skip)]
+    beat_cycles: Option<f64>,
+    #[serde(skip)]
+    modulation: Modulation,
+    /// Output level from a voice envelope (0..1). Not saved in plans.
+    #[serde(skip, default = "unity")]
+    gain: f32,
+    /// How fast the cycle runs relative to the frequency, e.g. quicker as
+    /// its creature hurries. Not saved in plans.
+    #[serde(skip, default = "unity")]
+    pace: f32,
+}
+
+fn unity() -> f32 {
+    1.0
 }
 
 impl Default for Oscillator {
@@ -119,6 +362,14 @@ impl Default for Oscillator {
             amplitude: 1.0,
             frequency: Frequency::default(),
             phase: 0.0,
+            beat: None,
+            beat_phase: 0.0,
+            retrigger: false,
+            wrapped: false,
+            beat_cycles: None,
+            modulation: Modulation::default(),
+            gain: unity(),
+            pace: unity(),
         }
     }
 }
@@ -130,9 +381,65 @@ impl Oscillator {
             amplitude,
             frequency: Frequency::new(frequency),
             phase: 0.0,
+            beat: None,
+            beat_phase: 0.0,
+            retrigger: false,
+            wrapped: false,
+            beat_cycles: None,
+            modulation: Modulation::default(),
+            gain: unity(),
+            pace: unity(),
         }
     }
 
+    pub fn beat_division(&self) -> Option<BeatDivision> {
+        self.beat
+    }
+
+    /// Lock to (or, with None, release from) a beat division of the transport.
+    pub fn set_beat_division(&mut self, division: Option<BeatDivision>) {
+        self.beat = division;
+        self.beat_cycles = None;
+    }
+
+    // Follow the transport: one cycle per beat division, at `beats` quarter
+    // notes into the song and the given tempo. A cycle completes when the
+    // transport carries the phase past the end of one; shifting the phase or
+    // moving the transport back doesn't count.
+    pub fn lock_to_beat(&mut self, beats: f64, bpm: f32) {
+        let Some(division) = self.beat else {
+            return;
+        };
+        let cycles = beats / division.beats() as f64;
+        let offset = self.beat_phase as f64;
+        self.wrapped = self
+            .beat_cycles
+            .is_some_and(|last| (cycles + offset).floor() > (last + offset).floor());
+        self.beat_cycles = Some(cycles);
+        self.phase = (cycles.fract() as f32 + self.beat_phase).rem_euclid(1.0);
+        self.frequency.jump(division.frequency(bpm));
+    }
+
+    pub fn wave(&self) -> Wave {
+        self.wave
+    }
+
+    pub fn set_wave(&mut self, wave: Wave) {
+        self.wave = wave;
+    }
+
+    pub fn amplitude(&self) -> f32 {
+        self.amplitude
+    }
+
+    pub fn set_amplitude(&mut self, amplitude: f32) {
+        self.amplitude = amplitude;
+    }
+
+    pub fn transition_time(&self) -> Duration {
+        self.frequency.tau()
+    }
+
     pub fn set_transition_time(&mut self, d: Duration) {
         self.frequency.set_tau(d);
     }
@@ -150,39 +457,201 @@ impl Oscillator {
         self.frequency.current()
     }
 
+    /// The frequency the cycle actually runs at, after modulation and pace.
+    pub fn effective_frequency(&self) -> f32 {
+        self.frequency.current() * self.modulation.frequency * self.pace
+    }
+
+    /// Current phase (cycles, 0..1).
+    pub fn phase(&self) -> f32 {
+        self.phase
+    }
+
+    // Set the phase directly. Beat-locked oscillators keep the new phase as
+    // their offset from the transport.
+    pub fn set_phase(&mut self, phase: f32) {
+        self.shift_phase(phase - self.phase);
+    }
+
+    // Restart the cycle from zero.
+    pub fn reset_phase(&mut self) {
+        self.set_phase(0.0);
+    }
+
+    /// Whether the last update completed a cycle.
+    pub fn wrapped(&self) -> bool {
+        self.wrapped
+    }
+
+    // Hard sync to a master that has just wrapped: restart, carrying over
+    // however far the master has already run into its new cycle.
+    pub fn sync_to(&mut self, master: &Oscillator) {
+        let master_hz = master.effective_frequency();
+        let elapsed = if master_hz > 0.0 {
+            master.phase / master_hz
+        } else {
+            0.0
+        };
+        self.set_phase((elapsed * self.effective_frequency()).fract());
+    }
+
+    pub fn set_modulation(&mut self, modulation: Modulation) {
+        self.modulation = modulation;
+    }
+
+    pub fn set_gain(&mut self, gain: f32) {
+        self.gain = gain;
+    }
+
+    pub fn pace(&self) -> f32 {
+        self.pace
+    }
+
+    pub fn set_pace(&mut self, pace: f32) {
+        self.pace = pace;
+    }
+
+    /// Whether an opening gate restarts the cycle.
+    pub fn retrigger(&self) -> bool {
+        self.retrigger
+    }
+
+    pub fn set_retrigger(&mut self, retrigger: bool) {
+        self.retrigger = retrigger;
+    }
+
+    // Offset the phase by a fraction of a cycle, wrapping into [0, 1).
+    pub fn shift_phase(&mut self, delta: f32) {
+        self.phase = (self.phase + delta).rem_euclid(1.0);
+        self.beat_phase = (self.beat_phase + delta).rem_euclid(1.0);
+    }
+
     // Advance the oscillator by dt using the average of f(t) and f(t+dt).
+    // Beat-locked oscillators are advanced by the transport instead.
     pub fn tick(&mut self, dt: f32) {
+        if self.beat.is_some() {
+            return;
+        }
+        self.wrapped = false;
         if dt <= 0.0 {
             return;
         }
-        let f0 = self.frequency.current();
-        let f1 = self.frequency.update(dt);
+        let f0 = self.effective_frequency();
+        self.frequency.update(dt);
+        let f1 = self.effective_frequency();
         let f_avg = 0.5 * (f0 + f1);
-        self.phase = (self.phase + f_avg * dt).fract();
+        let phase = self.phase + f_avg * dt;
+        self.wrapped = phase >= 1.0;
+        self.phase = phase.fract();
+    }
+
+    /// Output amplitude after modulation and envelope.
+    pub fn level(&self) -> f32 {
+        self.amplitude * self.modulation.amplitude * self.gain
     }
 
     // Sample the current waveform at the stored phase.
     pub fn sample(&self) -> f32 {
-        let a = self.amplitude;
-        match self.wave {
-            Wave::Flat => 0.0,
-            Wave::Sine => {
-                let phi = TAU * self.phase;
-                a * phi.sin()
-            }
-            Wave::Square => {
-                let phi = TAU * self.phase;
-                if phi.sin() >= 0.0 {
-                    a
-                } else {
-                    -a
-                }
-            }
-            Wave::Triangle => {
-                let p = (self.phase + 0.25).fract();
-                let tri = 1.0 - 4.0 * (p - 0.5).abs();
-                a * tri
-            }
-        }
+        self.level() * self.wave.value(self.phase)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::NoteValue;
+    use bevy::ecs::system::RunSystemOnce;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-5
+    }
+
+    fn beat_locked() -> Oscillator {
+        let mut osc = Oscillator::new(Wave::Sine, 1.0, 1.0);
+        osc.set_beat_division(Some(BeatDivision::new(NoteValue::Quarter)));
+        osc
+    }
+
+    #[test]
+    fn ticking_wraps_once_per_cycle() {
+        let mut osc = Oscillator::new(Wave::Sine, 1.0, 1.0);
+        osc.tick(0.6);
+        assert!(!osc.wrapped());
+        osc.tick(0.6);
+        assert!(osc.wrapped());
+        assert!(close(osc.phase(), 0.2));
+        osc.tick(0.1);
+        assert!(!osc.wrapped());
+    }
+
+    #[test]
+    fn beat_locked_oscillators_wrap_as_the_transport_crosses_a_cycle() {
+        let mut osc = beat_locked();
+        osc.lock_to_beat(0.9, 120.0);
+        assert!(!osc.wrapped());
+        osc.lock_to_beat(1.1, 120.0);
+        assert!(osc.wrapped());
+        assert!(close(osc.phase(), 0.1));
+
+        // Moving the phase back by hand is not a wrap.
+        osc.shift_phase(-0.3);
+        osc.lock_to_beat(1.15, 120.0);
+        assert!(!osc.wrapped());
+        assert!(close(osc.phase(), 0.85));
+        osc.set_phase(0.1);
+        osc.lock_to_beat(1.2, 120.0);
+        assert!(!osc.wrapped());
+        assert!(close(osc.phase(), 0.15));
+
+        // Nor is moving the transport back.
+        osc.lock_to_beat(0.5, 120.0);
+        assert!(!osc.wrapped());
+    }
+
+    #[test]
+    fn resetting_the_phase_restarts_the_cycle() {
+        let mut osc = Oscillator::new(Wave::Sine, 1.0, 1.0);
+        osc.tick(0.3);
+        osc.reset_phase();
+        assert_eq!(osc.phase(), 0.0);
+        assert!(!osc.wrapped());
+
+        // Beat-locked, the restart holds as an offset from the transport.
+        let mut osc = beat_locked();
+        osc.lock_to_beat(2.4, 120.0);
+        osc.reset_phase();
+        assert!(close(osc.phase(), 0.0));
+        osc.lock_to_beat(2.5, 120.0);
+        assert!(close(osc.phase(), 0.1));
+    }
+
+    #[test]
+    fn synced_oscillators_carry_over_the_masters_new_cycle() {
+        let mut master = Oscillator::new(Wave::Sine, 1.0, 2.0);
+        // 0.05 s into the master's new cycle.
+        master.tick(0.55);
+        assert!(master.wrapped());
+        let mut slave = Oscillator::new(Wave::Sine, 1.0, 1.0);
+        slave.tick(0.7);
+        slave.sync_to(&master);
+        assert!(close(slave.phase(), 0.05));
+    }
+
+    #[test]
+    fn hard_sync_restarts_slaves_when_their_master_wraps() {
+        let mut world = World::new();
+        let master = world.spawn(Oscillator::new(Wave::Sine, 1.0, 2.0)).id();
+        let mut slave_osc = Oscillator::new(Wave::Sine, 1.0, 1.0);
+        slave_osc.tick(0.7);
+        let slave = world.spawn((slave_osc, HardSync { master })).id();
+        let phase = |world: &World| world.get::<Oscillator>(slave).unwrap().phase();
+
+        world.get_mut::<Oscillator>(master).unwrap().tick(0.3);
+        world.run_system_once(hard_sync_oscillators).unwrap();
+        assert!(close(phase(&world), 0.7));
+
+        world.get_mut::<Oscillator>(master).unwrap().tick(0.25);
+        world.run_system_once(hard_sync_oscillators).unwrap();
+        assert!(close(phase(&world), 0.05));
     }
 }
@@ -0,0 +1,218 @@
+use bevy::prelude::*;
+
+use crate::voices::Note;
+
+/// Tracker-style layout: the bottom row (Z, S, X, D, C, ...) plays the
+/// current octave, the top row (Q, 2, W, 3, E, ...) the octave above.
+/// Semitones above C of the current octave.
+const KEYS: [(KeyCode, u8); 34] = [
+    (KeyCode::KeyZ, 0),
+    (KeyCode::KeyS, 1),
+    (KeyCode::KeyX, 2),
+    (KeyCode::KeyD, 3),
+    (KeyCode::KeyC, 4),
+    (KeyCode::KeyV, 5),
+    (KeyCode::KeyG, 6),
+    (KeyCode::KeyB, 7),
+    (KeyCode::KeyH, 8),
+    (KeyCode::KeyN, 9),
+    (KeyCode::KeyJ, 10),
+    (KeyCode::KeyM, 11),
+    (KeyCode::Comma, 12),
+    (KeyCode::KeyL, 13),
+    (KeyCode::Period, 14),
+    (KeyCode::Semicolon, 15),
+    (KeyCode::Slash, 16),
+    (KeyCode::KeyQ, 12),
+    (KeyCode::Digit2, 13),
+    (KeyCode::KeyW, 14),
+    (KeyCode::Digit3, 15),
+    (KeyCode::KeyE, 16),
+    (KeyCode::KeyR, 17),
+    (KeyCode::Digit5, 18),
+    (KeyCode::KeyT, 19),
+    (KeyCode::Digit6, 20),
+    (KeyCode::KeyY, 21),
+    (KeyCode::Digit7, 22),
+    (KeyCode::KeyU, 23),
+    (KeyCode::KeyI, 24),
+    (KeyCode::Digit9, 25),
+    (KeyCode::KeyO, 26),
+    (KeyCode::Digit0, 27),
+    (KeyCode::KeyP, 28),
+];
+
+pub const OCTAVES: std::ops::RangeInclusive<i8> = 0..=8;
+
+/// Computer-keyboard piano. While enabled, the letter and number keys play
+/// notes instead of their usual controls.
+#[derive(Resource, Debug, Clone)]
+pub struct Piano {
+    pub enabled: bool,
+    /// Octave of the bottom row; 4 puts Z on middle C.
+    pub octave: i8,
+    pub velocity: f32,
+    /// Keys held down and the notes they started, so notes still stop after
+    /// an octave shift.
+    held: Vec<(KeyCode, u8)>,
+}
+
+impl Default for Piano {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            octave: 4,
+            velocity: 0.8,
+            held: Vec::new(),
+        }
+    }
+}
+
+/// Run condition: keyboard controls that share keys with the piano.
+pub fn piano_disabled(piano: Res<Piano>) -> bool {
+    !piano.enabled
+}
+
+/// F3 toggles the piano.
+pub fn toggle_piano(keys: Res<ButtonInput<KeyCode>>, mut piano: ResMut<Piano>) {
+    if keys.just_pressed(KeyCode::F3) {
+        piano.enabled = !piano.enabled;
+    }
+}
+
+/// Play notes from the keyboard, and stop them when their keys come up or the
+/// piano is turned off:
+/// - Z..Slash, Q..P: notes over two octaves
+/// - Minus/Equal: octave down / up
+pub fn play_piano(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut piano: ResMut<Piano>,
+    mut notes: MessageWriter<Note>,
+) {
+    // Checking what's still held (rather than just released) also stops
+    // notes whose key came up while the piano wasn't listening.
+    let enabled = piano.enabled;
+    piano.held.retain(|(key, pitch)| {
+        let held = enabled && keys.pressed(*key);
+        if !held {
+            notes.write(Note::off(*pitch));
+        }
+        held
+    });
+    if !enabled {
+        return;
+    }
+
+    if keys.just_pressed(KeyCode::Minus) {
+        piano.octave = (piano.octave - 1).max(*OCTAVES.start());
+    }
+    if keys.just_pressed(KeyCode::Equal) {
+        piano.octave = (piano.octave + 1).min(*OCTAVES.end());
+    }
+    for (key, semitone) in KEYS {
+        if !keys.just_pressed(key) {
+            continue;
+        }
+        let pitch = 12 * (piano.octave as i32 + 1) + semitone as i32;
+        if !(0..=127).contains(&pitch) {
+            continue;
+        }
+        let pitch = pitch as u8;
+        piano.held.push((key, pitch));
+        notes.write(Note::on(pitch, piano.velocity));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::SystemId;
+
+    struct Keyboard {
+        world: World,
+        system: SystemId,
+    }
+
+    impl Keyboard {
+        fn new() -> Self {
+            let mut world = World::new();
+            world.insert_resource(Piano {
+                enabled: true,
+                ..default()
+            });
+            world.init_resource::<ButtonInput<KeyCode>>();
+            world.init_resource::<Messages<Note>>();
+            let system = world.register_system(play_piano);
+            Self { world, system }
+        }
+
+        /// Press and release keys for one frame, returning the notes played
+        /// as (pitch, on).
+        fn frame(&mut self, press: &[KeyCode], release: &[KeyCode]) -> Vec<(u8, bool)> {
+            {
+                let mut keys = self.world.resource_mut::<ButtonInput<KeyCode>>();
+                keys.clear();
+                for &key in press {
+                    keys.press(key);
+                }
+                for &key in release {
+                    keys.release(key);
+                }
+            }
+            self.world.run_system(self.system).unwrap();
+            self.world
+                .resource_mut::<Messages<Note>>()
+                .drain()
+                .map(|note| (note.pitch, note.is_on()))
+                .collect()
+        }
+
+        fn octave(&self) -> i8 {
+            self.world.resource::<Piano>().octave
+        }
+    }
+
+    #[test]
+    fn z_plays_middle_c_at_the_default_octave() {
+        let mut keyboard = Keyboard::new();
+        assert_eq!(keyboard.frame(&[KeyCode::KeyZ], &[]), [(60, true)]);
+        assert_eq!(keyboard.frame(&[KeyCode::KeyQ], &[]), [(72, true)]);
+        assert_eq!(keyboard.frame(&[KeyCode::KeyN], &[]), [(69, true)]);
+        assert_eq!(keyboard.frame(&[], &[KeyCode::KeyZ]), [(60, false)]);
+    }
+
+    #[test]
+    fn octave_shifts_stay_in_range() {
+        let mut keyboard = Keyboard::new();
+        keyboard.frame(&[KeyCode::Equal], &[KeyCode::Equal]);
+        assert_eq!(keyboard.frame(&[KeyCode::KeyZ], &[]), [(72, true)]);
+        for _ in 0..20 {
+            keyboard.frame(&[KeyCode::Minus], &[KeyCode::Minus]);
+        }
+        assert_eq!(keyboard.octave(), *OCTAVES.start());
+        for _ in 0..20 {
+            keyboard.frame(&[KeyCode::Equal], &[KeyCode::Equal]);
+        }
+        assert_eq!(keyboard.octave(), *OCTAVES.end());
+    }
+
+    #[test]
+    fn keys_release_the_pitch_they_played() {
+        let mut keyboard = Keyboard::new();
+        keyboard.frame(&[KeyCode::KeyZ], &[]);
+        // Shift up an octave with Z still down.
+        assert!(keyboard.frame(&[KeyCode::Equal], &[KeyCode::Equal]).is_empty());
+        assert_eq!(keyboard.frame(&[], &[KeyCode::KeyZ]), [(60, false)]);
+        assert_eq!(keyboard.frame(&[KeyCode::KeyZ], &[]), [(72, true)]);
+    }
+
+    #[test]
+    fn turning_the_piano_off_releases_held_notes() {
+        let mut keyboard = Keyboard::new();
+        keyboard.frame(&[KeyCode::KeyZ, KeyCode::KeyQ], &[]);
+        keyboard.world.resource_mut::<Piano>().enabled = false;
+        let mut released = keyboard.frame(&[], &[]);
+        released.sort();
+        assert_eq!(released, [(60, false), (72, false)]);
+    }
+}
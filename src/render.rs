@@ -0,0 +1,362 @@
+use bevy::app::{PluginGroupBuilder, ScheduleRunnerPlugin};
+use bevy::camera::RenderTarget;
+use bevy::prelude::*;
+use bevy::render::gpu_readback::{Readback, ReadbackComplete};
+use bevy::render::render_resource::{TextureFormat, TextureUsages};
+use bevy::render::renderer::RenderDevice;
+use bevy::render::settings::{RenderCreation, WgpuSettings};
+use bevy::render::RenderPlugin;
+use bevy::time::TimeUpdateStrategy;
+use bevy::window::ExitCondition;
+use bevy::winit::WinitPlugin;
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::PathBuf;
+use std::{fmt, time::Duration};
+
+use crate::audio_analysis::AudioClip;
+use crate::sound::{update_mixer, MasterBus, Mixer, SAMPLE_RATE};
+
+/// Slowest frame rate to render at: each frame is one update, and
+/// oscillator_tick caps updates at 50 ms.
+pub const MIN_RENDER_FPS: u32 = 20;
+/// Steps to wait for the last frames to come back from the GPU.
+const MAX_READBACK_LAG: u32 = 120;
+
+/// What to render offline and where.
+#[derive(Debug, Clone)]
+pub struct RenderSettings {
+    pub dir: PathBuf,
+    pub seconds: f32,
+    pub fps: u32,
+    pub size: UVec2,
+    /// Write frames as well as audio. Without video no GPU is needed.
+    pub video: bool,
+}
+
+impl RenderSettings {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            seconds: 10.0,
+            fps: 30,
+            size: UVec2::new(1280, 720),
+            video: true,
+        }
+    }
+
+    pub fn frames(&self) -> u32 {
+        (self.seconds * self.fps as f32).round() as u32
+    }
+
+    /// Simulated time per frame.
+    pub fn step(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.fps as f64)
+    }
+}
+
+/// Errors from writing a render.
+#[derive(Debug)]
+pub enum RenderError {
+    Io(io::Error),
+    Wav(hound::Error),
+    Image(image::ImageError),
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenderError::Io(err) => write!(f, "render I/O error: {err}"),
+            RenderError::Wav(err) => write!(f, "render wav error: {err}"),
+            RenderError::Image(err) => write!(f, "render image error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for RenderError {}
+
+impl From<io::Error> for RenderError {
+    fn from(err: io::Error) -> Self {
+        RenderError::Io(err)
+    }
+}
+
+impl From<hound::Error> for RenderError {
+    fn from(err: hound::Error) -> Self {
+        RenderError::Wav(err)
+    }
+}
+
+impl From<image::ImageError> for RenderError {
+    fn from(err: image::ImageError) -> Self {
+        RenderError::Image(err)
+    }
+}
+
+/// DefaultPlugins without a window, looping as fast as frames render and
+/// exiting only when the render is done. Pipelines compile up front so the
+/// first frames aren't blank.
+pub fn headless_plugins(settings: &RenderSettings) -> PluginGroupBuilder {
+    let wgpu = if settings.video {
+        WgpuSettings::default()
+    } else {
+        WgpuSettings {
+            backends: None,
+            ..default()
+        }
+    };
+    DefaultPlugins
+        .set(WindowPlugin {
+            primary_window: None,
+            exit_condition: ExitCondition::DontExit,
+            close_when_requested: false,
+            ..default()
+        })
+        .set(RenderPlugin {
+            render_creation: RenderCreation::Automatic(wgpu),
+            synchronous_pipeline_compilation: true,
+            ..default()
+        })
+        .disable::<WinitPlugin>()
+        .add(ScheduleRunnerPlugin::run_loop(Duration::ZERO))
+}
+
+/// Writes the render as it runs: the mix to `audio.wav` and frames to
+/// `frame_00000.png` and on.
+#[derive(Resource)]
+pub struct Recorder {
+    settings: RenderSettings,
+    wav: Option<hound::WavWriter<BufWriter<File>>>,
+    mixer: Mixer,
+    /// Played under the creatures, as with `--audio`.
+    clip: Option<AudioClip>,
+    buffer: Vec<f32>,
+    /// Steps run, and those recorded as audio.
+    steps: u32,
+    audio_frames: u32,
+    /// Audio sample frames (left and right) written.
+    samples: u64,
+    video_frames: u32,
+    error: Option<RenderError>,
+}
+
+impl Recorder {
+    pub fn new(settings: RenderSettings, clip: Option<AudioClip>) -> Result<Self, RenderError> {
+        fs::create_dir_all(&settings.dir)?;
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: SAMPLE_RATE,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        let wav = hound::WavWriter::create(settings.dir.join("audio.wav"), spec)?;
+        Ok(Self {
+            settings,
+            wav: Some(wav),
+            mixer: Mixer::new(SAMPLE_RATE),
+            clip,
+            buffer: Vec::new(),
+            steps: 0,
+            audio_frames: 0,
+            samples: 0,
+            video_frames: 0,
+            error: None,
+        })
+    }
+
+    fn record_audio(&mut self, bus: &MasterBus) -> Result<(), RenderError> {
+        let fps = self.settings.fps as u64;
+        self.audio_frames += 1;
+        let target = self.audio_frames as u64 * SAMPLE_RATE as u64 / fps;
+        let count = (target - self.samples) as usize;
+        self.buffer.resize(count * 2, 0.0);
+        self.mixer.render(&bus.input(), &mut self.buffer);
+
+        let Some(wav) = &mut self.wav else {
+            return Ok(());
+        };
+        for (i, frame) in self.buffer.chunks_exact(2).enumerate() {
+            let under = self.clip.as_ref().map_or(0.0, |clip| {
+                let secs = (self.samples + i as u64) as f64 / SAMPLE_RATE as f64;
+                // Looped, as when played live.
+                let index = (secs * clip.sample_rate() as f64) as usize;
+                let samples = clip.samples();
+                samples
+                    .get(index % samples.len().max(1))
+                    .copied()
+                    .unwrap_or(0.0)
+            });
+            for sample in frame {
+                wav.write_sample((sample + under).clamp(-1.0, 1.0))?;
+            }
+        }
+        self.samples = target;
+        Ok(())
+    }
+
+    fn save_frame(&mut self, data: &[u8]) -> Result<(), RenderError> {
+        let UVec2 {
+            x: width,
+            y: height,
+        } = self.settings.size;
+        // Rows come back padded to the GPU's copy alignment.
+        let row = width as usize * 4;
+        let padded = RenderDevice::align_copy_bytes_per_row(row);
+        let pixels: Vec<u8> = data
+            .chunks(padded)
+            .take(height as usize)
+            .flat_map(|line| &line[..row])
+            .copied()
+            .collect();
+        let path = self
+            .settings
+            .dir
+            .join(format!("frame_{:05}.png", self.video_frames));
+        image::save_buffer(
+            path,
+            &pixels,
+            width,
+            height,
+            image::ExtendedColorType::Rgba8,
+        )?;
+        self.video_frames += 1;
+        Ok(())
+    }
+
+    fn is_done(&self) -> bool {
+        let frames = self.settings.frames();
+        self.audio_frames >= frames && (!self.settings.video || self.video_frames >= frames)
+    }
+}
+
+/// Set up a render: fixed-step time, and the recorder.
+pub fn add_render(app: &mut App, recorder: Recorder) {
+    let step = recorder.settings.step();
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(step))
+        .insert_resource(recorder)
+        .add_systems(Startup, setup_render_camera)
+        .add_systems(
+            Update,
+            (record_step, finish_render).chain().after(update_mixer),
+        );
+}
+
+/// A camera drawing into an image, read back every frame for video. Without
+/// video nothing is drawn, but the image still sizes the view that sound is
+/// panned across.
+fn setup_render_camera(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    recorder: Res<Recorder>,
+) {
+    let size = recorder.settings.size;
+    let mut image = Image::new_target_texture(size.x, size.y, TextureFormat::Rgba8UnormSrgb);
+    image.texture_descriptor.usage |= TextureUsages::COPY_SRC;
+    let image = images.add(image);
+
+    commands.spawn((
+        Camera2d,
+        Camera {
+            target: RenderTarget::Image(image.clone().into()),
+            ..default()
+        },
+    ));
+    if !recorder.settings.video {
+        return;
+    }
+    commands.spawn(Readback::texture(image)).observe(
+        |readback: On<ReadbackComplete>, mut recorder: ResMut<Recorder>| {
+            if recorder.error.is_some() || recorder.video_frames >= recorder.settings.frames() {
+                return;
+            }
+            if let Err(err) = recorder.save_frame(&readback.data) {
+                recorder.error = Some(err);
+            }
+        },
+    );
+}
+
+/// Record this step's audio, until the render's length is reached.
+fn record_step(bus: Res<MasterBus>, mut recorder: ResMut<Recorder>) {
+    recorder.steps += 1;
+    if recorder.error.is_some() || recorder.audio_frames >= recorder.settings.frames() {
+        return;
+    }
+    if let Err(err) = recorder.record_audio(&bus) {
+        recorder.error = Some(err);
+    }
+}
+
+/// Finish the WAV file and exit once everything is written, or on error.
+fn finish_render(mut recorder: ResMut<Recorder>, mut exit: MessageWriter<AppExit>) {
+    let frames = recorder.settings.frames();
+    if recorder.error.is_none() && !recorder.is_done() {
+        if recorder.steps < frames + MAX_READBACK_LAG {
+            return;
+        }
+        error!(
+            "only {} of {frames} frames came back from the GPU",
+            recorder.video_frames
+        );
+        exit.write(AppExit::error());
+        return;
+    }
+    if let Some(wav) = recorder.wav.take()
+        && let Err(err) = wav.finalize()
+        && recorder.error.is_none()
+    {
+        recorder.error = Some(err.into());
+    }
+    match &recorder.error {
+        Some(err) => {
+            error!("render failed: {err}");
+            exit.write(AppExit::error());
+        }
+        None => {
+            info!(
+                "rendered {frames} frames to {}",
+                recorder.settings.dir.display()
+            );
+            exit.write(AppExit::Success);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn audio_only_renders_write_sound_for_every_frame() {
+        let dir = std::env::temp_dir().join(format!(
+            "creature-synth-render-{}",
+            std::process::id()
+        ));
+        let settings = RenderSettings {
+            seconds: 0.5,
+            fps: 24,
+            video: false,
+            ..RenderSettings::new(dir.clone())
+        };
+        let frames = settings.frames();
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .init_resource::<MasterBus>()
+            .insert_resource(Recorder::new(settings, None).unwrap())
+            .add_systems(Update, (record_step, finish_render).chain());
+        for _ in 0..frames {
+            assert_eq!(app.should_exit(), None);
+            app.update();
+        }
+        assert_eq!(app.should_exit(), Some(AppExit::Success));
+
+        let wav = hound::WavReader::open(dir.join("audio.wav")).unwrap();
+        let (spec, samples) = (wav.spec(), wav.len());
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(spec.channels, 2);
+        assert_eq!(spec.sample_rate, SAMPLE_RATE);
+        // Half a second, though a frame isn't a whole number of samples.
+        assert_eq!(samples, 2 * frames * SAMPLE_RATE / 24);
+        assert_eq!(samples, SAMPLE_RATE);
+    }
+}
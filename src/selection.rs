@@ -0,0 +1,417 @@
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+
+use crate::creature::{Creature, SpawnOrder, BODY_RADIUS};
+use crate::editor::EditorPreview;
+use crate::limb::{Limb, LimbSegmentBody, LimbSegments};
+
+/// How close (in world units) a click must land to a segment to pick its limb.
+const SEGMENT_PICK_RADIUS: f32 = 14.0;
+
+const OUTLINE_COLOR: Color = Color::srgb(1.0, 0.85, 0.2);
+const PARTNER_COLOR: Color = Color::srgb(1.0, 0.45, 0.7);
+
+/// The creature (and optionally one of its limbs) that keyboard controls act on.
+#[derive(Resource, Debug, Default, Clone, Copy)]
+pub struct Selection {
+    pub creature: Option<Entity>,
+    pub limb: Option<Entity>,
+    /// A second creature, to breed with the selected one.
+    pub partner: Option<Entity>,
+}
+
+impl Selection {
+    /// Whether a limb (with the given parent creature) is targeted by this
+    /// selection. An empty selection targets every limb.
+    pub fn targets(&self, limb: Entity, creature: Entity) -> bool {
+        match (self.creature, self.limb) {
+            (_, Some(selected)) => selected == limb,
+            (Some(selected), None) => selected == creature,
+            (None, None) => true,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.creature = None;
+        self.limb = None;
+        self.partner = None;
+    }
+
+    /// Forget a creature that's going away.
+    pub fn forget(&mut self, creature: Entity) {
+        if self.creature == Some(creature) {
+            self.creature = None;
+            self.limb = None;
+        }
+        if self.partner == Some(creature) {
+            self.partner = None;
+        }
+    }
+}
+
+/// Keyboard selection:
+/// - Tab: next creature
+/// - Backquote: next limb of the selected creature (then back to the whole creature)
+/// - Escape: clear the selection
+pub fn select_with_keys(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut selection: ResMut<Selection>,
+    creatures: Query<(Entity, &Children, Option<&SpawnOrder>), Selectable>,
+    limbs: Query<(), With<Limb>>,
+) {
+    if keys.just_pressed(KeyCode::Escape) {
+        selection.clear();
+        return;
+    }
+
+    if keys.just_pressed(KeyCode::Tab) {
+        // Spawn order, as in the inspector.
+        let mut ordered: Vec<(Option<SpawnOrder>, Entity)> = creatures
+            .iter()
+            .map(|(e, _, order)| (order.copied(), e))
+            .collect();
+        ordered.sort();
+        let ordered: Vec<Entity> = ordered.into_iter().map(|(_, e)| e).collect();
+        selection.creature = next_after(&ordered, selection.creature);
+        selection.limb = None;
+    }
+
+    if keys.just_pressed(KeyCode::Backquote) {
+        let Some((_, children, _)) = selection.creature.and_then(|c| creatures.get(c).ok()) else {
+            return;
+        };
+        let creature_limbs: Vec<Entity> = children
+            .iter()
+            .filter(|child| limbs.contains(*child))
+            .collect();
+        selection.limb = match selection.limb {
+            Some(limb) => creature_limbs
+                .iter()
+                .position(|l| *l == limb)
+                .and_then(|i| creature_limbs.get(i + 1).copied()),
+            None => creature_limbs.first().copied(),
+        };
+    }
+}
+
+/// Creatures that can be selected or bred: anything but the editor's preview.
+type Selectable = (With<Creature>, Without<EditorPreview>);
+type CreatureBodies<'w, 's> = Query<'w, 's, (Entity, &'static GlobalTransform), Selectable>;
+type SegmentBodies<'w, 's> =
+    Query<'w, 's, (Entity, &'static GlobalTransform), With<LimbSegmentBody>>;
+
+/// Where the cursor is in the world, if over the window.
+fn cursor_in_world(window: &Window, camera: &Camera, transform: &GlobalTransform) -> Option<Vec2> {
+    window
+        .cursor_position()
+        .and_then(|p| camera.viewport_to_world_2d(transform, p).ok())
+}
+
+/// The creature whose body is nearest a point, within the body's radius.
+fn body_at(creatures: &CreatureBodies, point: Vec2) -> Option<Entity> {
+    creatures
+        .iter()
+        .map(|(e, t)| (e, t.translation().truncate().distance(point)))
+        .filter(|(_, d)| *d <= BODY_RADIUS)
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(e, _)| e)
+}
+
+/// Whether Shift is held, to pick a partner rather than select.
+pub fn shift_held(keys: Res<ButtonInput<KeyCode>>) -> bool {
+    keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight])
+}
+
+/// Mouse selection, run on left click: clicking near a limb segment selects
+/// that limb, clicking a creature body selects the whole creature, clicking
+/// empty space clears.
+pub fn select_with_click(
+    window: Single<&Window, With<PrimaryWindow>>,
+    camera: Single<(&Camera, &GlobalTransform)>,
+    mut selection: ResMut<Selection>,
+    creatures: CreatureBodies,
+    segment_bodies: SegmentBodies,
+    parents: Query<&ChildOf>,
+    limbs: Query<(), With<Limb>>,
+) {
+    let (camera, camera_transform) = *camera;
+    let Some(cursor) = cursor_in_world(&window, camera, camera_transform) else {
+        return;
+    };
+    select_at(
+        &mut selection,
+        cursor,
+        &creatures,
+        &segment_bodies,
+        &parents,
+        &limbs,
+    );
+}
+
+/// Select what's at a point in the world, as a click there does.
+fn select_at(
+    selection: &mut Selection,
+    cursor: Vec2,
+    creatures: &CreatureBodies,
+    segment_bodies: &SegmentBodies,
+    parents: &Query<&ChildOf>,
+    limbs: &Query<(), With<Limb>>,
+) {
+    // Creature bodies sit on top of the limb roots, so check them first.
+    if let Some(creature) = body_at(creatures, cursor) {
+        selection.creature = Some(creature);
+        selection.limb = None;
+        if selection.partner == Some(creature) {
+            selection.partner = None;
+        }
+        return;
+    }
+
+    let segment_hit = segment_bodies
+        .iter()
+        .map(|(e, t)| (e, t.translation().truncate().distance(cursor)))
+        .filter(|(_, d)| *d <= SEGMENT_PICK_RADIUS)
+        .min_by(|a, b| a.1.total_cmp(&b.1));
+    let Some((segment_body, _)) = segment_hit else {
+        selection.clear();
+        return;
+    };
+
+    let Some(limb) = parents
+        .iter_ancestors(segment_body)
+        .find(|e| limbs.contains(*e))
+    else {
+        return;
+    };
+    selection.creature = parents.get(limb).ok().map(|c| c.parent());
+    selection.limb = Some(limb);
+}
+
+/// Shift-click: pick the creature under the cursor as the selected one's
+/// partner, or drop the partner when clicking away from any creature.
+pub fn pick_partner_with_click(
+    window: Single<&Window, With<PrimaryWindow>>,
+    camera: Single<(&Camera, &GlobalTransform)>,
+    mut selection: ResMut<Selection>,
+    creatures: CreatureBodies,
+) {
+    let (camera, camera_transform) = *camera;
+    let Some(cursor) = cursor_in_world(&window, camera, camera_transform) else {
+        return;
+    };
+    selection.partner =
+        body_at(&creatures, cursor).filter(|creature| selection.creature != Some(*creature));
+}
+
+/// Outline the selected creature's body and its partner's, and trace the
+/// selected limb.
+pub fn draw_selection_outline(
+    mut gizmos: Gizmos,
+    mut selection: ResMut<Selection>,
+    creatures: Query<&GlobalTransform, With<Creature>>,
+    limbs: Query<&LimbSegments>,
+    children: Query<&Children>,
+    segment_bodies: Query<&GlobalTransform, With<LimbSegmentBody>>,
+) {
+    if let Some(partner) = selection.partner {
+        if let Ok(transform) = creatures.get(partner) {
+            gizmos.circle_2d(
+                Isometry2d::from_translation(transform.translation().truncate()),
+                BODY_RADIUS + 6.0,
+                PARTNER_COLOR,
+            );
+        } else {
+            selection.partner = None;
+        }
+    }
+
+    let Some(creature) = selection.creature else {
+        return;
+    };
+    let Ok(creature_transform) = creatures.get(creature) else {
+        // The selected creature no longer exists.
+        selection.forget(creature);
+        return;
+    };
+    let center = creature_transform.translation().truncate();
+    gizmos.circle_2d(
+        Isometry2d::from_translation(center),
+        BODY_RADIUS + 6.0,
+        OUTLINE_COLOR,
+    );
+
+    let Some(segments) = selection.limb.and_then(|limb| limbs.get(limb).ok()) else {
+        return;
+    };
+    // Each segment's body, from the creature outwards.
+    let points = std::iter::once(center).chain(
+        segments
+            .iter()
+            .filter_map(|segment| children.get(segment).ok())
+            .filter_map(|children| children.iter().find_map(|e| segment_bodies.get(e).ok()))
+            .map(|t| t.translation().truncate()),
+    );
+    gizmos.linestrip_2d(points, OUTLINE_COLOR);
+}
+
+fn next_after(ordered: &[Entity], current: Option<Entity>) -> Option<Entity> {
+    let next = current
+        .and_then(|c| ordered.iter().position(|e| *e == c))
+        .map_or(0, |i| i + 1);
+    ordered.get(next % ordered.len().max(1)).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::creature::{example_creatures_plan, CreatureCommands};
+    use crate::lifecycle::Lifecycle;
+    use crate::testing::Harness;
+    use bevy::ecs::system::RunSystemOnce;
+
+    /// Press a key for one run of select_with_keys.
+    fn press(harness: &mut Harness, key: KeyCode) -> Selection {
+        let world = harness.app.world_mut();
+        let mut keys = ButtonInput::<KeyCode>::default();
+        keys.press(key);
+        world.insert_resource(keys);
+        world.run_system_once(select_with_keys).unwrap();
+        *world.resource::<Selection>()
+    }
+
+    fn click(harness: &mut Harness, point: Vec2) -> Selection {
+        let world = harness.app.world_mut();
+        world
+            .run_system_once(
+                move |mut selection: ResMut<Selection>,
+                      creatures: CreatureBodies,
+                      segment_bodies: SegmentBodies,
+                      parents: Query<&ChildOf>,
+                      limbs: Query<(), With<Limb>>| {
+                    select_at(
+                        &mut selection,
+                        point,
+                        &creatures,
+                        &segment_bodies,
+                        &parents,
+                        &limbs,
+                    );
+                },
+            )
+            .unwrap();
+        *world.resource::<Selection>()
+    }
+
+    fn harness() -> Harness {
+        let mut harness = Harness::new(example_creatures_plan());
+        harness.app.init_resource::<Selection>();
+        // Full grown, so limbs reach well clear of the bodies.
+        harness.advance(Lifecycle::default().grow_secs as f64 + 0.1);
+        harness
+    }
+
+    #[test]
+    fn tab_cycles_creatures_in_spawn_order() {
+        let mut harness = harness();
+        let creatures = harness.creatures();
+        for &creature in creatures.iter().chain(&creatures[..1]) {
+            assert_eq!(press(&mut harness, KeyCode::Tab).creature, Some(creature));
+        }
+    }
+
+    #[test]
+    fn creatures_born_later_come_later_in_the_cycle() {
+        let mut harness = harness();
+        let first = harness.creatures()[0];
+        let plan = example_creatures_plan().creatures.remove(0);
+        harness
+            .app
+            .world_mut()
+            .run_system_once(move |mut commands: Commands| commands.despawn_creature(first))
+            .unwrap();
+        harness.advance(Lifecycle::default().death_secs as f64 + 0.1);
+        // The newborn may well take the dead creature's entity index.
+        let born = harness
+            .app
+            .world_mut()
+            .run_system_once(move |mut commands: Commands| {
+                commands.spawn_creature(plan.clone()).creature
+            })
+            .unwrap();
+
+        let creatures = harness.creatures();
+        assert_eq!(creatures.last(), Some(&born));
+        let mut cycled = Vec::new();
+        for _ in 0..creatures.len() {
+            cycled.extend(press(&mut harness, KeyCode::Tab).creature);
+        }
+        assert_eq!(cycled, creatures);
+    }
+
+    #[test]
+    fn backquote_cycles_limbs_then_the_whole_creature() {
+        let mut harness = harness();
+        let creature = harness.creatures()[0];
+        let limbs = harness.limbs(creature);
+        assert_eq!(press(&mut harness, KeyCode::Backquote).limb, None);
+
+        press(&mut harness, KeyCode::Tab);
+        for &limb in &limbs {
+            let selection = press(&mut harness, KeyCode::Backquote);
+            assert_eq!(selection.limb, Some(limb));
+            assert_eq!(selection.creature, Some(creature));
+        }
+        assert_eq!(press(&mut harness, KeyCode::Backquote).limb, None);
+    }
+
+    #[test]
+    fn escape_clears_the_selection() {
+        let mut harness = harness();
+        press(&mut harness, KeyCode::Tab);
+        press(&mut harness, KeyCode::Backquote);
+        let selection = press(&mut harness, KeyCode::Escape);
+        assert_eq!(selection.creature, None);
+        assert_eq!(selection.limb, None);
+    }
+
+    #[test]
+    fn clicks_select_bodies_and_limbs() {
+        let mut harness = harness();
+        let creature = harness.creatures()[1];
+        let limb = harness.limbs(creature)[2];
+        let world = harness.world();
+        let place = |entity: Entity| {
+            world
+                .get::<GlobalTransform>(entity)
+                .unwrap()
+                .translation()
+                .truncate()
+        };
+        let body = place(creature);
+        // The tip of the limb, well clear of the body.
+        let tip = harness
+            .segments(limb)
+            .last()
+            .and_then(|&segment| {
+                world
+                    .get::<Children>(segment)
+                    .unwrap()
+                    .iter()
+                    .find(|child| world.get::<LimbSegmentBody>(*child).is_some())
+            })
+            .map(place)
+            .unwrap();
+
+        let selection = click(&mut harness, body);
+        assert_eq!(selection.creature, Some(creature));
+        assert_eq!(selection.limb, None);
+
+        let selection = click(&mut harness, tip);
+        assert_eq!(selection.creature, Some(creature));
+        assert_eq!(selection.limb, Some(limb));
+
+        let selection = click(&mut harness, Vec2::splat(10_000.0));
+        assert_eq!(selection.creature, None);
+        assert_eq!(selection.limb, None);
+    }
+}
@@ -0,0 +1,269 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::limb::Limb;
+use crate::oscillator::{Gate, Oscillator, Wave};
+use crate::transport::{BeatDivision, NoteValue, Transport};
+
+/// One sequencer step. Unset values leave the limbs as they are.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Step {
+    #[serde(default)]
+    pub gate: bool,
+    #[serde(default)]
+    pub frequency: Option<f32>,
+    #[serde(default)]
+    pub amplitude: Option<f32>,
+    #[serde(default)]
+    pub wave: Option<Wave>,
+}
+
+/// A step pattern played on every limb of a creature, one step per `rate`
+/// note, following the transport.
+#[derive(Component, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[require(SequencerPlayhead)]
+pub struct Sequencer {
+    pub steps: Vec<Step>,
+    #[serde(default = "default_rate")]
+    pub rate: BeatDivision,
+    /// How much of a gated step the gate stays open for (0..1).
+    #[serde(default = "default_gate_length")]
+    pub gate_length: f32,
+}
+
+fn default_rate() -> BeatDivision {
+    BeatDivision::new(NoteValue::Sixteenth)
+}
+
+fn default_gate_length() -> f32 {
+    0.5
+}
+
+impl Default for Sequencer {
+    /// Eight steps with the gate on every other one.
+    fn default() -> Self {
+        Self {
+            steps: (0..8)
+                .map(|i| Step {
+                    gate: i % 2 == 0,
+                    ..default()
+                })
+                .collect(),
+            rate: default_rate(),
+            gate_length: default_gate_length(),
+        }
+    }
+}
+
+impl Sequencer {
+    /// Which step (counted from the start of the song) plays at a transport
+    /// position, and how far through it we are.
+    pub fn step_at(&self, beats: f64) -> (u64, f32) {
+        let steps = beats.max(0.0) / self.rate.beats() as f64;
+        (steps.floor() as u64, steps.fract() as f32)
+    }
+
+    pub fn step(&self, count: u64) -> Option<&Step> {
+        if self.steps.is_empty() {
+            return None;
+        }
+        self.steps.get((count % self.steps.len() as u64) as usize)
+    }
+}
+
+/// Runtime state of a Sequencer: the step playing and whether its gate is open.
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct SequencerPlayhead {
+    step: Option<u64>,
+    gate_open: bool,
+}
+
+/// Move each sequencer to the transport position: apply a step's values to
+/// the creature's limbs as it starts, and open and close its gate. A stopped
+/// transport closes any open gate, and the step starts over on play.
+pub fn advance_sequencers(
+    transport: Res<Transport>,
+    mut sequencers: Query<(Entity, &Sequencer, &mut SequencerPlayhead, &Children)>,
+    mut oscillators: Query<&mut Oscillator, With<Limb>>,
+    mut gates: MessageWriter<Gate>,
+) {
+    for (creature, sequencer, mut playhead, children) in &mut sequencers {
+        if !transport.is_playing() {
+            if playhead.gate_open {
+                gates.write(Gate {
+                    target: creature,
+                    open: false,
+                });
+                playhead.gate_open = false;
+            }
+            playhead.step = None;
+            continue;
+        }
+        let (count, fraction) = sequencer.step_at(transport.position());
+        let Some(step) = sequencer.step(count) else {
+            continue;
+        };
+
+        if playhead.step != Some(count) {
+            if playhead.gate_open {
+                gates.write(Gate {
+                    target: creature,
+                    open: false,
+                });
+                playhead.gate_open = false;
+            }
+            for child in children.iter() {
+                let Ok(mut osc) = oscillators.get_mut(child) else {
+                    continue;
+                };
+                if let Some(hz) = step.frequency {
+                    osc.set_frequency(hz);
+                }
+                if let Some(amplitude) = step.amplitude {
+                    osc.set_amplitude(amplitude);
+                }
+                if let Some(wave) = step.wave {
+                    osc.set_wave(wave);
+                }
+            }
+            if step.gate {
+                gates.write(Gate {
+                    target: creature,
+                    open: true,
+                });
+                playhead.gate_open = true;
+            }
+            playhead.step = Some(count);
+        } else if playhead.gate_open && fraction >= sequencer.gate_length {
+            gates.write(Gate {
+                target: creature,
+                open: false,
+            });
+            playhead.gate_open = false;
+        }
+    }
+}
+
+/// Close the gate of a step left open when its creature's sequencer is
+/// removed, so the notes it started don't hold forever.
+pub fn close_removed_sequencer_gates(
+    mut removed: RemovedComponents<Sequencer>,
+    mut playheads: Query<&mut SequencerPlayhead, Without<Sequencer>>,
+    mut gates: MessageWriter<Gate>,
+) {
+    for creature in removed.read() {
+        let Ok(mut playhead) = playheads.get_mut(creature) else {
+            continue;
+        };
+        if playhead.gate_open {
+            gates.write(Gate {
+                target: creature,
+                open: false,
+            });
+        }
+        *playhead = SequencerPlayhead::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::message::Messages;
+    use bevy::ecs::system::RunSystemOnce;
+
+    /// A creature and its one limb, playing a sixteenth-note sequence whose
+    /// first step is gated at 2 Hz and second ungated at 3 Hz.
+    fn sequenced() -> (World, Entity, Entity) {
+        let mut world = World::new();
+        world.init_resource::<Transport>();
+        world.init_resource::<Messages<Gate>>();
+        let limb = world.spawn(Limb).id();
+        let sequencer = Sequencer {
+            steps: vec![
+                Step {
+                    gate: true,
+                    frequency: Some(2.0),
+                    ..default()
+                },
+                Step {
+                    frequency: Some(3.0),
+                    ..default()
+                },
+            ],
+            ..default()
+        };
+        let creature = world.spawn(sequencer).add_child(limb).id();
+        (world, creature, limb)
+    }
+
+    /// Run the sequencer at a transport position, returning the gates it
+    /// opened (true) and closed (false).
+    fn run_at(world: &mut World, beats: f64) -> Vec<bool> {
+        world.resource_mut::<Transport>().set_position(beats);
+        world.run_system_once(advance_sequencers).unwrap();
+        world
+            .resource_mut::<Messages<Gate>>()
+            .drain()
+            .map(|gate| gate.open)
+            .collect()
+    }
+
+    fn frequency(world: &World, limb: Entity) -> f32 {
+        world.get::<Oscillator>(limb).unwrap().target_frequency()
+    }
+
+    #[test]
+    fn steps_apply_their_values_as_they_start() {
+        let (mut world, _, limb) = sequenced();
+        assert_eq!(run_at(&mut world, 0.0), [true]);
+        assert_eq!(frequency(&world, limb), 2.0);
+
+        // Sixteenths: the second step starts a quarter of a beat in.
+        assert_eq!(run_at(&mut world, 0.25), [false]);
+        assert_eq!(frequency(&world, limb), 3.0);
+        assert!(run_at(&mut world, 0.3).is_empty());
+
+        // And the pattern repeats.
+        assert_eq!(run_at(&mut world, 0.5), [true]);
+        assert_eq!(frequency(&world, limb), 2.0);
+    }
+
+    #[test]
+    fn gates_close_after_the_gate_length() {
+        let (mut world, _, _) = sequenced();
+        assert_eq!(run_at(&mut world, 0.0), [true]);
+        // Half of the step by default.
+        assert!(run_at(&mut world, 0.1).is_empty());
+        assert_eq!(run_at(&mut world, 0.13), [false]);
+        assert!(run_at(&mut world, 0.2).is_empty());
+    }
+
+    #[test]
+    fn stopping_closes_an_open_gate() {
+        let (mut world, _, _) = sequenced();
+        assert_eq!(run_at(&mut world, 0.0), [true]);
+
+        world.resource_mut::<Transport>().stop();
+        assert_eq!(run_at(&mut world, 0.05), [false]);
+        assert!(run_at(&mut world, 0.05).is_empty());
+
+        // Playing again starts the step over.
+        world.resource_mut::<Transport>().play();
+        assert_eq!(run_at(&mut world, 0.05), [true]);
+    }
+
+    #[test]
+    fn removing_the_sequencer_closes_an_open_gate() {
+        let (mut world, creature, _) = sequenced();
+        assert_eq!(run_at(&mut world, 0.0), [true]);
+
+        world.entity_mut(creature).remove::<Sequencer>();
+        world.run_system_once(close_removed_sequencer_gates).unwrap();
+        let gates: Vec<(Entity, bool)> = world
+            .resource_mut::<Messages<Gate>>()
+            .drain()
+            .map(|gate| (gate.target, gate.open))
+            .collect();
+        assert_eq!(gates, [(creature, false)]);
+    }
+}
@@ -0,0 +1,479 @@
+use bevy::audio::{Decodable, Source};
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::creature::Creature;
+use crate::editor::EditorPreview;
+use crate::effects::{EffectChain, Effects};
+use crate::lifecycle::{presence, Dying, Lifecycle, Spawning};
+use crate::limb::Limb;
+use crate::oscillator::{Oscillator, Wave};
+
+pub const SAMPLE_RATE: u32 = 44_100;
+/// A limb moving at 1 Hz sounds at middle C, so notes sound at their pitch.
+const MIDDLE_C_HZ: f32 = 261.63;
+/// Frames rendered between looks at the latest MixerInput.
+const BLOCK_FRAMES: usize = 256;
+/// Output of one limb at amplitude 1, leaving headroom for many limbs.
+const VOICE_LEVEL: f32 = 0.1;
+/// Time constant (seconds) for smoothing per-frame parameter jumps.
+const SMOOTHING_SECS: f32 = 0.01;
+
+/// One limb as heard: its wave at an audible pitch.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct VoiceSound {
+    pub wave: Wave,
+    /// Hz.
+    pub frequency: f32,
+    pub level: f32,
+}
+
+/// One creature as heard.
+#[derive(Debug, Clone, Default)]
+pub struct CreatureSound {
+    pub voices: Vec<VoiceSound>,
+    pub effects: Effects,
+    /// The filter's modulating oscillator output (-1..1).
+    pub cutoff_offset: f32,
+    /// -1 (left) to 1 (right).
+    pub pan: f32,
+    /// Gain for distance from the listener (0..1).
+    pub attenuation: f32,
+}
+
+/// Everything the mixer plays, refreshed from the world every frame.
+#[derive(Debug, Clone, Default)]
+pub struct MixerInput {
+    pub creatures: Vec<(Entity, CreatureSound)>,
+    pub master_gain: f32,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct VoiceState {
+    phase: f32,
+    level: f32,
+}
+
+#[derive(Debug, Clone)]
+struct CreatureState {
+    /// The last render this creature was in.
+    generation: u64,
+    voices: Vec<VoiceState>,
+    chain: EffectChain,
+    cutoff_offset: f32,
+    pan: f32,
+    attenuation: f32,
+}
+
+/// Renders MixerInput to interleaved stereo, keeping phases and effect
+/// tails from one call to the next.
+pub struct Mixer {
+    sample_rate: u32,
+    creatures: HashMap<Entity, CreatureState>,
+    /// Renders so far, to spot creatures gone from the input.
+    generation: u64,
+}
+
+impl Mixer {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            creatures: HashMap::new(),
+            generation: 0,
+        }
+    }
+
+    /// Fill `out` (interleaved left, right) from the input.
+    pub fn render(&mut self, input: &MixerInput, out: &mut [f32]) {
+        out.fill(0.0);
+        self.generation += 1;
+        let generation = self.generation;
+        let dt = 1.0 / self.sample_rate as f32;
+        let smoothing = 1.0 - (-dt / SMOOTHING_SECS).exp();
+        for (entity, sound) in &input.creatures {
+            let state = self
+                .creatures
+                .entry(*entity)
+                .or_insert_with(|| CreatureState {
+                    generation,
+                    voices: Vec::new(),
+                    chain: EffectChain::new(self.sample_rate),
+                    cutoff_offset: 0.0,
+                    pan: sound.pan,
+                    attenuation: sound.attenuation,
+                });
+            state.generation = generation;
+            state
+                .voices
+                .resize(sound.voices.len(), VoiceState::default());
+            for frame in out.chunks_exact_mut(2) {
+                let mut dry = 0.0;
+                for (voice, voice_state) in sound.voices.iter().zip(&mut state.voices) {
+                    voice_state.level += (voice.level - voice_state.level) * smoothing;
+                    voice_state.phase = (voice_state.phase + voice.frequency * dt).fract();
+                    dry += voice.wave.value(voice_state.phase) * voice_state.level;
+                }
+                state.cutoff_offset += (sound.cutoff_offset - state.cutoff_offset) * smoothing;
+                let wet =
+                    state
+                        .chain
+                        .process(&sound.effects, state.cutoff_offset, dry * VOICE_LEVEL);
+                // Smoothed so moving creatures glide rather than step.
+                state.pan += (sound.pan - state.pan) * smoothing;
+                state.attenuation += (sound.attenuation - state.attenuation) * smoothing;
+                let (left, right) = pan_gains(state.pan);
+                frame[0] += wet * state.attenuation * left;
+                frame[1] += wet * state.attenuation * right;
+            }
+        }
+        self.creatures.retain(|_, state| state.generation == generation);
+        for sample in out {
+            *sample = (*sample * input.master_gain).tanh();
+        }
+    }
+}
+
+/// Left and right gains for a pan position. Equal-power, so a creature
+/// crossing the screen keeps its loudness.
+fn pan_gains(pan: f32) -> (f32, f32) {
+    let angle = (pan.clamp(-1.0, 1.0) + 1.0) * std::f32::consts::FRAC_PI_4;
+    (angle.cos(), angle.sin())
+}
+
+/// Pan and attenuation for a position in normalized device coordinates,
+/// where the view spans -1..1 on both axes. Creatures in view play at full
+/// level, corners included; beyond the edge they fade as distance^-rolloff,
+/// where the edge is at distance 1.
+pub fn spatialize(ndc: Vec2, rolloff: f32) -> (f32, f32) {
+    let pan = ndc.x.clamp(-1.0, 1.0);
+    let attenuation = ndc.abs().max_element().max(1.0).powf(-rolloff.max(0.0));
+    (pan, attenuation)
+}
+
+/// The creatures' sound, playable as an audio source.
+#[derive(Asset, TypePath, Debug, Clone)]
+pub struct Synth {
+    exchange: Arc<Mutex<InputExchange>>,
+}
+
+/// Where the world leaves new input for the audio thread, and the audio
+/// thread leaves the input it's done with, so that it's dropped back on the
+/// world's side. The audio thread only swaps pointers here, never
+/// allocating, freeing or waiting for the lock.
+#[derive(Debug, Default)]
+struct InputExchange {
+    fresh: Option<Arc<MixerInput>>,
+    retired: Option<Arc<MixerInput>>,
+}
+
+/// Renders the Synth in blocks, picking up the latest input between blocks.
+pub struct SynthDecoder {
+    exchange: Arc<Mutex<InputExchange>>,
+    input: Arc<MixerInput>,
+    mixer: Mixer,
+    buffer: Vec<f32>,
+    position: usize,
+}
+
+impl Iterator for SynthDecoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.position >= self.buffer.len() {
+            // Never block the audio thread; keep the old input if busy.
+            if let Ok(mut exchange) = self.exchange.try_lock() {
+                if let Some(fresh) = exchange.fresh.take() {
+                    exchange.retired = Some(std::mem::replace(&mut self.input, fresh));
+                }
+            }
+            self.mixer.render(&self.input, &mut self.buffer);
+            self.position = 0;
+        }
+        let sample = self.buffer[self.position];
+        self.position += 1;
+        Some(sample)
+    }
+}
+
+impl Source for SynthDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        2
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+impl Decodable for Synth {
+    type DecoderItem = f32;
+    type Decoder = SynthDecoder;
+
+    fn decoder(&self) -> SynthDecoder {
+        let buffer = vec![0.0; BLOCK_FRAMES * 2];
+        SynthDecoder {
+            exchange: self.exchange.clone(),
+            input: Arc::default(),
+            mixer: Mixer::new(SAMPLE_RATE),
+            position: buffer.len(),
+            buffer,
+        }
+    }
+}
+
+/// The master bus, and the line from the world to the audio thread.
+#[derive(Resource, Debug)]
+pub struct MasterBus {
+    pub gain: f32,
+    /// Audible pitch (Hz) of a limb moving at 1 Hz.
+    pub pitch_scale: f32,
+    /// How fast creatures outside the camera's view fade; 0 keeps them at
+    /// full level.
+    pub rolloff: f32,
+    input: Arc<MixerInput>,
+    exchange: Arc<Mutex<InputExchange>>,
+}
+
+impl Default for MasterBus {
+    fn default() -> Self {
+        Self {
+            gain: 0.5,
+            pitch_scale: MIDDLE_C_HZ,
+            rolloff: 2.0,
+            input: Arc::default(),
+            exchange: Arc::default(),
+        }
+    }
+}
+
+impl MasterBus {
+    pub fn synth(&self) -> Synth {
+        Synth {
+            exchange: self.exchange.clone(),
+        }
+    }
+
+    /// The latest input sent to the mixer.
+    pub fn input(&self) -> Arc<MixerInput> {
+        self.input.clone()
+    }
+
+    /// Send new input to the mixer.
+    fn send(&mut self, input: MixerInput) {
+        self.input = Arc::new(input);
+        let retired = match self.exchange.lock() {
+            Ok(mut exchange) => {
+                exchange.fresh = Some(self.input.clone());
+                exchange.retired.take()
+            }
+            Err(_) => None,
+        };
+        // Dropped here rather than on the audio thread.
+        drop(retired);
+    }
+}
+
+/// Start playing the creatures' sound.
+pub fn play_synth(mut commands: Commands, bus: Res<MasterBus>, mut synths: ResMut<Assets<Synth>>) {
+    commands.spawn(AudioPlayer(synths.add(bus.synth())));
+}
+
+type CreatureData<'a> = (
+    Entity,
+    &'a GlobalTransform,
+    &'a Children,
+    Option<&'a Effects>,
+    Option<&'a Spawning>,
+    Option<&'a Dying>,
+);
+
+/// Send every creature's limbs, effects and place relative to the camera to
+/// the mixer. Creatures coming or going fade in and out with their looks; the
+/// editor's preview isn't heard.
+pub fn update_mixer(
+    mut bus: ResMut<MasterBus>,
+    lifecycle: Res<Lifecycle>,
+    camera: Single<(&Camera, &GlobalTransform), With<Camera2d>>,
+    creatures: Query<CreatureData, (With<Creature>, Without<EditorPreview>)>,
+    limbs: Query<&Oscillator, With<Limb>>,
+) {
+    let (camera, camera_transform) = *camera;
+    let creatures = creatures
+        .iter()
+        .map(|(entity, transform, children, effects, spawning, dying)| {
+            let oscillators: Vec<&Oscillator> =
+                children.iter().filter_map(|c| limbs.get(c).ok()).collect();
+            let effects = effects.copied().unwrap_or_default();
+            let cutoff_offset = effects
+                .filter
+                .and_then(|filter| filter.modulation)
+                .and_then(|modulation| oscillators.get(modulation.limb))
+                .map_or(0.0, |osc| osc.wave().value(osc.phase()));
+            let (pan, attenuation) = camera
+                .world_to_ndc(camera_transform, transform.translation())
+                .map_or((0.0, 1.0), |ndc| spatialize(ndc.truncate(), bus.rolloff));
+            let attenuation = attenuation * presence(&lifecycle, spawning, dying);
+            let voices = oscillators
+                .iter()
+                .map(|osc| VoiceSound {
+                    wave: osc.wave(),
+                    frequency: osc.effective_frequency() * bus.pitch_scale,
+                    level: osc.level(),
+                })
+                .collect();
+            (
+                entity,
+                CreatureSound {
+                    voices,
+                    effects,
+                    cutoff_offset,
+                    pan,
+                    attenuation,
+                },
+            )
+        })
+        .collect();
+
+    let master_gain = bus.gain;
+    bus.send(MixerInput {
+        creatures,
+        master_gain,
+    });
+}
+
+/// Small master bus window: volume, pitch and distance rolloff.
+pub fn sound_panel(mut contexts: EguiContexts, mut bus: ResMut<MasterBus>) -> Result {
+    let ctx = contexts.ctx_mut()?;
+    egui::Window::new("Sound")
+        .default_pos([10.0, 220.0])
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.add(egui::Slider::new(&mut bus.gain, 0.0..=2.0).text("master"));
+            ui.add(
+                egui::Slider::new(&mut bus.pitch_scale, 20.0..=2000.0)
+                    .logarithmic(true)
+                    .text("Hz per limb Hz"),
+            );
+            ui.add(egui::Slider::new(&mut bus.rolloff, 0.0..=4.0).text("distance rolloff"));
+        });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-5
+    }
+
+    fn tone(hz: f32) -> MixerInput {
+        MixerInput {
+            creatures: vec![(
+                Entity::from_raw_u32(1).unwrap(),
+                CreatureSound {
+                    voices: vec![VoiceSound {
+                        wave: Wave::Sine,
+                        frequency: hz,
+                        level: 1.0,
+                    }],
+                    effects: Effects::default(),
+                    cutoff_offset: 0.0,
+                    pan: 0.0,
+                    attenuation: 1.0,
+                },
+            )],
+            master_gain: 1.0,
+        }
+    }
+
+    #[test]
+    fn mixing_in_blocks_keeps_the_phase() {
+        let input = tone(440.0);
+        let mut whole = vec![0.0; BLOCK_FRAMES * 4];
+        Mixer::new(SAMPLE_RATE).render(&input, &mut whole);
+        let mut mixer = Mixer::new(SAMPLE_RATE);
+        let mut blocks = vec![0.0; BLOCK_FRAMES * 4];
+        for block in blocks.chunks_mut(BLOCK_FRAMES * 2) {
+            mixer.render(&input, block);
+        }
+        assert!(whole.iter().any(|&sample| sample.abs() > 0.01));
+        assert_eq!(whole, blocks);
+    }
+
+    #[test]
+    fn creatures_leave_the_mix_when_gone() {
+        let mut mixer = Mixer::new(SAMPLE_RATE);
+        let mut out = vec![0.0; BLOCK_FRAMES * 2];
+        mixer.render(&tone(440.0), &mut out);
+        assert_eq!(mixer.creatures.len(), 1);
+        mixer.render(&MixerInput::default(), &mut out);
+        assert!(mixer.creatures.is_empty());
+        assert!(out.iter().all(|&sample| sample == 0.0));
+    }
+
+    #[test]
+    fn the_audio_thread_hands_old_input_back() {
+        let mut bus = MasterBus::default();
+        let mut decoder = bus.synth().decoder();
+        bus.send(tone(440.0));
+        decoder.next();
+        assert!(Arc::ptr_eq(&decoder.input, &bus.input()));
+        let retired = bus.exchange.lock().unwrap().retired.clone();
+        assert!(retired.is_some_and(|input| input.creatures.is_empty()));
+
+        bus.send(tone(220.0));
+        let exchange = bus.exchange.lock().unwrap();
+        assert!(exchange.retired.is_none());
+        assert!(exchange.fresh.is_some());
+    }
+
+    #[test]
+    fn panning_keeps_equal_power() {
+        for i in 0..=20 {
+            let pan = i as f32 / 10.0 - 1.0;
+            let (left, right) = pan_gains(pan);
+            assert!(close(left * left + right * right, 1.0), "at {pan}");
+        }
+        assert!(close(pan_gains(-1.0).1, 0.0));
+        assert!(close(pan_gains(1.0).0, 0.0));
+        let (left, right) = pan_gains(0.0);
+        assert!(close(left, right));
+    }
+
+    #[test]
+    fn creatures_in_view_play_at_full_level() {
+        for ndc in [
+            Vec2::ZERO,
+            Vec2::new(0.9, 0.9),
+            Vec2::new(-1.0, 1.0),
+            Vec2::new(0.5, -0.99),
+        ] {
+            let (pan, attenuation) = spatialize(ndc, 2.0);
+            assert_eq!(pan, ndc.x);
+            assert_eq!(attenuation, 1.0, "at {ndc}");
+        }
+    }
+
+    #[test]
+    fn creatures_fade_beyond_the_edge() {
+        let (pan, attenuation) = spatialize(Vec2::new(2.0, 0.5), 2.0);
+        assert_eq!(pan, 1.0);
+        assert!(close(attenuation, 0.25));
+        let (_, further) = spatialize(Vec2::new(0.0, -4.0), 2.0);
+        assert!(further < attenuation);
+        // No rolloff keeps everything at full level.
+        assert_eq!(spatialize(Vec2::new(10.0, 10.0), 0.0).1, 1.0);
+    }
+}
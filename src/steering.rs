@@ -0,0 +1,525 @@
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::creature::Creature;
+use crate::editor::EditorPreview;
+use crate::lifecycle::Dying;
+use crate::limb::Limb;
+use crate::oscillator::Oscillator;
+
+/// Slowest limbs run, at a standstill, relative to their own frequency.
+const PACE_AT_REST: f32 = 0.5;
+const MAX_PACE: f32 = 2.0;
+
+/// Steers a creature: its behaviours (Wander, Seek, Flee, Separation,
+/// Alignment, Cohesion) push on its velocity, and it faces the way it's
+/// going. Its limbs beat faster the faster it goes.
+#[derive(Component, Debug, Clone, Copy)]
+#[require(Transform)]
+pub struct Steering {
+    pub velocity: Vec2,
+    pub max_speed: f32,
+    /// Largest change in velocity per second.
+    pub max_force: f32,
+    /// Speed at which limbs beat at their own frequency.
+    pub cruise_speed: f32,
+}
+
+impl Default for Steering {
+    fn default() -> Self {
+        Self {
+            velocity: Vec2::ZERO,
+            max_speed: 80.0,
+            max_force: 60.0,
+            cruise_speed: 40.0,
+        }
+    }
+}
+
+impl Steering {
+    /// Limb pace at the current speed: slower at rest, 1 at cruising speed,
+    /// faster beyond.
+    pub fn pace(&self) -> f32 {
+        let speed = self.velocity.length() / self.cruise_speed.max(f32::EPSILON);
+        (PACE_AT_REST + (1.0 - PACE_AT_REST) * speed).min(MAX_PACE)
+    }
+
+    /// The force turning the velocity toward `direction` at full speed.
+    fn toward(&self, direction: Vec2) -> Vec2 {
+        direction.normalize_or_zero() * self.max_speed - self.velocity
+    }
+}
+
+/// Something to head for or away from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Target {
+    Point(Vec2),
+    /// Wherever an entity is, or nowhere once it's gone.
+    Entity(Entity),
+}
+
+/// Drift about, turning a little at random.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Wander {
+    pub weight: f32,
+    /// How far ahead the wander circle is.
+    pub distance: f32,
+    pub radius: f32,
+    /// How quickly the point on the circle moves (radians per second).
+    pub jitter: f32,
+    angle: f32,
+}
+
+impl Default for Wander {
+    fn default() -> Self {
+        Self {
+            weight: 1.0,
+            distance: 60.0,
+            radius: 30.0,
+            jitter: 3.0,
+            angle: 0.0,
+        }
+    }
+}
+
+/// Where wandering creatures' random turns come from. Seed it for runs that
+/// play out the same every time.
+#[derive(Resource, Debug)]
+pub struct Wandering {
+    rng: StdRng,
+}
+
+impl Default for Wandering {
+    fn default() -> Self {
+        Self {
+            rng: StdRng::from_os_rng(),
+        }
+    }
+}
+
+impl Wandering {
+    pub fn seeded(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+/// Head for a target.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Seek {
+    pub target: Target,
+    pub weight: f32,
+}
+
+/// Head away from a target that comes within `radius`.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Flee {
+    pub target: Target,
+    pub radius: f32,
+    pub weight: f32,
+}
+
+/// Keep clear of steering creatures within `radius`, the nearest most.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Separation {
+    pub radius: f32,
+    pub weight: f32,
+}
+
+/// Head the same way as steering creatures within `radius`.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Alignment {
+    pub radius: f32,
+    pub weight: f32,
+}
+
+/// Head for the middle of steering creatures within `radius`.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Cohesion {
+    pub radius: f32,
+    pub weight: f32,
+}
+
+impl Default for Separation {
+    fn default() -> Self {
+        Self {
+            radius: 250.0,
+            weight: 1.5,
+        }
+    }
+}
+
+impl Default for Alignment {
+    fn default() -> Self {
+        Self {
+            radius: 350.0,
+            weight: 1.0,
+        }
+    }
+}
+
+impl Default for Cohesion {
+    fn default() -> Self {
+        Self {
+            radius: 450.0,
+            weight: 0.8,
+        }
+    }
+}
+
+/// A steering creature's place and velocity at the start of a step.
+struct Neighbor {
+    entity: Entity,
+    position: Vec2,
+    velocity: Vec2,
+}
+
+type Behaviors<'a> = (
+    Option<&'a mut Wander>,
+    Option<&'a Seek>,
+    Option<&'a Flee>,
+    Option<&'a Separation>,
+    Option<&'a Alignment>,
+    Option<&'a Cohesion>,
+);
+type Steerer<'a> = (Entity, &'a mut Transform, &'a mut Steering, Behaviors<'a>);
+
+/// Sum each creature's behaviours into a force, and move it along.
+pub fn steer(
+    time: Res<Time>,
+    mut wandering: ResMut<Wandering>,
+    mut creatures: Query<Steerer, Without<Dying>>,
+    targets: Query<&GlobalTransform>,
+) {
+    let dt = time.delta_secs();
+    if dt <= 0.0 {
+        return;
+    }
+    let neighbors: Vec<Neighbor> = creatures
+        .iter()
+        .map(|(entity, transform, steering, _)| Neighbor {
+            entity,
+            position: transform.translation.truncate(),
+            velocity: steering.velocity,
+        })
+        .collect();
+    let locate = |target: Target| match target {
+        Target::Point(point) => Some(point),
+        Target::Entity(entity) => targets.get(entity).ok().map(|t| t.translation().truncate()),
+    };
+    let rng = &mut wandering.rng;
+
+    for (entity, mut transform, mut steering, behaviors) in &mut creatures {
+        let (wander, seek, flee, separation, alignment, cohesion) = behaviors;
+        let position = transform.translation.truncate();
+        let heading = match steering.velocity.try_normalize() {
+            Some(heading) => heading,
+            None => (transform.rotation * Vec3::X).truncate(),
+        };
+        let within = |radius: f32| {
+            neighbors.iter().filter(move |n| {
+                n.entity != entity && n.position.distance_squared(position) < radius * radius
+            })
+        };
+
+        let mut force = Vec2::ZERO;
+        if let Some(mut wander) = wander {
+            wander.angle += rng.random_range(-1.0..=1.0) * wander.jitter * dt;
+            let ahead = heading * wander.distance
+                + wander.radius * Vec2::from_angle(wander.angle).rotate(heading);
+            force += wander.weight * steering.toward(ahead);
+        }
+        if let Some(seek) = seek
+            && let Some(target) = locate(seek.target)
+        {
+            force += seek.weight * steering.toward(target - position);
+        }
+        if let Some(flee) = flee
+            && let Some(threat) = locate(flee.target)
+            && threat.distance(position) < flee.radius
+        {
+            force += flee.weight * steering.toward(position - threat);
+        }
+        if let Some(separation) = separation {
+            let away: Vec2 = within(separation.radius)
+                .map(|n| {
+                    let offset = position - n.position;
+                    offset / offset.length_squared().max(1.0)
+                })
+                .sum();
+            if away != Vec2::ZERO {
+                force += separation.weight * steering.toward(away);
+            }
+        }
+        if let Some(alignment) = alignment {
+            let heading: Vec2 = within(alignment.radius).map(|n| n.velocity).sum();
+            if heading != Vec2::ZERO {
+                force += alignment.weight * steering.toward(heading);
+            }
+        }
+        if let Some(cohesion) = cohesion {
+            let (sum, count) = within(cohesion.radius).fold((Vec2::ZERO, 0), |(sum, count), n| {
+                (sum + n.position, count + 1)
+            });
+            if count > 0 {
+                let middle = sum / count as f32;
+                force += cohesion.weight * steering.toward(middle - position);
+            }
+        }
+
+        let force = force.clamp_length_max(steering.max_force);
+        steering.velocity = (steering.velocity + force * dt).clamp_length_max(steering.max_speed);
+        transform.translation += (steering.velocity * dt).extend(0.0);
+        if let Some(heading) = steering.velocity.try_normalize() {
+            transform.rotation = Quat::from_rotation_z(heading.to_angle());
+        }
+    }
+}
+
+/// Beat steering creatures' limbs faster the faster they go.
+pub fn pace_limbs(
+    creatures: Query<(&Steering, &Children)>,
+    mut limbs: Query<&mut Oscillator, With<Limb>>,
+) {
+    for (steering, children) in &creatures {
+        let pace = steering.pace();
+        for child in children.iter() {
+            if let Ok(mut osc) = limbs.get_mut(child) {
+                osc.set_pace(pace);
+            }
+        }
+    }
+}
+
+/// Put limbs back to their own pace once their creature stops steering.
+pub fn reset_limb_pace(
+    mut removed: RemovedComponents<Steering>,
+    children: Query<&Children>,
+    mut limbs: Query<&mut Oscillator, With<Limb>>,
+) {
+    for creature in removed.read() {
+        let Ok(children) = children.get(creature) else {
+            continue;
+        };
+        for child in children.iter() {
+            if let Ok(mut osc) = limbs.get_mut(child) {
+                osc.set_pace(1.0);
+            }
+        }
+    }
+}
+
+/// Have every creature wander and flock as it's spawned, bar the editor's
+/// preview.
+pub fn add_flocking(
+    mut commands: Commands,
+    creatures: Query<Entity, (Added<Creature>, Without<EditorPreview>)>,
+) {
+    for creature in &creatures {
+        commands.entity(creature).insert((
+            Steering::default(),
+            Wander::default(),
+            Separation::default(),
+            Alignment::default(),
+            Cohesion::default(),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::creature::example_creatures_plan;
+    use crate::oscillator::oscillator_tick;
+    use crate::sound::{update_mixer, MasterBus};
+    use crate::testing::Harness;
+
+    /// Steering creatures at the given places, at rest and facing +x.
+    fn school(places: &[Vec2]) -> Harness {
+        let mut plan = example_creatures_plan();
+        plan.creatures.truncate(places.len());
+        for (creature, place) in plan.creatures.iter_mut().zip(places) {
+            creature.transform = Transform::from_translation(place.extend(0.0));
+        }
+        let mut harness = Harness::new(plan);
+        harness
+            .app
+            .insert_resource(Wandering::seeded(7))
+            .add_systems(
+                Update,
+                (steer, pace_limbs, reset_limb_pace)
+                    .chain()
+                    .before(oscillator_tick),
+            );
+        for creature in harness.creatures() {
+            harness
+                .app
+                .world_mut()
+                .entity_mut(creature)
+                .insert(Steering::default());
+        }
+        harness
+    }
+
+    fn position(harness: &Harness, creature: Entity) -> Vec2 {
+        harness
+            .world()
+            .get::<Transform>(creature)
+            .unwrap()
+            .translation
+            .truncate()
+    }
+
+    #[test]
+    fn seekers_reach_their_target() {
+        let mut harness = school(&[Vec2::ZERO]);
+        let creature = harness.creatures()[0];
+        let target = Vec2::new(-100.0, 200.0);
+        harness.app.world_mut().entity_mut(creature).insert(Seek {
+            target: Target::Point(target),
+            weight: 1.0,
+        });
+
+        harness.advance(3.0);
+        let before = position(&harness, creature).distance(target);
+        harness.advance(2.0);
+        assert!(position(&harness, creature).distance(target) < before);
+        assert!(before < target.length());
+    }
+
+    #[test]
+    fn fleers_only_run_from_threats_nearby() {
+        let mut harness = school(&[Vec2::ZERO, Vec2::new(1000.0, 0.0)]);
+        let [near, far] = harness.creatures()[..] else {
+            panic!("expected two creatures");
+        };
+        let threat = Vec2::new(20.0, 0.0);
+        for creature in [near, far] {
+            harness.app.world_mut().entity_mut(creature).insert(Flee {
+                target: Target::Point(threat),
+                radius: 200.0,
+                weight: 1.0,
+            });
+        }
+
+        harness.advance(2.0);
+        assert!(position(&harness, near).x < -20.0);
+        assert_eq!(position(&harness, far), Vec2::new(1000.0, 0.0));
+    }
+
+    #[test]
+    fn separation_pushes_neighbors_apart() {
+        let mut harness = school(&[Vec2::ZERO, Vec2::new(50.0, 0.0)]);
+        let [a, b] = harness.creatures()[..] else {
+            panic!("expected two creatures");
+        };
+        for creature in [a, b] {
+            harness
+                .app
+                .world_mut()
+                .entity_mut(creature)
+                .insert(Separation::default());
+        }
+
+        harness.advance(2.0);
+        assert!(position(&harness, a).distance(position(&harness, b)) > 100.0);
+    }
+
+    #[test]
+    fn limbs_beat_faster_as_creatures_hurry() {
+        let mut harness = school(&[Vec2::ZERO]);
+        let creature = harness.creatures()[0];
+        let paces = |harness: &Harness| -> Vec<f32> {
+            let world = harness.world();
+            world
+                .get::<Children>(creature)
+                .unwrap()
+                .iter()
+                .filter_map(|child| world.get::<Oscillator>(child))
+                .map(|osc| osc.pace())
+                .collect()
+        };
+        harness.advance(0.1);
+        assert!(paces(&harness).iter().all(|&pace| pace == PACE_AT_REST));
+
+        harness.app.world_mut().entity_mut(creature).insert(Seek {
+            target: Target::Point(Vec2::new(10_000.0, 0.0)),
+            weight: 1.0,
+        });
+        harness.advance(3.0);
+        let hurrying = paces(&harness);
+        assert!(!hurrying.is_empty());
+        assert!(hurrying.iter().all(|&pace| pace > 1.0), "{hurrying:?}");
+
+        // Back to their own pace once steering stops.
+        harness
+            .app
+            .world_mut()
+            .entity_mut(creature)
+            .remove::<Steering>();
+        harness.advance(0.1);
+        assert!(paces(&harness).iter().all(|&pace| pace == 1.0));
+    }
+
+    #[test]
+    fn creatures_sound_higher_as_they_hurry() {
+        let mut harness = school(&[Vec2::ZERO]);
+        let creature = harness.creatures()[0];
+        harness.app.world_mut().spawn(Camera2d);
+        harness
+            .app
+            .init_resource::<MasterBus>()
+            .add_systems(Update, update_mixer.after(oscillator_tick));
+        let pitches = |harness: &Harness| -> Vec<f32> {
+            let input = harness.world().resource::<MasterBus>().input();
+            input.creatures[0]
+                .1
+                .voices
+                .iter()
+                .map(|voice| voice.frequency)
+                .collect()
+        };
+        harness.advance(0.1);
+        let resting = pitches(&harness);
+        assert!(resting.iter().any(|&hz| hz > 0.0));
+
+        harness.app.world_mut().entity_mut(creature).insert(Seek {
+            target: Target::Point(Vec2::new(10_000.0, 0.0)),
+            weight: 1.0,
+        });
+        harness.advance(3.0);
+        let hurrying = pitches(&harness);
+        assert_eq!(hurrying.len(), resting.len());
+        for (hurrying, resting) in hurrying.iter().zip(&resting) {
+            assert!(
+                *hurrying >= resting * 2.0,
+                "{hurrying} at speed, {resting} at rest"
+            );
+        }
+    }
+
+    #[test]
+    fn seeded_wandering_plays_out_the_same() {
+        let wander = || {
+            let mut harness = school(&[Vec2::ZERO, Vec2::new(300.0, 0.0)]);
+            for creature in harness.creatures() {
+                harness
+                    .app
+                    .world_mut()
+                    .entity_mut(creature)
+                    .insert(Wander::default());
+            }
+            harness.advance(2.0);
+            let creatures = harness.creatures();
+            creatures
+                .into_iter()
+                .map(|creature| position(&harness, creature))
+                .collect::<Vec<_>>()
+        };
+        let first = wander();
+        assert_ne!(first[0], Vec2::ZERO);
+        assert_eq!(first, wander());
+    }
+}
@@ -0,0 +1,182 @@
+//! Headless harness for testing creatures: no window, no GPU, and time that
+//! only moves when told to.
+
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use std::time::Duration;
+
+use crate::creature::{spawn_creatures, Creature, CreatureNames, CreaturesPlan, SpawnOrder};
+use crate::lifecycle::{
+    fade_dying_creatures, grow_spawning_creatures, start_dying, wither_dying_creatures, Lifecycle,
+};
+use crate::limb::{
+    animate_limb_segments, record_fixed_samples, Limb, LimbAssetStore, LimbSegment,
+    LimbSegmentJoint,
+};
+use crate::oscillator::oscillator_tick;
+
+/// Simulated time per update, as at 60 FPS.
+const STEP_SECS: f64 = 1.0 / 60.0;
+
+/// A `MinimalPlugins` app with creatures spawned from a plan, ticking their
+/// oscillators and animating their limbs, and their coming and going.
+pub struct Harness {
+    pub app: App,
+}
+
+impl Harness {
+    /// Spawn the plan's creatures, at time zero.
+    pub fn new(plan: CreaturesPlan) -> Self {
+        Self::build(plan, None)
+    }
+
+    /// As `new`, but ticking oscillators at a fixed rate (Hz) and drawing
+    /// limbs between the steps, as with `--fixed-hz`.
+    pub fn fixed(plan: CreaturesPlan, hz: f64) -> Self {
+        Self::build(plan, Some(hz))
+    }
+
+    fn build(plan: CreaturesPlan, fixed_hz: Option<f64>) -> Self {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default(), TransformPlugin))
+            .init_asset::<Mesh>()
+            .init_asset::<ColorMaterial>()
+            .insert_resource(LimbAssetStore::default())
+            .init_resource::<CreatureNames>()
+            .insert_resource(plan)
+            .init_resource::<Lifecycle>()
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::ZERO))
+            .add_systems(Startup, spawn_creatures)
+            .add_systems(
+                Update,
+                (
+                    animate_limb_segments,
+                    grow_spawning_creatures,
+                    start_dying,
+                    wither_dying_creatures,
+                    fade_dying_creatures,
+                )
+                    .chain(),
+            );
+        match fixed_hz {
+            Some(hz) => app
+                .insert_resource(Time::<Fixed>::from_hz(hz))
+                .add_systems(FixedUpdate, (oscillator_tick, record_fixed_samples).chain()),
+            None => app.add_systems(Update, oscillator_tick.before(animate_limb_segments)),
+        };
+        app.update();
+        Self { app }
+    }
+
+    pub fn world(&self) -> &World {
+        self.app.world()
+    }
+
+    /// Seconds simulated so far.
+    pub fn elapsed(&self) -> f64 {
+        self.world().resource::<Time>().elapsed_secs_f64()
+    }
+
+    /// Run updates of at most 1/60 s until `secs` more have passed.
+    pub fn advance(&mut self, secs: f64) {
+        let mut remaining = secs;
+        while remaining > 1e-9 {
+            let step = remaining.min(STEP_SECS);
+            self.frame(step);
+            remaining -= step;
+        }
+    }
+
+    /// Run a single update, `secs` long.
+    pub fn frame(&mut self, secs: f64) {
+        self.app
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+                secs,
+            )));
+        self.app.update();
+    }
+
+    /// Run until `secs` since the start; does nothing if already past it.
+    pub fn advance_to(&mut self, secs: f64) {
+        self.advance(secs - self.elapsed());
+    }
+
+    /// Every creature, in spawn order.
+    pub fn creatures(&mut self) -> Vec<Entity> {
+        let world = self.app.world_mut();
+        let mut creatures: Vec<(Option<SpawnOrder>, Entity)> = world
+            .query_filtered::<(Entity, Option<&SpawnOrder>), With<Creature>>()
+            .iter(world)
+            .map(|(e, order)| (order.copied(), e))
+            .collect();
+        creatures.sort();
+        creatures.into_iter().map(|(_, e)| e).collect()
+    }
+
+    /// A creature's limbs, in plan order.
+    pub fn limbs(&self, creature: Entity) -> Vec<Entity> {
+        self.children_with::<Limb>(creature)
+    }
+
+    /// A limb's segments, from the body outwards.
+    pub fn segments(&self, limb: Entity) -> Vec<Entity> {
+        let mut segments = Vec::new();
+        let mut parent = limb;
+        while let Some(&segment) = self.children_with::<LimbSegment>(parent).first() {
+            segments.push(segment);
+            match self.children_with::<LimbSegmentJoint>(segment).first() {
+                Some(&joint) => parent = joint,
+                None => break,
+            }
+        }
+        segments
+    }
+
+    /// The outgoing joint of each of a limb's segments.
+    pub fn joints(&self, limb: Entity) -> Vec<Entity> {
+        self.segments(limb)
+            .into_iter()
+            .filter_map(|segment| {
+                self.children_with::<LimbSegmentJoint>(segment)
+                    .first()
+                    .copied()
+            })
+            .collect()
+    }
+
+    /// The rotation (radians about z) of each of a limb's segments.
+    pub fn segment_rotations(&self, limb: Entity) -> Vec<f32> {
+        self.segments(limb)
+            .into_iter()
+            .map(|segment| {
+                let transform = self.world().get::<Transform>(segment).unwrap();
+                transform.rotation.to_euler(EulerRot::XYZ).2
+            })
+            .collect()
+    }
+
+    /// A limb's segment rotations at each of the given times (seconds since
+    /// the start, ascending).
+    pub fn sample_rotations(&mut self, limb: Entity, times: &[f64]) -> Vec<Vec<f32>> {
+        times
+            .iter()
+            .map(|&time| {
+                self.advance_to(time);
+                self.segment_rotations(limb)
+            })
+            .collect()
+    }
+
+    fn children_with<T: Component>(&self, parent: Entity) -> Vec<Entity> {
+        let world = self.world();
+        world
+            .get::<Children>(parent)
+            .map(|children| {
+                children
+                    .iter()
+                    .filter(|child| world.get::<T>(*child).is_some())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,489 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::controls::ControlScheme;
+use crate::midi_clock::MidiClock;
+use crate::oscillator::{Frequency, Oscillator};
+use crate::piano::{Piano, OCTAVES};
+
+/// Taps further apart than this start a new tap-tempo measurement.
+const TAP_TIMEOUT_SECS: f64 = 2.0;
+/// How many recent taps are averaged.
+const MAX_TAPS: usize = 5;
+
+/// A note length, as used for beat-locked oscillators.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NoteValue {
+    Whole,
+    Half,
+    Quarter,
+    Eighth,
+    Sixteenth,
+    ThirtySecond,
+}
+
+impl NoteValue {
+    pub const ALL: [NoteValue; 6] = [
+        NoteValue::Whole,
+        NoteValue::Half,
+        NoteValue::Quarter,
+        NoteValue::Eighth,
+        NoteValue::Sixteenth,
+        NoteValue::ThirtySecond,
+    ];
+
+    /// Length in quarter-note beats.
+    pub fn beats(self) -> f32 {
+        match self {
+            NoteValue::Whole => 4.0,
+            NoteValue::Half => 2.0,
+            NoteValue::Quarter => 1.0,
+            NoteValue::Eighth => 0.5,
+            NoteValue::Sixteenth => 0.25,
+            NoteValue::ThirtySecond => 0.125,
+        }
+    }
+
+    /// The next shorter note value, if any.
+    pub fn shorter(self) -> Option<Self> {
+        let i = Self::ALL.iter().position(|v| *v == self)?;
+        Self::ALL.get(i + 1).copied()
+    }
+
+    /// The next longer note value, if any.
+    pub fn longer(self) -> Option<Self> {
+        let i = Self::ALL.iter().position(|v| *v == self)?;
+        i.checked_sub(1).map(|i| Self::ALL[i])
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NoteModifier {
+    #[default]
+    Straight,
+    /// One and a half times as long.
+    Dotted,
+    /// Three in the time of two.
+    Triplet,
+}
+
+impl NoteModifier {
+    pub const ALL: [NoteModifier; 3] = [
+        NoteModifier::Straight,
+        NoteModifier::Dotted,
+        NoteModifier::Triplet,
+    ];
+
+    fn factor(self) -> f32 {
+        match self {
+            NoteModifier::Straight => 1.0,
+            NoteModifier::Dotted => 1.5,
+            NoteModifier::Triplet => 2.0 / 3.0,
+        }
+    }
+}
+
+/// One oscillator cycle per note of this length, e.g. a dotted eighth.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BeatDivision {
+    pub value: NoteValue,
+    #[serde(default)]
+    pub modifier: NoteModifier,
+}
+
+impl Default for BeatDivision {
+    fn default() -> Self {
+        Self::new(NoteValue::Quarter)
+    }
+}
+
+impl BeatDivision {
+    pub fn new(value: NoteValue) -> Self {
+        Self {
+            value,
+            modifier: NoteModifier::Straight,
+        }
+    }
+
+    /// Length of one cycle in quarter-note beats.
+    pub fn beats(&self) -> f32 {
+        self.value.beats() * self.modifier.factor()
+    }
+
+    /// Oscillator frequency (Hz) at the given tempo.
+    pub fn frequency(&self, bpm: f32) -> f32 {
+        bpm / 60.0 / self.beats()
+    }
+}
+
+/// The global musical clock.
+///
+/// Tempo is in quarter-note beats per minute and glides between values with
+/// the same smoothing as oscillator frequencies. Position is counted in
+/// quarter-note beats since the start.
+#[derive(Resource, Debug, Clone)]
+pub struct Transport {
+    /// Tempo in beats per second.
+    tempo: Frequency,
+    pub beats_per_bar: u32,
+    pub beat_unit: u32,
+    playing: bool,
+    position: f64,
+    taps: Vec<f64>,
+    /// Following an external clock rather than running on its own.
+    synced: bool,
+}
+
+impl Default for Transport {
+    fn default() -> Self {
+        Self::new(120.0)
+    }
+}
+
+impl Transport {
+    pub fn new(bpm: f32) -> Self {
+        let mut tempo = Frequency::new(bpm / 60.0);
+        tempo.set_tau(Duration::from_millis(500));
+        Self {
+            tempo,
+            beats_per_bar: 4,
+            beat_unit: 4,
+            playing: true,
+            position: 0.0,
+            taps: Vec::new(),
+            synced: false,
+        }
+    }
+
+    /// The current (possibly gliding) tempo.
+    pub fn bpm(&self) -> f32 {
+        self.tempo.current() * 60.0
+    }
+
+    pub fn target_bpm(&self) -> f32 {
+        self.tempo.target() * 60.0
+    }
+
+    /// Glide to a new tempo.
+    pub fn set_bpm(&mut self, bpm: f32) {
+        self.tempo.set_target(bpm / 60.0);
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    pub fn play(&mut self) {
+        self.playing = true;
+    }
+
+    pub fn stop(&mut self) {
+        self.playing = false;
+    }
+
+    /// Position in quarter-note beats.
+    pub fn position(&self) -> f64 {
+        self.position
+    }
+
+    pub fn set_position(&mut self, beats: f64) {
+        self.position = beats.max(0.0);
+    }
+
+    /// Zero-based bar and the beat (in time-signature units) within it.
+    pub fn bar_and_beat(&self) -> (u64, f64) {
+        let unit = 4.0 / self.beat_unit.max(1) as f64;
+        let beats = self.position / unit;
+        let per_bar = self.beats_per_bar.max(1) as f64;
+        ((beats / per_bar).floor() as u64, beats % per_bar)
+    }
+
+    pub fn is_synced(&self) -> bool {
+        self.synced
+    }
+
+    /// Follow an external clock: jump straight to its position, tempo and
+    /// running state.
+    pub fn follow(&mut self, position: f64, bpm: f32, playing: bool) {
+        self.synced = true;
+        self.position = position.max(0.0);
+        self.tempo.jump(bpm / 60.0);
+        self.playing = playing;
+    }
+
+    /// Run on the internal clock again, carrying on from where the external
+    /// clock left off.
+    pub fn release(&mut self) {
+        self.synced = false;
+    }
+
+    /// Advance by dt seconds, integrating the gliding tempo.
+    pub fn advance(&mut self, dt: f32) {
+        if dt <= 0.0 {
+            return;
+        }
+        let f0 = self.tempo.current();
+        let f1 = self.tempo.update(dt);
+        if self.playing {
+            self.position += (0.5 * (f0 + f1) * dt) as f64;
+        }
+    }
+
+    /// Register a tap at `now` (seconds). Once two or more taps are close
+    /// enough together, returns the tapped tempo (BPM).
+    pub fn tap(&mut self, now: f64) -> Option<f32> {
+        if self
+            .taps
+            .last()
+            .is_some_and(|last| now - last > TAP_TIMEOUT_SECS)
+        {
+            self.taps.clear();
+        }
+        self.taps.push(now);
+        if self.taps.len() > MAX_TAPS {
+            self.taps.remove(0);
+        }
+        let (first, last) = (self.taps.first()?, self.taps.last()?);
+        let intervals = self.taps.len() - 1;
+        if intervals == 0 {
+            return None;
+        }
+        let interval = (last - first) / intervals as f64;
+        Some((60.0 / interval) as f32)
+    }
+}
+
+/// Advance the transport by real frame time.
+pub fn transport_tick(time: Res<Time>, mut transport: ResMut<Transport>) {
+    transport.advance(time.delta_secs());
+}
+
+/// Keyboard transport controls:
+/// - Space: play / stop
+/// - T: tap tempo (not while the piano is on)
+pub fn transport_user_update(
+    keys: Res<ButtonInput<KeyCode>>,
+    time: Res<Time<Real>>,
+    scheme: Res<ControlScheme>,
+    piano: Res<Piano>,
+    mut transport: ResMut<Transport>,
+) {
+    if keys.just_pressed(KeyCode::Space) {
+        if transport.is_playing() {
+            transport.stop();
+        } else {
+            transport.play();
+        }
+    }
+    if !piano.enabled
+        && keys.just_pressed(KeyCode::KeyT)
+        && let Some(bpm) = transport.tap(time.elapsed_secs_f64())
+    {
+        transport.set_bpm(bpm.clamp(scheme.tempo.min, scheme.tempo.max));
+    }
+}
+
+/// Set the phase of every beat-locked oscillator from the transport position.
+pub fn lock_oscillators_to_transport(transport: Res<Transport>, mut q: Query<&mut Oscillator>) {
+    let bpm = transport.bpm();
+    let position = transport.position();
+    for mut osc in &mut q {
+        if osc.beat_division().is_some() {
+            osc.lock_to_beat(position, bpm);
+        }
+    }
+}
+
+/// Small transport window: tempo, time signature, play / stop, position and
+/// MIDI clock sync, plus the keyboard piano. Tempo and play / stop are locked
+/// while synced.
+pub fn transport_panel(
+    mut contexts: EguiContexts,
+    scheme: Res<ControlScheme>,
+    mut transport: ResMut<Transport>,
+    mut clock: ResMut<MidiClock>,
+    mut piano: ResMut<Piano>,
+) -> Result {
+    let ctx = contexts.ctx_mut()?;
+    egui::Window::new("Transport")
+        .default_pos([10.0, 40.0])
+        .resizable(false)
+        .show(ctx, |ui| {
+            let synced = transport.is_synced();
+            let mut bpm = transport.target_bpm();
+            if ui
+                .add_enabled(
+                    !synced,
+                    egui::Slider::new(&mut bpm, scheme.tempo.range()).text("BPM"),
+                )
+                .changed()
+            {
+                transport.set_bpm(bpm);
+            }
+            ui.horizontal(|ui| {
+                ui.label("Time signature");
+                ui.add(egui::DragValue::new(&mut transport.beats_per_bar).range(1..=32));
+                ui.label("/");
+                egui::ComboBox::from_id_salt("beat unit")
+                    .selected_text(transport.beat_unit.to_string())
+                    .width(40.0)
+                    .show_ui(ui, |ui| {
+                        for unit in [2, 4, 8, 16] {
+                            ui.selectable_value(&mut transport.beat_unit, unit, unit.to_string());
+                        }
+                    });
+            });
+            ui.horizontal(|ui| {
+                if synced {
+                    ui.disable();
+                }
+                let label = if transport.is_playing() {
+                    "Stop"
+                } else {
+                    "Play"
+                };
+                if ui.button(label).clicked() {
+                    if transport.is_playing() {
+                        transport.stop();
+                    } else {
+                        transport.play();
+                    }
+                }
+                if ui.button("Rewind").clicked() {
+                    transport.set_position(0.0);
+                }
+                let (bar, beat) = transport.bar_and_beat();
+                ui.label(format!(
+                    "{}:{} @ {:.1} BPM",
+                    bar + 1,
+                    beat.floor() as u64 + 1,
+                    transport.bpm()
+                ));
+            });
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut clock.follow, "Follow MIDI clock");
+                if synced {
+                    ui.label("synced");
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut piano.enabled, "Keyboard piano (F3)");
+                ui.label("octave");
+                ui.add(egui::DragValue::new(&mut piano.octave).range(OCTAVES));
+            });
+        });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-3
+    }
+
+    #[test]
+    fn taps_average_the_latest_intervals() {
+        let mut transport = Transport::default();
+        assert_eq!(transport.tap(0.0), None);
+        for now in [0.5, 1.0, 1.5, 2.0] {
+            assert!(close(transport.tap(now).unwrap(), 120.0));
+        }
+        // The first tap falls out of the window: four intervals over 1.9 s.
+        let bpm = transport.tap(2.4).unwrap();
+        assert!(close(bpm, 60.0 / (1.9 / 4.0)), "{bpm}");
+    }
+
+    #[test]
+    fn a_long_gap_starts_tapping_afresh() {
+        let mut transport = Transport::default();
+        transport.tap(0.0);
+        transport.tap(0.5);
+        assert_eq!(transport.tap(0.5 + TAP_TIMEOUT_SECS + 0.1), None);
+        assert!(close(transport.tap(0.75 + TAP_TIMEOUT_SECS + 0.1).unwrap(), 240.0));
+    }
+
+    #[test]
+    fn tempo_glides_toward_its_target() {
+        let mut transport = Transport::new(120.0);
+        transport.set_bpm(60.0);
+        assert_eq!(transport.bpm(), 120.0);
+        assert_eq!(transport.target_bpm(), 60.0);
+        // One time constant covers 1 - 1/e of the way.
+        transport.advance(0.5);
+        let bpm = transport.bpm();
+        assert!(close(bpm, 60.0 + 60.0 / std::f32::consts::E), "{bpm}");
+        for _ in 0..100 {
+            transport.advance(0.1);
+        }
+        assert!(close(transport.bpm(), 60.0));
+    }
+
+    #[test]
+    fn dotted_and_triplet_divisions() {
+        let dotted_eighth = BeatDivision {
+            value: NoteValue::Eighth,
+            modifier: NoteModifier::Dotted,
+        };
+        assert!(close(dotted_eighth.beats(), 0.75));
+        assert!(close(dotted_eighth.frequency(120.0), 2.0 / 0.75));
+
+        let triplet_quarter = BeatDivision {
+            value: NoteValue::Quarter,
+            modifier: NoteModifier::Triplet,
+        };
+        assert!(close(triplet_quarter.beats(), 2.0 / 3.0));
+        assert!(close(triplet_quarter.frequency(120.0), 3.0));
+
+        assert!(close(BeatDivision::default().frequency(120.0), 2.0));
+    }
+
+    #[test]
+    fn bars_turn_over_at_their_boundaries() {
+        let mut transport = Transport::default();
+        transport.set_position(3.5);
+        assert_eq!(transport.bar_and_beat(), (0, 3.5));
+        transport.set_position(4.0);
+        assert_eq!(transport.bar_and_beat(), (1, 0.0));
+        transport.set_position(8.0);
+        assert_eq!(transport.bar_and_beat(), (2, 0.0));
+
+        // 6/8: six eighths, or three quarter-note beats, to the bar.
+        transport.beats_per_bar = 6;
+        transport.beat_unit = 8;
+        transport.set_position(3.0);
+        assert_eq!(transport.bar_and_beat(), (1, 0.0));
+        transport.set_position(2.5);
+        assert_eq!(transport.bar_and_beat(), (0, 5.0));
+    }
+
+    #[test]
+    fn stopping_holds_the_position() {
+        let mut transport = Transport::new(120.0);
+        transport.stop();
+        transport.advance(1.0);
+        assert_eq!(transport.position(), 0.0);
+        transport.play();
+        transport.advance(1.0);
+        assert!(close(transport.position() as f32, 2.0));
+    }
+
+    #[test]
+    fn following_a_clock_jumps_and_releasing_carries_on() {
+        let mut transport = Transport::new(120.0);
+        transport.follow(16.0, 90.0, false);
+        assert!(transport.is_synced());
+        assert_eq!(transport.position(), 16.0);
+        assert!(close(transport.bpm(), 90.0));
+        assert!(!transport.is_playing());
+
+        transport.release();
+        assert!(!transport.is_synced());
+        transport.play();
+        transport.advance(2.0);
+        assert!(close(transport.position() as f32, 19.0));
+    }
+}
@@ -0,0 +1,644 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::limb::Limb;
+use crate::oscillator::{Gate, Oscillator};
+use crate::selection::Selection;
+
+/// A note starting or stopping.
+///
+/// Without a target, notes play on the selected creature, or with nothing
+/// selected on every creature with a VoiceAllocator. Creatures without one
+/// play monophonically.
+#[derive(Message, Debug, Clone, Copy, PartialEq)]
+pub struct Note {
+    pub target: Option<Entity>,
+    /// MIDI note number; 60 is middle C.
+    pub pitch: u8,
+    /// 0..1. Zero releases the note, as in MIDI.
+    pub velocity: f32,
+}
+
+impl Note {
+    pub fn on(pitch: u8, velocity: f32) -> Self {
+        Self {
+            target: None,
+            pitch,
+            velocity,
+        }
+    }
+
+    pub fn off(pitch: u8) -> Self {
+        Self::on(pitch, 0.0)
+    }
+
+    /// Parse a raw MIDI note on / off message on any channel.
+    pub fn parse_midi(bytes: &[u8]) -> Option<Self> {
+        match *bytes {
+            [status, pitch, velocity, ..] if status & 0xF0 == 0x90 => {
+                Some(Self::on(pitch & 0x7F, (velocity & 0x7F) as f32 / 127.0))
+            }
+            [status, pitch, ..] if status & 0xF0 == 0x80 => Some(Self::off(pitch & 0x7F)),
+            _ => None,
+        }
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.velocity > 0.0
+    }
+}
+
+/// How notes map to limb frequencies: the reference note moves at the
+/// reference frequency, and each octave up doubles it.
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct NoteMapping {
+    pub reference_pitch: u8,
+    pub reference_hz: f32,
+}
+
+impl Default for NoteMapping {
+    fn default() -> Self {
+        Self {
+            reference_pitch: 60,
+            reference_hz: 1.0,
+        }
+    }
+}
+
+impl NoteMapping {
+    pub fn frequency(&self, pitch: u8) -> f32 {
+        let semitones = pitch as f32 - self.reference_pitch as f32;
+        self.reference_hz * 2f32.powf(semitones / 12.0)
+    }
+}
+
+/// Attack, decay and release times (seconds) and the sustain level (0..1).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Adsr {
+    pub attack: f32,
+    pub decay: f32,
+    pub sustain: f32,
+    pub release: f32,
+}
+
+impl Default for Adsr {
+    fn default() -> Self {
+        Self {
+            attack: 0.05,
+            decay: 0.2,
+            sustain: 0.7,
+            release: 0.5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum Stage {
+    #[default]
+    Idle,
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+}
+
+/// ADSR envelope state for one voice.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Envelope {
+    stage: Stage,
+    level: f32,
+    velocity: f32,
+}
+
+impl Envelope {
+    /// Start (or restart) the attack from the current level.
+    pub fn open(&mut self, velocity: f32) {
+        self.stage = Stage::Attack;
+        self.velocity = velocity;
+    }
+
+    pub fn close(&mut self) {
+        if self.stage != Stage::Idle {
+            self.stage = Stage::Release;
+        }
+    }
+
+    /// Advance by dt seconds, returning the output level.
+    pub fn tick(&mut self, adsr: &Adsr, dt: f32) -> f32 {
+        let rate = |secs: f32| dt / secs.max(1e-3);
+        match self.stage {
+            Stage::Idle => self.level = 0.0,
+            Stage::Attack => {
+                self.level += rate(adsr.attack);
+                if self.level >= 1.0 {
+                    self.level = 1.0;
+                    self.stage = Stage::Decay;
+                }
+            }
+            Stage::Decay => {
+                self.level -= rate(adsr.decay) * (1.0 - adsr.sustain);
+                if self.level <= adsr.sustain {
+                    self.level = adsr.sustain;
+                    self.stage = Stage::Sustain;
+                }
+            }
+            Stage::Sustain => self.level = adsr.sustain,
+            Stage::Release => {
+                self.level -= rate(adsr.release);
+                if self.level <= 0.0 {
+                    self.level = 0.0;
+                    self.stage = Stage::Idle;
+                }
+            }
+        }
+        self.level * self.velocity
+    }
+}
+
+/// How a new note picks a limb.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VoiceMode {
+    /// Take the next free limb in turn; with none free, steal the next one.
+    #[default]
+    RoundRobin,
+    /// Take the first free limb; with none free, drop the note.
+    LowestFree,
+    /// Take the first free limb; with none free, steal the oldest note.
+    StealOldest,
+}
+
+impl VoiceMode {
+    pub const ALL: [VoiceMode; 3] = [
+        VoiceMode::RoundRobin,
+        VoiceMode::LowestFree,
+        VoiceMode::StealOldest,
+    ];
+}
+
+/// Play a creature like a polyphonic synth: each held note takes one limb,
+/// sets its frequency and opens its envelope. Limbs are silent between notes.
+#[derive(Component, Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[require(Voices)]
+pub struct VoiceAllocator {
+    #[serde(default)]
+    pub mode: VoiceMode,
+    #[serde(default)]
+    pub envelope: Adsr,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Voice {
+    note: Option<u8>,
+    /// When the note started, for stealing the oldest.
+    started: u64,
+    envelope: Envelope,
+}
+
+/// Runtime state of a VoiceAllocator: one voice per limb, in limb order.
+#[derive(Component, Debug, Clone, Default)]
+pub struct Voices {
+    voices: Vec<Voice>,
+    next: usize,
+    notes_started: u64,
+}
+
+impl Voices {
+    fn allocate(&mut self, mode: VoiceMode) -> Option<usize> {
+        let count = self.voices.len();
+        let free = |voice: &Voice| voice.note.is_none();
+        match mode {
+            VoiceMode::RoundRobin => {
+                if count == 0 {
+                    return None;
+                }
+                let index = (0..count)
+                    .map(|offset| (self.next + offset) % count)
+                    .find(|i| free(&self.voices[*i]))
+                    .unwrap_or(self.next % count);
+                self.next = (index + 1) % count;
+                Some(index)
+            }
+            VoiceMode::LowestFree => self.voices.iter().position(free),
+            VoiceMode::StealOldest => self
+                .voices
+                .iter()
+                .position(free)
+                .or_else(|| (0..count).min_by_key(|i| self.voices[*i].started)),
+        }
+    }
+
+    /// Start a note, returning the voice playing it. A note already held
+    /// restarts on the same voice.
+    pub fn note_on(&mut self, mode: VoiceMode, pitch: u8, velocity: f32) -> Option<usize> {
+        let index = self
+            .voices
+            .iter()
+            .position(|voice| voice.note == Some(pitch))
+            .or_else(|| self.allocate(mode))?;
+        self.notes_started += 1;
+        let voice = &mut self.voices[index];
+        voice.note = Some(pitch);
+        voice.started = self.notes_started;
+        voice.envelope.open(velocity);
+        Some(index)
+    }
+
+    /// Release a note, returning the voice that was playing it.
+    pub fn note_off(&mut self, pitch: u8) -> Option<usize> {
+        let index = self
+            .voices
+            .iter()
+            .position(|voice| voice.note == Some(pitch))?;
+        let voice = &mut self.voices[index];
+        voice.note = None;
+        voice.envelope.close();
+        Some(index)
+    }
+}
+
+/// Allocate notes to limbs, follow gates, and apply each voice's envelope to
+/// its limb's oscillator.
+pub fn play_voices(
+    time: Res<Time>,
+    mapping: Res<NoteMapping>,
+    selection: Res<Selection>,
+    mut notes: MessageReader<Note>,
+    mut gates: MessageReader<Gate>,
+    mut creatures: Query<(Entity, &VoiceAllocator, &mut Voices, &Children)>,
+    mut oscillators: Query<&mut Oscillator, With<Limb>>,
+) {
+    let notes: Vec<Note> = notes.read().copied().collect();
+    let gates: Vec<Gate> = gates.read().copied().collect();
+    let dt = time.delta_secs();
+    let selected = selection.creature;
+
+    for (creature, allocator, mut voices, children) in &mut creatures {
+        let limbs: Vec<Entity> = children
+            .iter()
+            .filter(|child| oscillators.contains(*child))
+            .collect();
+        voices.voices.resize(limbs.len(), Voice::default());
+
+        for note in &notes {
+            // Released wherever it's held, even if the selection has moved
+            // since it was played.
+            if !note.is_on() {
+                if note.target.is_none_or(|t| t == creature) {
+                    voices.note_off(note.pitch);
+                }
+                continue;
+            }
+            if note.target.or(selected).is_some_and(|t| t != creature) {
+                continue;
+            }
+            let Some(index) = voices.note_on(allocator.mode, note.pitch, note.velocity) else {
+                continue;
+            };
+            if let Ok(mut osc) = oscillators.get_mut(limbs[index]) {
+                osc.set_frequency(mapping.frequency(note.pitch));
+                if osc.retrigger() {
+                    osc.reset_phase();
+                }
+            }
+        }
+
+        // Gates (e.g. from a sequencer) open envelopes without a note.
+        for gate in &gates {
+            for (voice, limb) in voices.voices.iter_mut().zip(&limbs) {
+                if gate.target != creature && gate.target != *limb {
+                    continue;
+                }
+                if gate.open {
+                    voice.envelope.open(1.0);
+                } else if voice.note.is_none() {
+                    voice.envelope.close();
+                }
+            }
+        }
+
+        for (voice, limb) in voices.voices.iter_mut().zip(&limbs) {
+            let level = voice.envelope.tick(&allocator.envelope, dt);
+            if let Ok(mut osc) = oscillators.get_mut(*limb) {
+                osc.set_gain(level);
+            }
+        }
+    }
+}
+
+/// A note held down on a monophonic creature, and the creature (or limb)
+/// its gate was opened on.
+#[derive(Debug, Clone, Copy)]
+pub struct HeldNote {
+    pitch: u8,
+    creature: Entity,
+    target: Entity,
+}
+
+/// Play notes on a creature without a VoiceAllocator as a monophonic synth:
+/// the latest held note sets the frequency of its limbs (or just the selected
+/// limb) and opens a gate on them. Notes are released on whatever they were
+/// played on, even if the selection has moved since.
+pub fn play_monophonic(
+    mapping: Res<NoteMapping>,
+    selection: Res<Selection>,
+    mut notes: MessageReader<Note>,
+    mut held: Local<Vec<HeldNote>>,
+    allocators: Query<(), With<VoiceAllocator>>,
+    mut limbs: Query<(Entity, &ChildOf, &mut Oscillator), With<Limb>>,
+    mut gates: MessageWriter<Gate>,
+) {
+    let mut set_pitch = |target: Entity, pitch: u8| {
+        for (limb, child_of, mut osc) in &mut limbs {
+            if limb == target || child_of.parent() == target {
+                osc.set_frequency(mapping.frequency(pitch));
+            }
+        }
+    };
+
+    for note in notes.read() {
+        if note.is_on() {
+            let Some(creature) = note.target.or(selection.creature) else {
+                continue;
+            };
+            if allocators.contains(creature) {
+                continue;
+            }
+            let target = selection
+                .limb
+                .filter(|_| selection.creature == Some(creature))
+                .unwrap_or(creature);
+            held.retain(|h| h.pitch != note.pitch || h.target != target);
+            held.push(HeldNote {
+                pitch: note.pitch,
+                creature,
+                target,
+            });
+            set_pitch(target, note.pitch);
+            gates.write(Gate { target, open: true });
+            continue;
+        }
+
+        let Some(index) = held
+            .iter()
+            .rposition(|h| h.pitch == note.pitch && note.target.is_none_or(|c| c == h.creature))
+        else {
+            continue;
+        };
+        let released = held.remove(index);
+        // A later note on the same target is still playing.
+        if held[index..].iter().any(|h| h.target == released.target) {
+            continue;
+        }
+        // Fall back to the previous held note without a new gate.
+        match held[..index]
+            .iter()
+            .rev()
+            .find(|h| h.target == released.target)
+        {
+            Some(previous) => set_pitch(released.target, previous.pitch),
+            None => {
+                gates.write(Gate {
+                    target: released.target,
+                    open: false,
+                });
+            }
+        }
+    }
+}
+
+/// Give limbs back their full amplitude when a creature stops being played
+/// as a synth.
+pub fn reset_voice_gains(
+    mut removed: RemovedComponents<VoiceAllocator>,
+    children: Query<&Children>,
+    mut oscillators: Query<&mut Oscillator, With<Limb>>,
+) {
+    for creature in removed.read() {
+        let Ok(children) = children.get(creature) else {
+            continue;
+        };
+        for child in children.iter() {
+            if let Ok(mut osc) = oscillators.get_mut(child) {
+                osc.set_gain(1.0);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::message::Messages;
+
+    fn voices(count: usize) -> Voices {
+        Voices {
+            voices: vec![Voice::default(); count],
+            ..default()
+        }
+    }
+
+    #[test]
+    fn round_robin_takes_voices_in_turn() {
+        let mut voices = voices(3);
+        let mode = VoiceMode::RoundRobin;
+        assert_eq!(voices.note_on(mode, 60, 1.0), Some(0));
+        assert_eq!(voices.note_on(mode, 62, 1.0), Some(1));
+        assert_eq!(voices.note_on(mode, 64, 1.0), Some(2));
+        // All busy: steal the next in turn.
+        assert_eq!(voices.note_on(mode, 65, 1.0), Some(0));
+        assert_eq!(voices.note_off(62), Some(1));
+        assert_eq!(voices.note_on(mode, 67, 1.0), Some(1));
+        // A held note restarts where it is.
+        assert_eq!(voices.note_on(mode, 64, 1.0), Some(2));
+    }
+
+    #[test]
+    fn lowest_free_drops_notes_when_full() {
+        let mut voices = voices(2);
+        let mode = VoiceMode::LowestFree;
+        assert_eq!(voices.note_on(mode, 60, 1.0), Some(0));
+        assert_eq!(voices.note_on(mode, 62, 1.0), Some(1));
+        assert_eq!(voices.note_on(mode, 64, 1.0), None);
+        assert_eq!(voices.note_off(64), None);
+        voices.note_off(60);
+        assert_eq!(voices.note_on(mode, 64, 1.0), Some(0));
+    }
+
+    #[test]
+    fn steal_oldest_takes_the_longest_held_voice() {
+        let mut voices = voices(2);
+        let mode = VoiceMode::StealOldest;
+        assert_eq!(voices.note_on(mode, 60, 1.0), Some(0));
+        assert_eq!(voices.note_on(mode, 62, 1.0), Some(1));
+        assert_eq!(voices.note_on(mode, 64, 1.0), Some(0));
+        assert_eq!(voices.note_on(mode, 65, 1.0), Some(1));
+        assert_eq!(voices.note_off(60), None);
+        assert_eq!(voices.note_off(64), Some(0));
+    }
+
+    #[test]
+    fn envelope_runs_through_its_stages() {
+        let adsr = Adsr {
+            attack: 0.1,
+            decay: 0.2,
+            sustain: 0.5,
+            release: 0.4,
+        };
+        let mut envelope = Envelope::default();
+        let tick = |envelope: &mut Envelope| envelope.tick(&adsr, 0.05);
+        assert_eq!(tick(&mut envelope), 0.0);
+
+        envelope.open(0.8);
+        let levels: Vec<f32> = (0..8).map(|_| tick(&mut envelope)).collect();
+        // Attack to full, decay to sustain, hold, scaled by velocity.
+        let expected = [0.5, 1.0, 0.875, 0.75, 0.625, 0.5, 0.5, 0.5].map(|l| l * 0.8);
+        for (level, expected) in levels.iter().zip(expected) {
+            assert!((level - expected).abs() < 1e-5, "{levels:?}");
+        }
+
+        envelope.close();
+        let levels: Vec<f32> = (0..5).map(|_| tick(&mut envelope)).collect();
+        let expected = [0.375, 0.25, 0.125, 0.0, 0.0].map(|l| l * 0.8);
+        for (level, expected) in levels.iter().zip(expected) {
+            assert!((level - expected).abs() < 1e-5, "{levels:?}");
+        }
+        assert_eq!(envelope.stage, Stage::Idle);
+
+        // Closing an idle envelope leaves it idle.
+        envelope.close();
+        assert_eq!(envelope.stage, Stage::Idle);
+    }
+
+    #[test]
+    fn midi_note_messages_parse_on_any_channel() {
+        let on = Note::parse_midi(&[0x93, 60, 127]).unwrap();
+        assert_eq!(on, Note::on(60, 1.0));
+        assert!(on.is_on());
+
+        // Note on with velocity 0 is a note off.
+        let off = Note::parse_midi(&[0x90, 60, 0]).unwrap();
+        assert!(!off.is_on());
+        assert_eq!(Note::parse_midi(&[0x8F, 61, 64]), Some(Note::off(61)));
+
+        // Other messages, and truncated ones, are ignored.
+        assert_eq!(Note::parse_midi(&[0xB0, 7, 100]), None);
+        assert_eq!(Note::parse_midi(&[0x90, 60]), None);
+        assert_eq!(Note::parse_midi(&[]), None);
+    }
+
+    /// Two monophonic creatures with one limb each, and a persistent
+    /// play_monophonic to send notes through.
+    struct Mono {
+        world: World,
+        system: bevy::ecs::system::SystemId,
+        creatures: [Entity; 2],
+        limbs: [Entity; 2],
+    }
+
+    impl Mono {
+        fn new() -> Self {
+            let mut world = World::new();
+            world.init_resource::<NoteMapping>();
+            world.init_resource::<Selection>();
+            world.init_resource::<Messages<Note>>();
+            world.init_resource::<Messages<Gate>>();
+            let limbs = [world.spawn(Limb).id(), world.spawn(Limb).id()];
+            let creatures = limbs.map(|limb| world.spawn_empty().add_child(limb).id());
+            let system = world.register_system(play_monophonic);
+            Self {
+                world,
+                system,
+                creatures,
+                limbs,
+            }
+        }
+
+        fn select(&mut self, creature: usize) {
+            self.world.resource_mut::<Selection>().creature = Some(self.creatures[creature]);
+        }
+
+        /// Play a note, returning the gates opened (true) and closed
+        /// (false), and on which creature.
+        fn play(&mut self, note: Note) -> Vec<(Entity, bool)> {
+            self.world.write_message(note);
+            self.world.run_system(self.system).unwrap();
+            self.world
+                .resource_mut::<Messages<Gate>>()
+                .drain()
+                .map(|gate| (gate.target, gate.open))
+                .collect()
+        }
+
+        fn pitch(&self, limb: usize) -> f32 {
+            let osc = self.world.get::<Oscillator>(self.limbs[limb]).unwrap();
+            osc.target_frequency()
+        }
+    }
+
+    #[test]
+    fn the_latest_held_note_plays() {
+        let mut mono = Mono::new();
+        mono.select(0);
+        let a = mono.creatures[0];
+        let hz = |pitch| NoteMapping::default().frequency(pitch);
+
+        assert_eq!(mono.play(Note::on(60, 1.0)), [(a, true)]);
+        assert_eq!(mono.play(Note::on(64, 1.0)), [(a, true)]);
+        assert_eq!(mono.pitch(0), hz(64));
+
+        // Releasing the latest falls back to the one before, gate still open.
+        assert!(mono.play(Note::off(64)).is_empty());
+        assert_eq!(mono.pitch(0), hz(60));
+        assert_eq!(mono.play(Note::off(60)), [(a, false)]);
+
+        // Releasing an earlier note leaves the latest playing.
+        mono.play(Note::on(60, 1.0));
+        mono.play(Note::on(67, 1.0));
+        assert!(mono.play(Note::off(60)).is_empty());
+        assert_eq!(mono.pitch(0), hz(67));
+        assert_eq!(mono.play(Note::off(67)), [(a, false)]);
+    }
+
+    #[test]
+    fn notes_are_released_where_they_were_played() {
+        let mut mono = Mono::new();
+        let [a, b] = mono.creatures;
+        mono.select(0);
+        assert_eq!(mono.play(Note::on(60, 1.0)), [(a, true)]);
+
+        mono.select(1);
+        assert_eq!(mono.play(Note::on(64, 1.0)), [(b, true)]);
+        assert_eq!(mono.play(Note::off(60)), [(a, false)]);
+        assert_eq!(mono.play(Note::off(64)), [(b, false)]);
+        assert_eq!(mono.pitch(0), NoteMapping::default().frequency(60));
+    }
+
+    #[test]
+    fn polyphonic_notes_are_released_where_they_were_played() {
+        let mut world = World::new();
+        world.init_resource::<Time>();
+        world.init_resource::<NoteMapping>();
+        world.init_resource::<Selection>();
+        world.init_resource::<Messages<Note>>();
+        world.init_resource::<Messages<Gate>>();
+        let [a, b] = [(); 2].map(|_| {
+            let limb = world.spawn(Limb).id();
+            world.spawn(VoiceAllocator::default()).add_child(limb).id()
+        });
+        let system = world.register_system(play_voices);
+        let held = |world: &World, creature: Entity| {
+            world.get::<Voices>(creature).unwrap().voices[0].note
+        };
+
+        world.resource_mut::<Selection>().creature = Some(a);
+        world.write_message(Note::on(60, 1.0));
+        world.run_system(system).unwrap();
+        assert_eq!(held(&world, a), Some(60));
+        assert_eq!(held(&world, b), None);
+
+        world.resource_mut::<Selection>().creature = Some(b);
+        world.write_message(Note::off(60));
+        world.run_system(system).unwrap();
+        assert_eq!(held(&world, a), None);
+    }
+}